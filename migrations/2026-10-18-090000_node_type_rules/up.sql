@@ -0,0 +1,11 @@
+-- Your SQL goes here
+-- Declarative constraints of the node types, the rules of the built-in types are set by the
+-- database setup
+alter table "node_types" add column "name_policy" text not null default 'required';
+alter table "node_types" add column "allows_description" boolean not null default 1;
+alter table "node_types" add column "allows_root" boolean not null default 1;
+-- Comma separated values of the node types, '*' means any type
+alter table "node_types" add column "parent_types" text not null default '*';
+alter table "node_types" add column "child_types" text not null default '*';
+-- One of 'group', 'subgroup', 'owner' or 'none'
+alter table "node_types" add column "uniqueness_scope" text not null default 'none';
@@ -0,0 +1,8 @@
+drop trigger "nodes_regular_unique_insert";
+drop trigger "nodes_regular_unique_update";
+drop trigger "nodes_owner_unique_insert";
+drop trigger "nodes_owner_unique_update";
+drop trigger "nodes_symlink_subgroup_insert";
+drop trigger "nodes_symlink_subgroup_update";
+drop trigger "nodes_no_self_link_insert";
+drop trigger "nodes_no_cycle_update";
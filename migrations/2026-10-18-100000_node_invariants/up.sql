@@ -0,0 +1,113 @@
+-- The tree invariants are enforced by the database too, so that other tools writing into it
+-- can't corrupt the tree. The ids of the node types are assigned by the database setup, so the
+-- rules are written as triggers that look up the type values instead of partial indexes.
+-- The messages are the names of the matching RelanotesValidationRejection variants.
+
+-- Regular names are unique in the group
+create trigger "nodes_regular_unique_insert" before insert on "nodes"
+when (select "value" from "node_types" where "id" = new."type_id") = 'regular'
+begin
+    select raise(abort, 'DuplicateRegularNode') where exists (
+        select 1 from "nodes" n
+        inner join "subgroups" s on s."id" = n."subgroup_id"
+        where n."type_id" = new."type_id"
+            and n."name" = new."name"
+            and s."group_id" = (select "group_id" from "subgroups" where "id" = new."subgroup_id")
+    );
+end;
+
+create trigger "nodes_regular_unique_update" before update of "name", "type_id", "subgroup_id" on "nodes"
+when (select "value" from "node_types" where "id" = new."type_id") = 'regular'
+begin
+    select raise(abort, 'DuplicateRegularNode') where exists (
+        select 1 from "nodes" n
+        inner join "subgroups" s on s."id" = n."subgroup_id"
+        where n."id" != new."id"
+            and n."type_id" = new."type_id"
+            and n."name" = new."name"
+            and s."group_id" = (select "group_id" from "subgroups" where "id" = new."subgroup_id")
+    );
+end;
+
+-- Sticky notes and inherited nodes are unique in the children of the owner
+create trigger "nodes_owner_unique_insert" before insert on "nodes"
+when (select "value" from "node_types" where "id" = new."type_id") in ('sticky_notes', 'inherited')
+begin
+    select raise(abort, 'DuplicateStickyNote') where exists (
+        select 1 from "nodes" n
+        where n."type_id" = new."type_id"
+            and n."linked_to_id" = new."linked_to_id"
+            and n."name" = new."name"
+    ) and (select "value" from "node_types" where "id" = new."type_id") = 'sticky_notes';
+    select raise(abort, 'DuplicateInheritedNode') where exists (
+        select 1 from "nodes" n
+        where n."type_id" = new."type_id"
+            and n."linked_to_id" = new."linked_to_id"
+            and n."name" = new."name"
+    );
+end;
+
+create trigger "nodes_owner_unique_update" before update of "name", "type_id", "linked_to_id" on "nodes"
+when (select "value" from "node_types" where "id" = new."type_id") in ('sticky_notes', 'inherited')
+begin
+    select raise(abort, 'DuplicateStickyNote') where exists (
+        select 1 from "nodes" n
+        where n."id" != new."id"
+            and n."type_id" = new."type_id"
+            and n."linked_to_id" = new."linked_to_id"
+            and n."name" = new."name"
+    ) and (select "value" from "node_types" where "id" = new."type_id") = 'sticky_notes';
+    select raise(abort, 'DuplicateInheritedNode') where exists (
+        select 1 from "nodes" n
+        where n."id" != new."id"
+            and n."type_id" = new."type_id"
+            and n."linked_to_id" = new."linked_to_id"
+            and n."name" = new."name"
+    );
+end;
+
+-- Symlinks can't target nodes of their own subgroup
+create trigger "nodes_symlink_subgroup_insert" before insert on "nodes"
+when (select "value" from "node_types" where "id" = new."type_id") = 'symlinks'
+begin
+    select raise(abort, 'SymLinkToSameSubgroup')
+    where (select "subgroup_id" from "nodes" where "id" = new."linked_to_id") = new."subgroup_id";
+end;
+
+create trigger "nodes_symlink_subgroup_update" before update of "type_id", "linked_to_id", "subgroup_id" on "nodes"
+begin
+    select raise(abort, 'SymLinkToSameSubgroup')
+    where (select "value" from "node_types" where "id" = new."type_id") = 'symlinks'
+        and (select "subgroup_id" from "nodes" where "id" = new."linked_to_id") = new."subgroup_id";
+    -- The source can't be moved into the subgroup of its symlinks either
+    select raise(abort, 'SymLinkToSameSubgroup') where exists (
+        select 1 from "nodes" n
+        inner join "node_types" t on t."id" = n."type_id"
+        where t."value" = 'symlinks'
+            and n."linked_to_id" = new."id"
+            and n."subgroup_id" = new."subgroup_id"
+    );
+end;
+
+-- The links never form a cycle
+create trigger "nodes_no_self_link_insert" before insert on "nodes"
+when new."linked_to_id" = new."id"
+begin
+    select raise(abort, 'LinkedToItself');
+end;
+
+create trigger "nodes_no_cycle_update" before update of "linked_to_id" on "nodes"
+when new."linked_to_id" is not null
+begin
+    select raise(abort, 'LinkedToItself') where new."linked_to_id" = new."id";
+    select raise(abort, 'CyclicLink') where exists (
+        with recursive "ancestors"("id") as (
+            select new."linked_to_id"
+            union
+            select n."linked_to_id" from "nodes" n
+            inner join "ancestors" a on n."id" = a."id"
+            where n."linked_to_id" is not null
+        )
+        select 1 from "ancestors" where "id" = new."id"
+    );
+end;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+drop index "nodes_name_subgroup_id";
+drop index "nodes_linked_to_id";
+drop index "nodes_subgroup_id";
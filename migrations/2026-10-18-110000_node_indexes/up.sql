@@ -0,0 +1,11 @@
+-- The access paths of the loading and the validation, the triggers use them too.
+-- Subgroups by group are covered by their unique ("group_id", "name") index.
+
+-- The nodes of a subgroup
+create index "nodes_subgroup_id" on "nodes" ("subgroup_id");
+
+-- The children, sticky notes and symlinks of a node
+create index "nodes_linked_to_id" on "nodes" ("linked_to_id");
+
+-- The nodes with a name in the subgroups of a group
+create index "nodes_name_subgroup_id" on "nodes" ("name", "subgroup_id");
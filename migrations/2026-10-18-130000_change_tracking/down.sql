@@ -0,0 +1,13 @@
+-- This file should undo anything in `up.sql`
+drop trigger "nodes_changes_delete";
+drop trigger "nodes_changes_update";
+drop trigger "nodes_changes_insert";
+drop trigger "subgroups_changes_delete";
+drop trigger "subgroups_changes_update";
+drop trigger "subgroups_changes_insert";
+drop trigger "groups_changes_delete";
+drop trigger "groups_changes_update";
+drop trigger "groups_changes_insert";
+drop table "changes";
+drop trigger "nodes_version";
+alter table "nodes" drop column "version";
@@ -0,0 +1,73 @@
+-- Other processes can write into the same database, so the changes are tracked by the database.
+-- The version of a node is bumped by every write, the edits of a stale tree are detected with it.
+alter table "nodes" add column "version" integer not null default 1;
+
+create trigger "nodes_version" after update of "linked_to_id", "type_id", "name", "description", "subgroup_id" on "nodes"
+begin
+    update "nodes" set "version" = old."version" + 1 where "id" = new."id";
+end;
+
+-- The log of the written rows, the loaded models apply only the changes made after they were
+-- loaded. The parent is the group of a subgroup and the subgroup of a node, the moved rows are
+-- logged with the old and the new parent.
+create table "changes" (
+    "id" integer not null primary key autoincrement,
+    "table_name" text not null,
+    "row_id" integer not null,
+    "parent_id" integer
+);
+
+create trigger "groups_changes_insert" after insert on "groups"
+begin
+    insert into "changes" ("table_name", "row_id") values ('groups', new."id");
+end;
+
+create trigger "groups_changes_update" after update on "groups"
+begin
+    insert into "changes" ("table_name", "row_id") values ('groups', new."id");
+end;
+
+create trigger "groups_changes_delete" after delete on "groups"
+begin
+    insert into "changes" ("table_name", "row_id") values ('groups', old."id");
+end;
+
+create trigger "subgroups_changes_insert" after insert on "subgroups"
+begin
+    insert into "changes" ("table_name", "row_id", "parent_id")
+    values ('subgroups', new."id", new."group_id");
+end;
+
+create trigger "subgroups_changes_update" after update on "subgroups"
+begin
+    insert into "changes" ("table_name", "row_id", "parent_id")
+    values ('subgroups', old."id", old."group_id");
+    insert into "changes" ("table_name", "row_id", "parent_id")
+    select 'subgroups', new."id", new."group_id" where new."group_id" != old."group_id";
+end;
+
+create trigger "subgroups_changes_delete" after delete on "subgroups"
+begin
+    insert into "changes" ("table_name", "row_id", "parent_id")
+    values ('subgroups', old."id", old."group_id");
+end;
+
+create trigger "nodes_changes_insert" after insert on "nodes"
+begin
+    insert into "changes" ("table_name", "row_id", "parent_id")
+    values ('nodes', new."id", new."subgroup_id");
+end;
+
+create trigger "nodes_changes_update" after update of "linked_to_id", "type_id", "name", "description", "subgroup_id" on "nodes"
+begin
+    insert into "changes" ("table_name", "row_id", "parent_id")
+    values ('nodes', old."id", old."subgroup_id");
+    insert into "changes" ("table_name", "row_id", "parent_id")
+    select 'nodes', new."id", new."subgroup_id" where new."subgroup_id" != old."subgroup_id";
+end;
+
+create trigger "nodes_changes_delete" after delete on "nodes"
+begin
+    insert into "changes" ("table_name", "row_id", "parent_id")
+    values ('nodes', old."id", old."subgroup_id");
+end;
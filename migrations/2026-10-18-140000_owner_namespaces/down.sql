@@ -0,0 +1,39 @@
+-- This file should undo anything in `up.sql`
+drop trigger "nodes_owner_unique_insert";
+drop trigger "nodes_owner_unique_update";
+
+create trigger "nodes_owner_unique_insert" before insert on "nodes"
+when (select "value" from "node_types" where "id" = new."type_id") in ('sticky_notes', 'inherited')
+begin
+    select raise(abort, 'DuplicateStickyNote') where exists (
+        select 1 from "nodes" n
+        where n."type_id" = new."type_id"
+            and n."linked_to_id" = new."linked_to_id"
+            and n."name" = new."name"
+    ) and (select "value" from "node_types" where "id" = new."type_id") = 'sticky_notes';
+    select raise(abort, 'DuplicateInheritedNode') where exists (
+        select 1 from "nodes" n
+        where n."type_id" = new."type_id"
+            and n."linked_to_id" = new."linked_to_id"
+            and n."name" = new."name"
+    );
+end;
+
+create trigger "nodes_owner_unique_update" before update of "name", "type_id", "linked_to_id" on "nodes"
+when (select "value" from "node_types" where "id" = new."type_id") in ('sticky_notes', 'inherited')
+begin
+    select raise(abort, 'DuplicateStickyNote') where exists (
+        select 1 from "nodes" n
+        where n."id" != new."id"
+            and n."type_id" = new."type_id"
+            and n."linked_to_id" = new."linked_to_id"
+            and n."name" = new."name"
+    ) and (select "value" from "node_types" where "id" = new."type_id") = 'sticky_notes';
+    select raise(abort, 'DuplicateInheritedNode') where exists (
+        select 1 from "nodes" n
+        where n."id" != new."id"
+            and n."type_id" = new."type_id"
+            and n."linked_to_id" = new."linked_to_id"
+            and n."name" = new."name"
+    );
+end;
@@ -0,0 +1,94 @@
+-- Divisors are transparent, so sticky notes and inherited nodes are unique in the namespace of
+-- the nearest owner which is not a divisor. The namespace has the children of the owner and,
+-- recursively, the children of the divisors among them.
+drop trigger "nodes_owner_unique_insert";
+drop trigger "nodes_owner_unique_update";
+
+create trigger "nodes_owner_unique_insert" before insert on "nodes"
+when (select "value" from "node_types" where "id" = new."type_id") in ('sticky_notes', 'inherited')
+begin
+    select case (select "value" from "node_types" where "id" = new."type_id")
+        when 'sticky_notes' then raise(abort, 'DuplicateStickyNote')
+        else raise(abort, 'DuplicateInheritedNode')
+    end
+    where exists (
+        -- From the linked node up through the divisors to the nearest other node
+        with recursive "owners"("id", "linked_to_id", "is_divisor") as (
+            select n."id", n."linked_to_id", t."value" = 'divisor' from "nodes" n
+            inner join "node_types" t on t."id" = n."type_id"
+            where n."id" = new."linked_to_id"
+            union
+            select n."id", n."linked_to_id", t."value" = 'divisor' from "owners" o
+            inner join "nodes" n on n."id" = o."linked_to_id"
+            inner join "node_types" t on t."id" = n."type_id"
+            where o."is_divisor"
+        ),
+        -- The children of the owner (the roots of the subgroup without one) and of the divisors
+        -- among them
+        "namespace"("id", "is_divisor") as (
+            select n."id", t."value" = 'divisor' from "nodes" n
+            inner join "node_types" t on t."id" = n."type_id"
+            where n."linked_to_id" is (
+                    select case when exists (select 1 from "owners")
+                        then (select "id" from "owners" where not "is_divisor")
+                        else new."linked_to_id"
+                    end
+                )
+                and (n."linked_to_id" is not null or n."subgroup_id" = new."subgroup_id")
+            union
+            select n."id", t."value" = 'divisor' from "namespace" m
+            inner join "nodes" n on n."linked_to_id" = m."id"
+            inner join "node_types" t on t."id" = n."type_id"
+            where m."is_divisor"
+        )
+        select 1 from "namespace" m
+        inner join "nodes" n on n."id" = m."id"
+        where n."type_id" = new."type_id"
+            and n."name" = new."name"
+    );
+end;
+
+create trigger "nodes_owner_unique_update" before update of "name", "type_id", "linked_to_id" on "nodes"
+when (select "value" from "node_types" where "id" = new."type_id") in ('sticky_notes', 'inherited')
+begin
+    select case (select "value" from "node_types" where "id" = new."type_id")
+        when 'sticky_notes' then raise(abort, 'DuplicateStickyNote')
+        else raise(abort, 'DuplicateInheritedNode')
+    end
+    where exists (
+        -- From the linked node up through the divisors to the nearest other node
+        with recursive "owners"("id", "linked_to_id", "is_divisor") as (
+            select n."id", n."linked_to_id", t."value" = 'divisor' from "nodes" n
+            inner join "node_types" t on t."id" = n."type_id"
+            where n."id" = new."linked_to_id"
+            union
+            select n."id", n."linked_to_id", t."value" = 'divisor' from "owners" o
+            inner join "nodes" n on n."id" = o."linked_to_id"
+            inner join "node_types" t on t."id" = n."type_id"
+            where o."is_divisor"
+        ),
+        -- The children of the owner (the roots of the subgroup without one) and of the divisors
+        -- among them
+        "namespace"("id", "is_divisor") as (
+            select n."id", t."value" = 'divisor' from "nodes" n
+            inner join "node_types" t on t."id" = n."type_id"
+            where n."linked_to_id" is (
+                    select case when exists (select 1 from "owners")
+                        then (select "id" from "owners" where not "is_divisor")
+                        else new."linked_to_id"
+                    end
+                )
+                and (n."linked_to_id" is not null or n."subgroup_id" = new."subgroup_id")
+            union
+            select n."id", t."value" = 'divisor' from "namespace" m
+            inner join "nodes" n on n."linked_to_id" = m."id"
+            inner join "node_types" t on t."id" = n."type_id"
+            where m."is_divisor"
+        )
+        select 1 from "namespace" m
+        inner join "nodes" n on n."id" = m."id"
+        where n."id" != new."id"
+            and n."type_id" = new."type_id"
+            and n."name" = new."name"
+    );
+end;
@@ -0,0 +1,218 @@
+-- This file should undo anything in `up.sql`
+create table "new_nodes" (
+    "id" integer not null primary key autoincrement,
+    "linked_to_id" integer,
+    "type_id" integer not null,
+    "name" text not null,
+    "description" text,
+    "subgroup_id" integer not null,
+    "version" integer not null default 1,
+    foreign key ("linked_to_id") references "nodes" ("id")
+        on delete restrict,
+    foreign key ("type_id") references "node_types" ("id"),
+    foreign key ("subgroup_id") references "subgroups" ("id")
+        on delete cascade
+);
+
+insert into "new_nodes" ("id", "linked_to_id", "type_id", "name", "description", "subgroup_id", "version")
+select "id", "linked_to_id", "type_id", "name", "description", "subgroup_id", "version" from "nodes";
+
+-- The ids of the deleted nodes are never reused, the change log refers to them
+insert into "sqlite_sequence" ("name", "seq")
+select 'new_nodes', 0 where not exists (select 1 from "sqlite_sequence" where "name" = 'new_nodes');
+update "sqlite_sequence" set "seq" = (
+    select max("seq") from "sqlite_sequence" where "name" in ('nodes', 'new_nodes')
+) where "name" = 'new_nodes';
+
+drop table "nodes";
+alter table "new_nodes" rename to "nodes";
+
+-- The indexes and the triggers were dropped with the old table
+create index "nodes_subgroup_id" on "nodes" ("subgroup_id");
+create index "nodes_linked_to_id" on "nodes" ("linked_to_id");
+create index "nodes_name_subgroup_id" on "nodes" ("name", "subgroup_id");
+
+-- The invariants of the tree
+create trigger "nodes_regular_unique_insert" before insert on "nodes"
+when (select "value" from "node_types" where "id" = new."type_id") = 'regular'
+begin
+    select raise(abort, 'DuplicateRegularNode') where exists (
+        select 1 from "nodes" n
+        inner join "subgroups" s on s."id" = n."subgroup_id"
+        where n."type_id" = new."type_id"
+            and n."name" = new."name"
+            and s."group_id" = (select "group_id" from "subgroups" where "id" = new."subgroup_id")
+    );
+end;
+
+create trigger "nodes_regular_unique_update" before update of "name", "type_id", "subgroup_id" on "nodes"
+when (select "value" from "node_types" where "id" = new."type_id") = 'regular'
+begin
+    select raise(abort, 'DuplicateRegularNode') where exists (
+        select 1 from "nodes" n
+        inner join "subgroups" s on s."id" = n."subgroup_id"
+        where n."id" != new."id"
+            and n."type_id" = new."type_id"
+            and n."name" = new."name"
+            and s."group_id" = (select "group_id" from "subgroups" where "id" = new."subgroup_id")
+    );
+end;
+
+create trigger "nodes_owner_unique_insert" before insert on "nodes"
+when (select "value" from "node_types" where "id" = new."type_id") in ('sticky_notes', 'inherited')
+begin
+    select case (select "value" from "node_types" where "id" = new."type_id")
+        when 'sticky_notes' then raise(abort, 'DuplicateStickyNote')
+        else raise(abort, 'DuplicateInheritedNode')
+    end
+    where exists (
+        -- From the linked node up through the divisors to the nearest other node
+        with recursive "owners"("id", "linked_to_id", "is_divisor") as (
+            select n."id", n."linked_to_id", t."value" = 'divisor' from "nodes" n
+            inner join "node_types" t on t."id" = n."type_id"
+            where n."id" = new."linked_to_id"
+            union
+            select n."id", n."linked_to_id", t."value" = 'divisor' from "owners" o
+            inner join "nodes" n on n."id" = o."linked_to_id"
+            inner join "node_types" t on t."id" = n."type_id"
+            where o."is_divisor"
+        ),
+        -- The children of the owner (the roots of the subgroup without one) and of the divisors
+        -- among them
+        "namespace"("id", "is_divisor") as (
+            select n."id", t."value" = 'divisor' from "nodes" n
+            inner join "node_types" t on t."id" = n."type_id"
+            where n."linked_to_id" is (
+                    select case when exists (select 1 from "owners")
+                        then (select "id" from "owners" where not "is_divisor")
+                        else new."linked_to_id"
+                    end
+                )
+                and (n."linked_to_id" is not null or n."subgroup_id" = new."subgroup_id")
+            union
+            select n."id", t."value" = 'divisor' from "namespace" m
+            inner join "nodes" n on n."linked_to_id" = m."id"
+            inner join "node_types" t on t."id" = n."type_id"
+            where m."is_divisor"
+        )
+        select 1 from "namespace" m
+        inner join "nodes" n on n."id" = m."id"
+        where n."type_id" = new."type_id"
+            and n."name" = new."name"
+    );
+end;
+
+create trigger "nodes_owner_unique_update" before update of "name", "type_id", "linked_to_id" on "nodes"
+when (select "value" from "node_types" where "id" = new."type_id") in ('sticky_notes', 'inherited')
+begin
+    select case (select "value" from "node_types" where "id" = new."type_id")
+        when 'sticky_notes' then raise(abort, 'DuplicateStickyNote')
+        else raise(abort, 'DuplicateInheritedNode')
+    end
+    where exists (
+        -- From the linked node up through the divisors to the nearest other node
+        with recursive "owners"("id", "linked_to_id", "is_divisor") as (
+            select n."id", n."linked_to_id", t."value" = 'divisor' from "nodes" n
+            inner join "node_types" t on t."id" = n."type_id"
+            where n."id" = new."linked_to_id"
+            union
+            select n."id", n."linked_to_id", t."value" = 'divisor' from "owners" o
+            inner join "nodes" n on n."id" = o."linked_to_id"
+            inner join "node_types" t on t."id" = n."type_id"
+            where o."is_divisor"
+        ),
+        -- The children of the owner (the roots of the subgroup without one) and of the divisors
+        -- among them
+        "namespace"("id", "is_divisor") as (
+            select n."id", t."value" = 'divisor' from "nodes" n
+            inner join "node_types" t on t."id" = n."type_id"
+            where n."linked_to_id" is (
+                    select case when exists (select 1 from "owners")
+                        then (select "id" from "owners" where not "is_divisor")
+                        else new."linked_to_id"
+                    end
+                )
+                and (n."linked_to_id" is not null or n."subgroup_id" = new."subgroup_id")
+            union
+            select n."id", t."value" = 'divisor' from "namespace" m
+            inner join "nodes" n on n."linked_to_id" = m."id"
+            inner join "node_types" t on t."id" = n."type_id"
+            where m."is_divisor"
+        )
+        select 1 from "namespace" m
+        inner join "nodes" n on n."id" = m."id"
+        where n."id" != new."id"
+            and n."type_id" = new."type_id"
+            and n."name" = new."name"
+    );
+end;
+
+create trigger "nodes_symlink_subgroup_insert" before insert on "nodes"
+when (select "value" from "node_types" where "id" = new."type_id") = 'symlinks'
+begin
+    select raise(abort, 'SymLinkToSameSubgroup')
+    where (select "subgroup_id" from "nodes" where "id" = new."linked_to_id") = new."subgroup_id";
+end;
+
+create trigger "nodes_symlink_subgroup_update" before update of "type_id", "linked_to_id", "subgroup_id" on "nodes"
+begin
+    select raise(abort, 'SymLinkToSameSubgroup')
+    where (select "value" from "node_types" where "id" = new."type_id") = 'symlinks'
+        and (select "subgroup_id" from "nodes" where "id" = new."linked_to_id") = new."subgroup_id";
+    -- The source can't be moved into the subgroup of its symlinks either
+    select raise(abort, 'SymLinkToSameSubgroup') where exists (
+        select 1 from "nodes" n
+        inner join "node_types" t on t."id" = n."type_id"
+        where t."value" = 'symlinks'
+            and n."linked_to_id" = new."id"
+            and n."subgroup_id" = new."subgroup_id"
+    );
+end;
+
+create trigger "nodes_no_self_link_insert" before insert on "nodes"
+when new."linked_to_id" = new."id"
+begin
+    select raise(abort, 'LinkedToItself');
+end;
+
+create trigger "nodes_no_cycle_update" before update of "linked_to_id" on "nodes"
+when new."linked_to_id" is not null
+begin
+    select raise(abort, 'LinkedToItself') where new."linked_to_id" = new."id";
+    select raise(abort, 'CyclicLink') where exists (
+        with recursive "ancestors"("id") as (
+            select new."linked_to_id"
+            union
+            select n."linked_to_id" from "nodes" n
+            inner join "ancestors" a on n."id" = a."id"
+            where n."linked_to_id" is not null
+        )
+        select 1 from "ancestors" where "id" = new."id"
+    );
+end;
+
+-- The versions and the change log
+create trigger "nodes_version" after update of "linked_to_id", "type_id", "name", "description", "subgroup_id" on "nodes"
+begin
+    update "nodes" set "version" = old."version" + 1 where "id" = new."id";
+end;
+
+create trigger "nodes_changes_insert" after insert on "nodes"
+begin
+    insert into "changes" ("table_name", "row_id", "parent_id")
+    values ('nodes', new."id", new."subgroup_id");
+end;
+
+create trigger "nodes_changes_update" after update of "linked_to_id", "type_id", "name", "description", "subgroup_id" on "nodes"
+begin
+    insert into "changes" ("table_name", "row_id", "parent_id")
+    values ('nodes', old."id", old."subgroup_id");
+    insert into "changes" ("table_name", "row_id", "parent_id")
+    select 'nodes', new."id", new."subgroup_id" where new."subgroup_id" != old."subgroup_id";
+end;
+
+create trigger "nodes_changes_delete" after delete on "nodes"
+begin
+    insert into "changes" ("table_name", "row_id", "parent_id")
+    values ('nodes', old."id", old."subgroup_id");
+end;
@@ -0,0 +1,59 @@
+-- The moved divisor takes its namespace children into the namespace of its new owner, where the
+-- sticky notes and inherited nodes can't repeat the names
+create trigger "nodes_divisor_namespace_update" before update of "linked_to_id" on "nodes"
+when (select "value" from "node_types" where "id" = new."type_id") = 'divisor'
+begin
+    select case "value"
+        when 'sticky_notes' then raise(abort, 'DuplicateStickyNote')
+        else raise(abort, 'DuplicateInheritedNode')
+    end
+    from (
+        -- From the new linked node up through the divisors to the nearest other node
+        with recursive "owners"("id", "linked_to_id", "is_divisor") as (
+            select n."id", n."linked_to_id", t."value" = 'divisor' from "nodes" n
+            inner join "node_types" t on t."id" = n."type_id"
+            where n."id" = new."linked_to_id"
+            union
+            select n."id", n."linked_to_id", t."value" = 'divisor' from "owners" o
+            inner join "nodes" n on n."id" = o."linked_to_id"
+            inner join "node_types" t on t."id" = n."type_id"
+            where o."is_divisor"
+        ),
+        -- The new namespace without the moved divisor, which can still be in it
+        "namespace"("id", "is_divisor") as (
+            select n."id", t."value" = 'divisor' from "nodes" n
+            inner join "node_types" t on t."id" = n."type_id"
+            where n."linked_to_id" is (
+                    select case when exists (select 1 from "owners")
+                        then (select "id" from "owners" where not "is_divisor")
+                        else new."linked_to_id"
+                    end
+                )
+                and (n."linked_to_id" is not null or n."subgroup_id" = new."subgroup_id")
+                and n."id" != new."id"
+            union
+            select n."id", t."value" = 'divisor' from "namespace" m
+            inner join "nodes" n on n."linked_to_id" = m."id"
+            inner join "node_types" t on t."id" = n."type_id"
+            where m."is_divisor" and n."id" != new."id"
+        ),
+        -- The children of the moved divisor and of the divisors among them
+        "moved"("id", "is_divisor") as (
+            select n."id", t."value" = 'divisor' from "nodes" n
+            inner join "node_types" t on t."id" = n."type_id"
+            where n."linked_to_id" = new."id"
+            union
+            select n."id", t."value" = 'divisor' from "moved" m
+            inner join "nodes" n on n."linked_to_id" = m."id"
+            inner join "node_types" t on t."id" = n."type_id"
+            where m."is_divisor"
+        )
+        select t."value" from "moved" m
+        inner join "nodes" n on n."id" = m."id"
+        inner join "node_types" t on t."id" = n."type_id"
+        inner join "nodes" o on o."type_id" = n."type_id" and o."name" = n."name"
+        inner join "namespace" s on s."id" = o."id"
+        where t."value" in ('sticky_notes', 'inherited')
+        limit 1
+    );
+end;
@@ -0,0 +1,4 @@
+create table "groups" (
+    "id" integer generated by default as identity primary key,
+    "name" text not null unique
+);
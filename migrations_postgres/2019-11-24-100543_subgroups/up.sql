@@ -0,0 +1,8 @@
+create table "subgroups" (
+    "id" integer generated by default as identity primary key,
+    "group_id" integer not null,
+    "name" text not null,
+    foreign key ("group_id") references "groups" ("id")
+        on delete cascade,
+    unique ("group_id", "name")
+);
@@ -0,0 +1,5 @@
+create table "node_types" (
+    "id" integer generated by default as identity primary key,
+    "name" text not null,
+    "value" text not null unique
+);
@@ -0,0 +1,13 @@
+create table "nodes" (
+    "id" integer generated by default as identity primary key,
+    "linked_to_id" integer,
+    "type_id" integer not null,
+    "name" text not null,
+    "description" text,
+    "subgroup_id" integer not null,
+    foreign key ("linked_to_id") references "nodes" ("id")
+        on delete restrict,
+    foreign key ("type_id") references "node_types" ("id"),
+    foreign key ("subgroup_id") references "subgroups" ("id")
+        on delete cascade
+);
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+alter table "node_types" drop column "uniqueness_scope";
+alter table "node_types" drop column "child_types";
+alter table "node_types" drop column "parent_types";
+alter table "node_types" drop column "allows_root";
+alter table "node_types" drop column "allows_description";
+alter table "node_types" drop column "name_policy";
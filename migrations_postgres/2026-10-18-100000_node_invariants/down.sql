@@ -0,0 +1,8 @@
+drop trigger "nodes_regular_unique" on "nodes";
+drop trigger "nodes_owner_unique" on "nodes";
+drop trigger "nodes_symlink_subgroup" on "nodes";
+drop trigger "nodes_no_cycle" on "nodes";
+drop function "nodes_regular_unique"();
+drop function "nodes_owner_unique"();
+drop function "nodes_symlink_subgroup"();
+drop function "nodes_no_cycle"();
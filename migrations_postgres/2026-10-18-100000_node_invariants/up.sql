@@ -0,0 +1,96 @@
+-- The same invariants as the SQLite triggers, the messages are the names of the matching
+-- RelanotesValidationRejection variants
+
+-- Regular names are unique in the group
+create function "nodes_regular_unique"() returns trigger as $$
+begin
+    if (select "value" from "node_types" where "id" = new."type_id") = 'regular' and exists (
+        select 1 from "nodes" n
+        inner join "subgroups" s on s."id" = n."subgroup_id"
+        where n."id" != new."id"
+            and n."type_id" = new."type_id"
+            and n."name" = new."name"
+            and s."group_id" = (select "group_id" from "subgroups" where "id" = new."subgroup_id")
+    ) then
+        raise exception 'DuplicateRegularNode';
+    end if;
+    return new;
+end;
+$$ language plpgsql;
+
+create trigger "nodes_regular_unique" before insert or update of "name", "type_id", "subgroup_id"
+on "nodes" for each row execute function "nodes_regular_unique"();
+
+-- Sticky notes and inherited nodes are unique in the children of the owner
+create function "nodes_owner_unique"() returns trigger as $$
+declare
+    type_value text := (select "value" from "node_types" where "id" = new."type_id");
+begin
+    if type_value in ('sticky_notes', 'inherited') and exists (
+        select 1 from "nodes" n
+        where n."id" != new."id"
+            and n."type_id" = new."type_id"
+            and n."linked_to_id" = new."linked_to_id"
+            and n."name" = new."name"
+    ) then
+        if type_value = 'sticky_notes' then
+            raise exception 'DuplicateStickyNote';
+        end if;
+        raise exception 'DuplicateInheritedNode';
+    end if;
+    return new;
+end;
+$$ language plpgsql;
+
+create trigger "nodes_owner_unique" before insert or update of "name", "type_id", "linked_to_id"
+on "nodes" for each row execute function "nodes_owner_unique"();
+
+-- Symlinks can't target nodes of their own subgroup
+create function "nodes_symlink_subgroup"() returns trigger as $$
+begin
+    if (select "value" from "node_types" where "id" = new."type_id") = 'symlinks'
+        and (select "subgroup_id" from "nodes" where "id" = new."linked_to_id") = new."subgroup_id"
+    then
+        raise exception 'SymLinkToSameSubgroup';
+    end if;
+    -- The source can't be moved into the subgroup of its symlinks either
+    if tg_op = 'UPDATE' and exists (
+        select 1 from "nodes" n
+        inner join "node_types" t on t."id" = n."type_id"
+        where t."value" = 'symlinks'
+            and n."linked_to_id" = new."id"
+            and n."subgroup_id" = new."subgroup_id"
+    ) then
+        raise exception 'SymLinkToSameSubgroup';
+    end if;
+    return new;
+end;
+$$ language plpgsql;
+
+create trigger "nodes_symlink_subgroup" before insert or update of "type_id", "linked_to_id", "subgroup_id"
+on "nodes" for each row execute function "nodes_symlink_subgroup"();
+
+-- The links never form a cycle
+create function "nodes_no_cycle"() returns trigger as $$
+begin
+    if new."linked_to_id" = new."id" then
+        raise exception 'LinkedToItself';
+    end if;
+    if tg_op = 'UPDATE' and new."linked_to_id" is not null and exists (
+        with recursive "ancestors"("id") as (
+            select new."linked_to_id"
+            union
+            select n."linked_to_id" from "nodes" n
+            inner join "ancestors" a on n."id" = a."id"
+            where n."linked_to_id" is not null
+        )
+        select 1 from "ancestors" where "id" = new."id"
+    ) then
+        raise exception 'CyclicLink';
+    end if;
+    return new;
+end;
+$$ language plpgsql;
+
+create trigger "nodes_no_cycle" before insert or update of "linked_to_id"
+on "nodes" for each row execute function "nodes_no_cycle"();
@@ -0,0 +1,4 @@
+-- The versions of the seed data added by the setup, every version is added once
+create table "seed_versions" (
+    "version" integer primary key not null
+);
@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+drop trigger "nodes_changes" on "nodes";
+drop trigger "subgroups_changes" on "subgroups";
+drop trigger "groups_changes" on "groups";
+drop function "log_change"();
+drop table "changes";
+drop trigger "nodes_version" on "nodes";
+drop function "nodes_version"();
+alter table "nodes" drop column "version";
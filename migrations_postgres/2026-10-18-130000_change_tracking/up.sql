@@ -0,0 +1,52 @@
+-- The same tracking as the SQLite triggers: the version of a node is bumped by every write and
+-- the written rows are logged for the loaded models of the other processes
+alter table "nodes" add column "version" integer not null default 1;
+
+create function "nodes_version"() returns trigger as $$
+begin
+    new."version" := old."version" + 1;
+    return new;
+end;
+$$ language plpgsql;
+
+create trigger "nodes_version" before update of "linked_to_id", "type_id", "name", "description", "subgroup_id"
+on "nodes" for each row execute function "nodes_version"();
+
+-- The parent is the group of a subgroup and the subgroup of a node, the moved rows are logged
+-- with the old and the new parent
+create table "changes" (
+    "id" serial primary key,
+    "table_name" text not null,
+    "row_id" integer not null,
+    "parent_id" integer
+);
+
+-- The name of the parent column is the argument of the trigger. The writers take the log in
+-- turns, so that the changes are committed in the order of their ids and no reader skips one.
+create function "log_change"() returns trigger as $$
+declare
+    parent_column text := tg_argv[0];
+begin
+    lock table "changes" in exclusive mode;
+    if tg_op in ('UPDATE', 'DELETE') then
+        insert into "changes" ("table_name", "row_id", "parent_id")
+        values (tg_table_name, old."id", (to_jsonb(old) ->> parent_column)::integer);
+    end if;
+    if tg_op = 'INSERT' or (tg_op = 'UPDATE'
+        and (to_jsonb(new) ->> parent_column) is distinct from (to_jsonb(old) ->> parent_column))
+    then
+        insert into "changes" ("table_name", "row_id", "parent_id")
+        values (tg_table_name, new."id", (to_jsonb(new) ->> parent_column)::integer);
+    end if;
+    return null;
+end;
+$$ language plpgsql;
+
+create trigger "groups_changes" after insert or update or delete
+on "groups" for each row execute function "log_change"();
+
+create trigger "subgroups_changes" after insert or update or delete
+on "subgroups" for each row execute function "log_change"('group_id');
+
+create trigger "nodes_changes" after insert or delete or update of "linked_to_id", "type_id", "name", "description", "subgroup_id"
+on "nodes" for each row execute function "log_change"('subgroup_id');
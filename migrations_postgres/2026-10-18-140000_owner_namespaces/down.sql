@@ -0,0 +1,20 @@
+-- This file should undo anything in `up.sql`
+create or replace function "nodes_owner_unique"() returns trigger as $$
+declare
+    type_value text := (select "value" from "node_types" where "id" = new."type_id");
+begin
+    if type_value in ('sticky_notes', 'inherited') and exists (
+        select 1 from "nodes" n
+        where n."id" != new."id"
+            and n."type_id" = new."type_id"
+            and n."linked_to_id" = new."linked_to_id"
+            and n."name" = new."name"
+    ) then
+        if type_value = 'sticky_notes' then
+            raise exception 'DuplicateStickyNote';
+        end if;
+        raise exception 'DuplicateInheritedNode';
+    end if;
+    return new;
+end;
+$$ language plpgsql;
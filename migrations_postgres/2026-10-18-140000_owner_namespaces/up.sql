@@ -0,0 +1,55 @@
+-- Divisors are transparent, so sticky notes and inherited nodes are unique in the namespace of
+-- the nearest owner which is not a divisor. The namespace has the children of the owner and,
+-- recursively, the children of the divisors among them.
+create or replace function "nodes_owner_unique"() returns trigger as $$
+declare
+    type_value text := (select "value" from "node_types" where "id" = new."type_id");
+    owner_id integer := new."linked_to_id";
+begin
+    if type_value is null or type_value not in ('sticky_notes', 'inherited') then
+        return new;
+    end if;
+    -- From the linked node up through the divisors to the nearest other node
+    if exists (select 1 from "nodes" where "id" = new."linked_to_id") then
+        owner_id := (
+            with recursive "owners"("id", "linked_to_id", "is_divisor") as (
+                select n."id", n."linked_to_id", t."value" = 'divisor' from "nodes" n
+                inner join "node_types" t on t."id" = n."type_id"
+                where n."id" = new."linked_to_id"
+                union
+                select n."id", n."linked_to_id", t."value" = 'divisor' from "owners" o
+                inner join "nodes" n on n."id" = o."linked_to_id"
+                inner join "node_types" t on t."id" = n."type_id"
+                where o."is_divisor"
+            )
+            select "id" from "owners" where not "is_divisor"
+        );
+    end if;
+    if exists (
+        -- The children of the owner (the roots of the subgroup without one) and of the divisors
+        -- among them
+        with recursive "namespace"("id", "is_divisor") as (
+            select n."id", t."value" = 'divisor' from "nodes" n
+            inner join "node_types" t on t."id" = n."type_id"
+            where n."linked_to_id" is not distinct from owner_id
+                and (owner_id is not null or n."subgroup_id" = new."subgroup_id")
+            union
+            select n."id", t."value" = 'divisor' from "namespace" m
+            inner join "nodes" n on n."linked_to_id" = m."id"
+            inner join "node_types" t on t."id" = n."type_id"
+            where m."is_divisor"
+        )
+        select 1 from "namespace" m
+        inner join "nodes" n on n."id" = m."id"
+        where n."id" != new."id"
+            and n."type_id" = new."type_id"
+            and n."name" = new."name"
+    ) then
+        if type_value = 'sticky_notes' then
+            raise exception 'DuplicateStickyNote';
+        end if;
+        raise exception 'DuplicateInheritedNode';
+    end if;
+    return new;
+end;
+$$ language plpgsql;
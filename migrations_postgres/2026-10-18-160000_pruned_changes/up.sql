@@ -0,0 +1,5 @@
+-- The changes logged up to these ones were removed from the log. The models synced before the
+-- latest one can't be refreshed from the log anymore, they are loaded again instead.
+create table "pruned_changes" (
+    "up_to" integer primary key not null
+);
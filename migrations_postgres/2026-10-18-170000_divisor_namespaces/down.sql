@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+drop trigger "nodes_divisor_namespace" on "nodes";
+drop function "nodes_divisor_namespace"();
@@ -0,0 +1,70 @@
+-- The moved divisor takes its namespace children into the namespace of its new owner, where the
+-- sticky notes and inherited nodes can't repeat the names
+create function "nodes_divisor_namespace"() returns trigger as $$
+declare
+    owner_id integer := new."linked_to_id";
+    duplicate_type text;
+begin
+    if (select "value" from "node_types" where "id" = new."type_id") is distinct from 'divisor' then
+        return new;
+    end if;
+    -- From the new linked node up through the divisors to the nearest other node
+    if exists (select 1 from "nodes" where "id" = new."linked_to_id") then
+        owner_id := (
+            with recursive "owners"("id", "linked_to_id", "is_divisor") as (
+                select n."id", n."linked_to_id", t."value" = 'divisor' from "nodes" n
+                inner join "node_types" t on t."id" = n."type_id"
+                where n."id" = new."linked_to_id"
+                union
+                select n."id", n."linked_to_id", t."value" = 'divisor' from "owners" o
+                inner join "nodes" n on n."id" = o."linked_to_id"
+                inner join "node_types" t on t."id" = n."type_id"
+                where o."is_divisor"
+            )
+            select "id" from "owners" where not "is_divisor"
+        );
+    end if;
+    duplicate_type := (
+        -- The new namespace without the moved divisor, which can still be in it
+        with recursive "namespace"("id", "is_divisor") as (
+            select n."id", t."value" = 'divisor' from "nodes" n
+            inner join "node_types" t on t."id" = n."type_id"
+            where n."linked_to_id" is not distinct from owner_id
+                and (owner_id is not null or n."subgroup_id" = new."subgroup_id")
+                and n."id" != new."id"
+            union
+            select n."id", t."value" = 'divisor' from "namespace" m
+            inner join "nodes" n on n."linked_to_id" = m."id"
+            inner join "node_types" t on t."id" = n."type_id"
+            where m."is_divisor" and n."id" != new."id"
+        ),
+        -- The children of the moved divisor and of the divisors among them
+        "moved"("id", "is_divisor") as (
+            select n."id", t."value" = 'divisor' from "nodes" n
+            inner join "node_types" t on t."id" = n."type_id"
+            where n."linked_to_id" = new."id"
+            union
+            select n."id", t."value" = 'divisor' from "moved" m
+            inner join "nodes" n on n."linked_to_id" = m."id"
+            inner join "node_types" t on t."id" = n."type_id"
+            where m."is_divisor"
+        )
+        select t."value" from "moved" m
+        inner join "nodes" n on n."id" = m."id"
+        inner join "node_types" t on t."id" = n."type_id"
+        inner join "nodes" o on o."type_id" = n."type_id" and o."name" = n."name"
+        inner join "namespace" s on s."id" = o."id"
+        where t."value" in ('sticky_notes', 'inherited')
+        limit 1
+    );
+    if duplicate_type = 'sticky_notes' then
+        raise exception 'DuplicateStickyNote';
+    elsif duplicate_type = 'inherited' then
+        raise exception 'DuplicateInheritedNode';
+    end if;
+    return new;
+end;
+$$ language plpgsql;
+
+create trigger "nodes_divisor_namespace" before update of "linked_to_id"
+on "nodes" for each row execute function "nodes_divisor_namespace"();
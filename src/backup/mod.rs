@@ -0,0 +1,271 @@
+use crate::database_setup::{plan_database_setup, setup_database};
+use crate::errors::RelanotesError;
+use crate::integrity_check::check_database;
+use crate::notebooks::is_notebook;
+use crate::storage::SqliteStorage;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use raw::RawDatabase;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+mod raw;
+
+const BACKUP_EXTENSION: &str = "backup";
+// Like 20261018-093005-250, in UTC
+const TIMESTAMP_LENGTH: usize = 19;
+
+#[allow(non_local_definitions)] // diesel 1.4 derives expand to non-local impls
+mod rows {
+    use diesel::sql_types::Text;
+
+    #[derive(QueryableByName)]
+    pub struct IntegrityCheckRow {
+        #[sql_type = "Text"]
+        pub integrity_check: String,
+    }
+}
+use rows::IntegrityCheckRow;
+
+/// Which backups are kept after a new one is written, the latest one is always kept
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetentionPolicy {
+    pub keep_last: usize,
+    pub max_age: Option<Duration>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            keep_last: 10,
+            max_age: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backup {
+    pub path: PathBuf,
+    // When the backup was written, the timestamps sort like the times
+    pub timestamp: String,
+}
+
+/// The rotating backups of the notebooks in one directory, named by the notebook and the time
+pub struct Backups {
+    directory: PathBuf,
+    retention: RetentionPolicy,
+}
+
+impl Backups {
+    pub fn new(directory: impl AsRef<Path>, retention: RetentionPolicy) -> Self {
+        Backups {
+            directory: directory.as_ref().to_path_buf(),
+            retention,
+        }
+    }
+
+    /// Writes the snapshot of the notebook and removes the backups that are not kept anymore.
+    /// The notebook can be open and written meanwhile.
+    pub fn back_up(&self, notebook: impl AsRef<Path>) -> Result<Backup, RelanotesError> {
+        let notebook = notebook.as_ref();
+        fs::create_dir_all(&self.directory)?;
+        let name = notebook_name(notebook)?;
+        let backup = loop {
+            let timestamp = timestamp(SystemTime::now());
+            let path = self
+                .directory
+                .join(format!("{}-{}.{}", name, timestamp, BACKUP_EXTENSION));
+            if !path.exists() {
+                break Backup { path, timestamp };
+            }
+            thread::sleep(Duration::from_millis(1));
+        };
+        snapshot(notebook, &backup.path)?;
+        self.rotate(notebook)?;
+        Ok(backup)
+    }
+
+    /// The backups of the notebook, the latest one first
+    pub fn list(&self, notebook: impl AsRef<Path>) -> Result<Vec<Backup>, RelanotesError> {
+        if !self.directory.exists() {
+            return Ok(vec![]);
+        }
+        let prefix = format!("{}-", notebook_name(notebook.as_ref())?);
+        let suffix = format!(".{}", BACKUP_EXTENSION);
+        let mut backups = vec![];
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            let timestamp = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(&prefix))
+                .and_then(|name| name.strip_suffix(&suffix))
+                .filter(|timestamp| is_timestamp(timestamp))
+                .map(String::from);
+            if let Some(timestamp) = timestamp {
+                backups.push(Backup { path, timestamp });
+            }
+        }
+        backups.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        Ok(backups)
+    }
+
+    fn rotate(&self, notebook: &Path) -> Result<(), RelanotesError> {
+        let cutoff = self
+            .retention
+            .max_age
+            .and_then(|max_age| SystemTime::now().checked_sub(max_age))
+            .map(timestamp);
+        for (i, backup) in self.list(notebook)?.iter().enumerate().skip(1) {
+            let expired = cutoff
+                .as_ref()
+                .is_some_and(|cutoff| &backup.timestamp < cutoff);
+            if i >= self.retention.keep_last || expired {
+                fs::remove_file(&backup.path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes the snapshot of the notebook to a new file with the online backup API of SQLite. The
+/// snapshot has the last committed state, even if the notebook is written meanwhile.
+pub fn snapshot(
+    notebook: impl AsRef<Path>,
+    destination: impl AsRef<Path>,
+) -> Result<(), RelanotesError> {
+    let (notebook, destination) = (notebook.as_ref(), destination.as_ref());
+    if !notebook.is_file() {
+        return Err(RelanotesError::NotebookNotFound(notebook.to_path_buf()));
+    }
+    if destination.exists() {
+        return Err(RelanotesError::NotebookExists(destination.to_path_buf()));
+    }
+    // Copied under a scratch name, so that a failed copy doesn't leave a broken snapshot behind
+    let mut scratch_name = destination.file_name().unwrap_or_default().to_os_string();
+    scratch_name.push(".copying");
+    let scratch = ScratchFile(destination.with_file_name(scratch_name));
+    let _ = fs::remove_file(&scratch.0);
+    let source = RawDatabase::open_read_only(notebook)?;
+    RawDatabase::open_or_create(&scratch.0)?.copy_from(&source)?;
+    fs::rename(&scratch.0, destination)?;
+    Ok(())
+}
+
+/// Replaces the notebook with the snapshot, the notebook is created if it's missing. The
+/// snapshot is checked and migrated on a scratch copy first, nothing is replaced if it has
+/// problems.
+pub fn restore(
+    snapshot: impl AsRef<Path>,
+    notebook: impl AsRef<Path>,
+) -> Result<(), RelanotesError> {
+    let (snapshot, notebook) = (snapshot.as_ref(), notebook.as_ref());
+    if !snapshot.is_file() {
+        return Err(RelanotesError::NotebookNotFound(snapshot.to_path_buf()));
+    }
+    if !is_notebook(snapshot)? {
+        return Err(RelanotesError::CorruptedSnapshot {
+            path: snapshot.to_path_buf(),
+            problems: vec!["Not a SQLite database".to_string()],
+        });
+    }
+    let mut scratch_name = notebook.file_name().unwrap_or_default().to_os_string();
+    scratch_name.push(".restoring");
+    let scratch = ScratchFile(notebook.with_file_name(scratch_name));
+    let _ = fs::remove_file(&scratch.0);
+
+    RawDatabase::open_or_create(&scratch.0)?.copy_from(&RawDatabase::open_read_only(snapshot)?)?;
+    let problems = verify(&scratch.0)?;
+    if !problems.is_empty() {
+        return Err(RelanotesError::CorruptedSnapshot {
+            path: snapshot.to_path_buf(),
+            problems,
+        });
+    }
+    RawDatabase::open_or_create(notebook)?.copy_from(&RawDatabase::open_read_only(&scratch.0)?)
+}
+
+// The problems of the file, from SQLite and from the integrity check of the model
+fn verify(path: &Path) -> Result<Vec<String>, RelanotesError> {
+    let conn = SqliteConnection::establish(&path.to_string_lossy())
+        .map_err(RelanotesError::ConnectionError)?;
+    let rows = match diesel::sql_query("pragma integrity_check").load::<IntegrityCheckRow>(&conn) {
+        Ok(rows) => rows,
+        Err(e) => return Ok(vec![e.to_string()]),
+    };
+    let problems = rows
+        .into_iter()
+        .map(|row| row.integrity_check)
+        .filter(|result| result != "ok")
+        .collect::<Vec<_>>();
+    if !problems.is_empty() {
+        return Ok(problems);
+    }
+    let storage = SqliteStorage::new(conn)?;
+    if plan_database_setup(&storage)?.schema_version.is_none() {
+        return Ok(vec!["Not a notebook".to_string()]);
+    }
+    // The snapshots of the older versions are migrated before the check
+    setup_database(&storage)?;
+    Ok(check_database(&storage)?
+        .into_iter()
+        .map(|problem| format!("{:?}", problem))
+        .collect())
+}
+
+// Removed when dropped, whether the restore succeeded or not
+struct ScratchFile(PathBuf);
+
+impl Drop for ScratchFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn notebook_name(notebook: &Path) -> Result<String, RelanotesError> {
+    notebook
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .ok_or_else(|| RelanotesError::NotebookNotFound(notebook.to_path_buf()))
+}
+
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (days, seconds_of_day) = ((seconds / 86400) as i64, seconds % 86400);
+    // The civil date of the days since the epoch, with the eras of 400 years that start in March
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+fn is_timestamp(value: &str) -> bool {
+    value.len() == TIMESTAMP_LENGTH
+        && value.char_indices().all(|(i, c)| match i {
+            8 | 15 => c == '-',
+            _ => c.is_ascii_digit(),
+        })
+}
@@ -0,0 +1,89 @@
+use crate::errors::RelanotesError;
+use libsqlite3_sys as ffi;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::path::Path;
+use std::ptr;
+use std::thread;
+use std::time::Duration;
+
+// How long the copy waits for the writers of other connections
+const BUSY_TIMEOUT_MS: c_int = 5000;
+const BUSY_RETRIES: usize = 50;
+const MAIN: &[u8] = b"main\0";
+
+/// The connection of the backup API, separate from the connection of the storage
+pub(super) struct RawDatabase {
+    handle: *mut ffi::sqlite3,
+}
+
+impl RawDatabase {
+    pub(super) fn open_read_only(path: &Path) -> Result<Self, RelanotesError> {
+        RawDatabase::open(path, ffi::SQLITE_OPEN_READONLY)
+    }
+
+    pub(super) fn open_or_create(path: &Path) -> Result<Self, RelanotesError> {
+        RawDatabase::open(path, ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE)
+    }
+
+    fn open(path: &Path, flags: c_int) -> Result<Self, RelanotesError> {
+        let c_path = CString::new(path.to_string_lossy().as_bytes())
+            .map_err(|e| RelanotesError::BackupFailed(e.to_string()))?;
+        let mut handle = ptr::null_mut();
+        // The handle is allocated even if the opening fails, so it's closed with the error
+        let code =
+            unsafe { ffi::sqlite3_open_v2(c_path.as_ptr(), &mut handle, flags, ptr::null()) };
+        let database = RawDatabase { handle };
+        if code != ffi::SQLITE_OK {
+            return Err(database.error(code));
+        }
+        unsafe { ffi::sqlite3_busy_timeout(handle, BUSY_TIMEOUT_MS) };
+        Ok(database)
+    }
+
+    fn error(&self, code: c_int) -> RelanotesError {
+        let message = if self.handle.is_null() {
+            ffi::code_to_str(code).to_string()
+        } else {
+            unsafe { CStr::from_ptr(ffi::sqlite3_errmsg(self.handle)) }
+                .to_string_lossy()
+                .into_owned()
+        };
+        RelanotesError::BackupFailed(message)
+    }
+
+    /// Replaces the content of this database with the source in one transaction, the source
+    /// is read at one point in time even if it's written by other connections
+    pub(super) fn copy_from(&self, source: &RawDatabase) -> Result<(), RelanotesError> {
+        let main = MAIN.as_ptr() as *const c_char;
+        let backup = unsafe { ffi::sqlite3_backup_init(self.handle, main, source.handle, main) };
+        if backup.is_null() {
+            return Err(self.error(ffi::SQLITE_ERROR));
+        }
+        let mut code = ffi::SQLITE_OK;
+        for _ in 0..BUSY_RETRIES {
+            // All pages in one step, so the writes of the other connections can't restart it
+            code = unsafe { ffi::sqlite3_backup_step(backup, -1) };
+            if code != ffi::SQLITE_BUSY && code != ffi::SQLITE_LOCKED {
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        let finish_code = unsafe { ffi::sqlite3_backup_finish(backup) };
+        match (code, finish_code) {
+            (ffi::SQLITE_DONE, ffi::SQLITE_OK) => Ok(()),
+            (ffi::SQLITE_DONE, error_code) => Err(self.error(error_code)),
+            (ffi::SQLITE_BUSY, _) | (ffi::SQLITE_LOCKED, _) => Err(RelanotesError::BackupFailed(
+                ffi::code_to_str(code).to_string(),
+            )),
+            (error_code, _) => Err(self.error(error_code)),
+        }
+    }
+}
+
+impl Drop for RawDatabase {
+    fn drop(&mut self) {
+        // Closing a null handle is a no-op
+        unsafe { ffi::sqlite3_close(self.handle) };
+    }
+}
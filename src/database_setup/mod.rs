@@ -1,43 +1,243 @@
-use super::schema::*;
-use diesel::prelude::*;
+use crate::groups_mod::subgroups_mod::nodes_mod::node_types::{
+    NodeTypeDefinition, NodeTypesRegistry, NodeTypesSet,
+};
+use crate::storage::{set_foreign_keys, SqliteStorage, Storage};
+use diesel::result::Error;
 use diesel::sqlite::SqliteConnection;
+use diesel_migrations::{MigrationConnection, RunMigrationsError};
+use std::io::Write;
 
 embed_migrations!("migrations/");
-fn setup_migrations(conn: &SqliteConnection) -> Result<(), diesel_migrations::RunMigrationsError> {
-    embedded_migrations::run(conn)
-}
-
-fn add_node_types(conn: &SqliteConnection) -> Result<usize, diesel::result::Error> {
-    diesel::insert_or_ignore_into(node_types::table)
-        .values(&vec![
-            (
-                node_types::name.eq("Regular"),
-                node_types::value.eq("regular"),
-            ),
-            (
-                node_types::name.eq("Sticky Notes"),
-                node_types::value.eq("sticky_notes"),
-            ),
-            (
-                node_types::name.eq("Inherited"),
-                node_types::value.eq("inherited"),
-            ),
-            (
-                node_types::name.eq("Symlinks"),
-                node_types::value.eq("symlinks"),
-            ),
-        ])
-        .execute(conn)
-}
-
-fn setup_initial_data(conn: &SqliteConnection) -> Result<(), diesel::result::Error> {
-    // But remember that this function will be called with each start-up, so we have to check if the data is loaded or not
-    add_node_types(conn)?;
+
+/// Why the database couldn't be set up
+#[derive(Debug)]
+pub enum SetupError {
+    /// The migration with the version failed, the ones before it were rolled back too
+    Migration {
+        version: Option<String>,
+        error: RunMigrationsError,
+    },
+    /// The seed data with the version couldn't be added
+    Seed { version: i32, error: Error },
+    /// The versions couldn't be read or the transaction of the setup failed
+    Query(Error),
+}
+
+impl std::fmt::Display for SetupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SetupError::Migration {
+                version: Some(version),
+                error,
+            } => write!(f, "Migration {} failed ({})", version, error),
+            SetupError::Migration {
+                version: None,
+                error,
+            } => write!(f, "Migrations failed ({})", error),
+            SetupError::Seed { version, error } => {
+                write!(f, "Seed data {} couldn't be added ({})", version, error)
+            }
+            SetupError::Query(e) => write!(f, "Setup queries failed ({})", e),
+        }
+    }
+}
+
+impl std::error::Error for SetupError {}
+
+impl From<Error> for SetupError {
+    fn from(e: Error) -> Self {
+        SetupError::Query(e)
+    }
+}
+
+/// The changes made by the setup, or the ones that the dry run would make
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetupChanges {
+    /// The latest migration that was run before, `None` for a new database
+    pub schema_version: Option<String>,
+    /// The versions of the migrations run by the setup, in order
+    pub migrations: Vec<String>,
+    /// The latest seed data that was added before, 0 for a new database
+    pub seed_version: i32,
+    /// The versions of the seed data added by the setup, in order
+    pub seeds: Vec<i32>,
+}
+
+impl SetupChanges {
+    pub fn is_empty(&self) -> bool {
+        self.migrations.is_empty() && self.seeds.is_empty()
+    }
+
+    /// The latest migration after the setup
+    pub fn current_schema_version(&self) -> Option<&str> {
+        self.migrations
+            .last()
+            .or(self.schema_version.as_ref())
+            .map(String::as_str)
+    }
+}
+
+type Seed = fn(&dyn Storage) -> Result<(), Error>;
+
+// The seed data of the releases, every version is added once and in order. Released seeds are
+// never changed, the changes of the data are added as new versions, so that all databases are
+// upgraded the same way.
+const SEEDS: &[(i32, Seed)] = &[(1, add_node_types), (2, allow_nested_divisors)];
+
+fn add_node_types(storage: &dyn Storage) -> Result<(), Error> {
+    for definition in NodeTypeDefinition::built_in() {
+        definition.save(storage)?;
+    }
     Ok(())
 }
 
-pub fn setup_database(conn: &SqliteConnection) -> Option<()> {
-    setup_migrations(conn).ok()?;
-    setup_initial_data(conn).ok()?;
-    Some(())
+// Divisors can divide the children of other divisors, the other rules of the saved type are kept
+fn allow_nested_divisors(storage: &dyn Storage) -> Result<(), Error> {
+    let registry = NodeTypesRegistry::load(storage)?;
+    let mut divisor = match registry.iter().find(|(_, d)| d.value == "divisor") {
+        Some((_, definition)) => definition.clone(),
+        None => return Ok(()),
+    };
+    for types in [&mut divisor.parent_types, &mut divisor.child_types] {
+        if let NodeTypesSet::Only(values) = types {
+            if !values.iter().any(|value| value == "divisor") {
+                values.push("divisor".into());
+            }
+        }
+    }
+    divisor.save(storage)?;
+    Ok(())
+}
+
+/// Adds the seed data newer than the version of the storage, returns the added versions
+pub(crate) fn add_seeds(storage: &dyn Storage) -> Result<Vec<i32>, SetupError> {
+    let seed_version = storage.seed_version()?;
+    let mut added = vec![];
+    for &(version, seed) in SEEDS.iter().filter(|(version, _)| *version > seed_version) {
+        seed(storage).map_err(|error| SetupError::Seed { version, error })?;
+        storage.add_seed_version(version)?;
+        added.push(version);
+    }
+    Ok(added)
+}
+
+type RunMigrations<C> = fn(&C, &mut dyn Write) -> Result<(), RunMigrationsError>;
+
+fn apply<C: MigrationConnection>(
+    conn: &C,
+    storage: &dyn Storage,
+    run_migrations: RunMigrations<C>,
+) -> Result<SetupChanges, SetupError> {
+    diesel_migrations::setup_database(conn)?;
+    let schema_version = conn.previously_run_migration_versions()?.into_iter().max();
+    // The versions are only reported in the output of the migrations
+    let mut output = vec![];
+    let result = run_migrations(conn, &mut output);
+    let migrations = String::from_utf8_lossy(&output)
+        .lines()
+        .filter_map(|line| line.strip_prefix("Running migration "))
+        .map(String::from)
+        .collect::<Vec<_>>();
+    result.map_err(|error| SetupError::Migration {
+        version: migrations.last().cloned(),
+        error,
+    })?;
+    let seed_version = storage.seed_version()?;
+    let seeds = add_seeds(storage)?;
+    Ok(SetupChanges {
+        schema_version,
+        migrations,
+        seed_version,
+        seeds,
+    })
+}
+
+// Everything is run in one transaction, the dry run rolls it back after checking that it passes
+fn set_up<C: MigrationConnection>(
+    conn: &C,
+    storage: &dyn Storage,
+    run_migrations: RunMigrations<C>,
+    dry_run: bool,
+) -> Result<SetupChanges, SetupError> {
+    let mut changes = None;
+    let outcome = conn.transaction::<(), SetupError, _>(|| {
+        changes = Some(apply(conn, storage, run_migrations)?);
+        if dry_run {
+            Err(SetupError::Query(Error::RollbackTransaction))
+        } else {
+            Ok(())
+        }
+    });
+    match (outcome, changes) {
+        (Ok(()), Some(changes)) => Ok(changes),
+        (Err(SetupError::Query(Error::RollbackTransaction)), Some(changes)) if dry_run => {
+            Ok(changes)
+        }
+        (Err(e), _) => Err(e),
+        (Ok(()), None) => unreachable!("The setup was not applied"),
+    }
+}
+
+fn run_migrations(
+    conn: &SqliteConnection,
+    output: &mut dyn Write,
+) -> Result<(), RunMigrationsError> {
+    embedded_migrations::run_with_output(conn, output)
+}
+
+// The migrations rebuilding the tables can't run with the foreign keys enforced, so they are
+// disabled outside the transaction of the setup
+fn set_up_sqlite(storage: &SqliteStorage, dry_run: bool) -> Result<SetupChanges, SetupError> {
+    let conn = &*storage.connection();
+    set_foreign_keys(conn, false)?;
+    let changes = set_up(conn, storage, run_migrations, dry_run);
+    set_foreign_keys(conn, true)?;
+    changes
+}
+
+/// Runs the pending migrations and adds the new seed data
+pub fn setup_database(storage: &SqliteStorage) -> Result<SetupChanges, SetupError> {
+    set_up_sqlite(storage, false)
+}
+
+/// The changes that `setup_database` would make, nothing is changed
+pub fn plan_database_setup(storage: &SqliteStorage) -> Result<SetupChanges, SetupError> {
+    set_up_sqlite(storage, true)
+}
+
+// The migrations of PostgreSQL are kept separately, the types of the ids and the triggers differ
+#[cfg(feature = "postgres")]
+mod postgres_migrations {
+    embed_migrations!("migrations_postgres/");
+
+    pub fn run(
+        conn: &diesel::pg::PgConnection,
+        output: &mut dyn std::io::Write,
+    ) -> Result<(), diesel_migrations::RunMigrationsError> {
+        embedded_migrations::run_with_output(conn, output)
+    }
+}
+
+#[cfg(feature = "postgres")]
+pub fn setup_postgres_database(
+    storage: &crate::storage::PgStorage,
+) -> Result<SetupChanges, SetupError> {
+    set_up(
+        &*storage.connection(),
+        storage,
+        postgres_migrations::run,
+        false,
+    )
+}
+
+#[cfg(feature = "postgres")]
+pub fn plan_postgres_database_setup(
+    storage: &crate::storage::PgStorage,
+) -> Result<SetupChanges, SetupError> {
+    set_up(
+        &*storage.connection(),
+        storage,
+        postgres_migrations::run,
+        true,
+    )
 }
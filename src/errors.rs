@@ -0,0 +1,193 @@
+use crate::database_setup::SetupError;
+use crate::groups_mod::subgroups_mod::nodes_mod::{
+    EditConflict, PathResolutionError, RelanotesValidationRejection,
+};
+use std::path::PathBuf;
+
+/// The errors of the crate, so that the front-ends can match on one type
+#[derive(Debug)]
+pub enum RelanotesError {
+    NodeMutationError(String),
+    DBQueriesError(diesel::result::Error),
+    NodeNotFound(i32),
+    NodeHasChildren(i32),
+    // Nodes outside of the deleted or moved set (symlinks from other subgroups, unloaded
+    // children) are still linked to it
+    NodeIsReferenced {
+        node_id: i32,
+        referenced_by: Vec<i32>,
+    },
+    ChildCannotBeLifted(i32),
+    // The parent of the deleted node was quarantined while loading, so its children can't be
+    // lifted to it
+    ParentNotLoaded {
+        node_id: i32,
+        parent_id: i32,
+    },
+    CyclicMove {
+        node_id: i32,
+        new_parent_id: i32,
+    },
+    SubGroupNotFound(i32),
+    GroupNameNotFound(String),
+    SubGroupNameNotFound(String),
+    ConnectionError(diesel::ConnectionError),
+    DatabaseSetupFailed(SetupError),
+    NotebookNotFound(PathBuf),
+    NotebookExists(PathBuf),
+    // Reading or writing the files of the notebooks, like the registry of the recent ones
+    IOError(std::io::Error),
+    // The message of SQLite, when the backup API fails
+    BackupFailed(String),
+    CorruptedSnapshot {
+        path: PathBuf,
+        problems: Vec<String>,
+    },
+    // The nodes were written by another process since they were loaded
+    EditConflicts(Vec<EditConflict>),
+    ValidationRejection(RelanotesValidationRejection),
+    PathResolution(PathResolutionError),
+}
+
+impl std::fmt::Display for RelanotesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RelanotesError::NodeMutationError(e) => write!(f, "{}", e),
+            RelanotesError::DBQueriesError(e) => write!(f, "DBQueriesError ({})", e),
+            RelanotesError::NodeNotFound(id) => write!(f, "Node not found ({})", id),
+            RelanotesError::NodeHasChildren(id) => write!(f, "Node has children ({})", id),
+            RelanotesError::NodeIsReferenced {
+                node_id,
+                referenced_by,
+            } => write!(
+                f,
+                "Node ({}) is referenced by other nodes ({:?})",
+                node_id, referenced_by
+            ),
+            RelanotesError::ChildCannotBeLifted(id) => {
+                write!(f, "Child can't be linked to the new parent ({})", id)
+            }
+            RelanotesError::ParentNotLoaded { node_id, parent_id } => write!(
+                f,
+                "Parent ({}) of the node ({}) is not loaded",
+                parent_id, node_id
+            ),
+            RelanotesError::CyclicMove {
+                node_id,
+                new_parent_id,
+            } => write!(
+                f,
+                "Moving node ({}) under ({}) creates a cycle",
+                node_id, new_parent_id
+            ),
+            RelanotesError::SubGroupNotFound(id) => write!(f, "Subgroup not found ({})", id),
+            RelanotesError::GroupNameNotFound(name) => write!(f, "Group not found ({})", name),
+            RelanotesError::SubGroupNameNotFound(name) => {
+                write!(f, "Subgroup not found ({})", name)
+            }
+            RelanotesError::ConnectionError(e) => write!(f, "ConnectionError ({})", e),
+            RelanotesError::DatabaseSetupFailed(e) => {
+                write!(f, "Couldn't set up the database ({})", e)
+            }
+            RelanotesError::NotebookNotFound(path) => {
+                write!(f, "Notebook not found ({})", path.display())
+            }
+            RelanotesError::NotebookExists(path) => {
+                write!(f, "Notebook already exists ({})", path.display())
+            }
+            RelanotesError::IOError(e) => write!(f, "IOError ({})", e),
+            RelanotesError::BackupFailed(message) => write!(f, "Backup failed ({})", message),
+            RelanotesError::CorruptedSnapshot { path, problems } => write!(
+                f,
+                "Snapshot ({}) can't be restored ({:?})",
+                path.display(),
+                problems
+            ),
+            RelanotesError::EditConflicts(conflicts) => write!(
+                f,
+                "Nodes were changed by another process ({:?})",
+                conflicts.iter().map(|c| c.node_id).collect::<Vec<i32>>()
+            ),
+            RelanotesError::ValidationRejection(e) => write!(f, "{}", e),
+            RelanotesError::PathResolution(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for RelanotesError {
+    fn description(&self) -> &str {
+        match self {
+            RelanotesError::NodeMutationError(e) => e.as_str(),
+            RelanotesError::DBQueriesError(_) => "Got problems with running queries",
+            RelanotesError::NodeNotFound(_) => "Node not found",
+            RelanotesError::NodeHasChildren(_) => "Node has children",
+            RelanotesError::NodeIsReferenced { .. } => "Node is referenced by other nodes",
+            RelanotesError::ChildCannotBeLifted(_) => "Child can't be linked to the new parent",
+            RelanotesError::ParentNotLoaded { .. } => "Parent of the node is not loaded",
+            RelanotesError::CyclicMove { .. } => "Moving the node creates a cycle",
+            RelanotesError::SubGroupNotFound(_) => "Subgroup not found",
+            RelanotesError::GroupNameNotFound(_) => "Group not found",
+            RelanotesError::SubGroupNameNotFound(_) => "Subgroup not found",
+            RelanotesError::ConnectionError(_) => "Couldn't connect to the database",
+            RelanotesError::DatabaseSetupFailed(_) => "Couldn't set up the database",
+            RelanotesError::NotebookNotFound(_) => "Notebook not found",
+            RelanotesError::NotebookExists(_) => "Notebook already exists",
+            RelanotesError::IOError(_) => "Couldn't read or write the file",
+            RelanotesError::BackupFailed(_) => "Backup failed",
+            RelanotesError::CorruptedSnapshot { .. } => "The snapshot can't be restored",
+            RelanotesError::EditConflicts(_) => "The nodes were changed by another process",
+            RelanotesError::ValidationRejection(_) => "The node didn't pass the validation",
+            RelanotesError::PathResolution(_) => "The path can't be resolved",
+        }
+    }
+}
+
+impl RelanotesError {
+    /// Maps the violations of the database triggers back to the validation rejections
+    pub(crate) fn from_node_write(
+        e: diesel::result::Error,
+        node_id: Option<i32>,
+        name: &str,
+    ) -> Self {
+        if let diesel::result::Error::DatabaseError(_, info) = &e {
+            if let Some(rejection) = RelanotesValidationRejection::from_constraint_violation(
+                info.message(),
+                node_id,
+                name,
+            ) {
+                return RelanotesError::ValidationRejection(rejection);
+            }
+        }
+        RelanotesError::DBQueriesError(e)
+    }
+}
+
+impl From<diesel::result::Error> for RelanotesError {
+    fn from(e: diesel::result::Error) -> Self {
+        RelanotesError::DBQueriesError(e)
+    }
+}
+
+impl From<SetupError> for RelanotesError {
+    fn from(e: SetupError) -> Self {
+        RelanotesError::DatabaseSetupFailed(e)
+    }
+}
+
+impl From<std::io::Error> for RelanotesError {
+    fn from(e: std::io::Error) -> Self {
+        RelanotesError::IOError(e)
+    }
+}
+
+impl From<RelanotesValidationRejection> for RelanotesError {
+    fn from(e: RelanotesValidationRejection) -> Self {
+        RelanotesError::ValidationRejection(e)
+    }
+}
+
+impl From<PathResolutionError> for RelanotesError {
+    fn from(e: PathResolutionError) -> Self {
+        RelanotesError::PathResolution(e)
+    }
+}
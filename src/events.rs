@@ -0,0 +1,162 @@
+use crate::groups_mod::subgroups_mod::nodes_mod::GraphNode;
+use crate::models::{GroupElement, SubGroupElement};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, ThreadId};
+
+/// The change of the model, with everything needed to patch the views without reloading them
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "event")]
+pub enum Event {
+    GroupCreated {
+        group: GroupElement,
+    },
+    GroupUpdated {
+        group: GroupElement,
+    },
+    // The subgroups and the nodes of the group are deleted with it
+    GroupDeleted {
+        group_id: i32,
+    },
+    SubGroupCreated {
+        subgroup: SubGroupElement,
+    },
+    SubGroupUpdated {
+        subgroup: SubGroupElement,
+    },
+    SubGroupDeleted {
+        group_id: i32,
+        subgroup_id: i32,
+    },
+    // The tree of the subgroup was loaded, the nodes are sorted by id
+    SubGroupLoaded {
+        subgroup_id: i32,
+        nodes: Vec<GraphNode>,
+    },
+    SubGroupUnloaded {
+        subgroup_id: i32,
+    },
+    NodeCreated {
+        subgroup_id: i32,
+        node: GraphNode,
+    },
+    NodeUpdated {
+        subgroup_id: i32,
+        node: GraphNode,
+    },
+    // The moved node first, then its descendants, whose paths could change with it
+    NodeMoved {
+        node_id: i32,
+        from_subgroup_id: i32,
+        to_subgroup_id: i32,
+        nodes: Vec<GraphNode>,
+    },
+    NodeDeleted {
+        subgroup_id: i32,
+        node_ids: Vec<i32>,
+    },
+}
+
+#[derive(Default)]
+struct Channels {
+    subscribers: Vec<Sender<Event>>,
+    // The events of the open transactions of every thread, the innermost one last. The clones of
+    // the workspace share the channels, the transaction of one thread doesn't hold back or drop
+    // the events of the others.
+    pending: HashMap<ThreadId, Vec<Vec<Event>>>,
+}
+
+/// Publishes the events of the workspace to the subscribers. The events of the transactions are
+/// published only when they are committed, the transactions are followed per thread.
+#[derive(Clone, Default)]
+pub struct Events {
+    channels: Arc<Mutex<Channels>>,
+}
+
+impl Events {
+    /// The receiver gets the events published from now on, it's unsubscribed when dropped
+    pub fn subscribe(&self) -> Receiver<Event> {
+        let (sender, receiver) = channel();
+        self.channels.lock().subscribers.push(sender);
+        receiver
+    }
+
+    // The event is only built when someone listens
+    pub(crate) fn publish(&self, event: impl FnOnce() -> Event) {
+        let mut channels = self.channels.lock();
+        if channels.subscribers.is_empty() {
+            return;
+        }
+        let event = event();
+        match channels
+            .transactions()
+            .and_then(|pending| pending.last_mut())
+        {
+            Some(pending) => pending.push(event),
+            None => channels.send(event),
+        }
+    }
+
+    /// Holds back the events of f, they are published if it succeeds and dropped if it fails.
+    /// The events of the nested transactions wait for the outer ones.
+    pub(crate) fn transaction<T, E>(&self, f: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+        self.channels
+            .lock()
+            .pending
+            .entry(thread::current().id())
+            .or_default()
+            .push(vec![]);
+        let mut frame = TransactionFrame {
+            channels: &self.channels,
+            committed: false,
+        };
+        let result = f();
+        frame.committed = result.is_ok();
+        result
+    }
+}
+
+// Closes the transaction of the current thread when dropped, so that the events of a panicking
+// one are dropped like the failed ones instead of holding back the later events of the thread
+struct TransactionFrame<'a> {
+    channels: &'a Mutex<Channels>,
+    committed: bool,
+}
+
+impl Drop for TransactionFrame<'_> {
+    fn drop(&mut self) {
+        let mut channels = self.channels.lock();
+        let events = channels
+            .transactions()
+            .and_then(|pending| pending.pop())
+            .unwrap_or_default();
+        let outer = channels
+            .transactions()
+            .and_then(|pending| pending.last_mut());
+        match (self.committed, outer) {
+            (false, _) => {}
+            (true, Some(outer)) => outer.extend(events),
+            (true, None) => events.into_iter().for_each(|event| channels.send(event)),
+        }
+        if channels
+            .transactions()
+            .is_some_and(|pending| pending.is_empty())
+        {
+            channels.pending.remove(&thread::current().id());
+        }
+    }
+}
+
+impl Channels {
+    // The open transactions of the current thread
+    fn transactions(&mut self) -> Option<&mut Vec<Vec<Event>>> {
+        self.pending.get_mut(&thread::current().id())
+    }
+
+    fn send(&mut self, event: Event) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
@@ -3,53 +3,213 @@ pub mod subgroups_mod;
 use subgroups_mod::SubGroups;
 
 use crate::abstracts::{Loadable, Saveable};
+use crate::events::{Event, Events};
+use crate::groups_mod::subgroups_mod::nodes_mod::node_types::{
+    NodeTypeDefinition, NodeTypesCache, NodeTypesRegistry, UniquenessScope,
+};
+use crate::groups_mod::subgroups_mod::nodes_mod::paths::resolve_in_trees;
+use crate::groups_mod::subgroups_mod::nodes_mod::refresh::{
+    changed_rows, changes_after, check_versions,
+};
+use crate::groups_mod::subgroups_mod::nodes_mod::undo::UndoLog;
+use crate::groups_mod::subgroups_mod::nodes_mod::{
+    GraphNode, Node, NodeType, NodesTree, ParsedPath, PathResolutionError, RefreshReport,
+    RelanotesError, RelanotesValidationRejection,
+};
 use crate::groups_mod::subgroups_mod::SubGroupAbstraction;
-use crate::models::GroupElement;
-use crate::schema::groups;
-use diesel::prelude::*;
-use diesel::SqliteConnection;
+use crate::models::{ChangeElement, GroupElement};
+use crate::storage::SharedStorage;
 use std::collections::HashMap;
+use std::sync::Arc;
 
-pub struct GroupAbstraction<'a> {
+#[derive(Clone)]
+pub struct GroupAbstraction {
     pub group: GroupElement,
-    conn: &'a SqliteConnection,
-    pub subgroups: SubGroups<'a>,
+    storage: SharedStorage,
+    pub subgroups: SubGroups,
+    events: Events,
 }
 
-impl<'a> GroupAbstraction<'a> {
-    fn new(conn: &'a SqliteConnection, group: GroupElement) -> Self {
-        let subgroups = SubGroups::new(conn, group.id);
+impl GroupAbstraction {
+    fn new(
+        storage: SharedStorage,
+        group: GroupElement,
+        node_types_cache: NodeTypesCache,
+        events: Events,
+    ) -> Self {
+        let subgroups =
+            SubGroups::in_workspace(storage.clone(), group.id, node_types_cache, events.clone());
         GroupAbstraction {
             group,
-            conn,
+            storage,
             subgroups,
+            events,
         }
     }
+
+    /// The subgroup with the name, the subgroups are loaded on the first access
+    pub fn subgroup(&mut self, name: &str) -> Result<&mut SubGroupAbstraction, RelanotesError> {
+        if !self.subgroups.loaded {
+            self.subgroups.load()?;
+        }
+        self.subgroups
+            .subgroups_map
+            .values_mut()
+            .find(|sg| sg.subgroup.name == name)
+            .ok_or_else(|| RelanotesError::SubGroupNameNotFound(name.to_string()))
+    }
+
+    pub fn create_subgroup(
+        &mut self,
+        name: &str,
+    ) -> Result<&mut SubGroupAbstraction, RelanotesError> {
+        if !self.subgroups.loaded {
+            self.subgroups.load()?;
+        }
+        let subgroup_id = self.subgroups.create(name.to_string())?.subgroup.id;
+        Ok(self.subgroups.subgroups_map.get_mut(&subgroup_id).unwrap())
+    }
+
+    /// Resolves the path across the loaded subgroups of the group. Without the subgroup prefix
+    /// the path can start in any loaded subgroup.
+    pub fn resolve_path(&self, path: &str) -> Result<i32, PathResolutionError> {
+        let path = ParsedPath::parse(path)?;
+        let trees = self
+            .subgroups
+            .subgroups_map
+            .values()
+            .filter(|sg| sg.nodes.loaded)
+            .map(|sg| &sg.nodes)
+            .collect::<Vec<&NodesTree>>();
+        let start_trees = match &path.subgroup_name {
+            Some(subgroup_name) => {
+                let subgroup = self
+                    .subgroups
+                    .subgroups_map
+                    .values()
+                    .find(|sg| sg.subgroup.name == *subgroup_name)
+                    .ok_or_else(|| PathResolutionError::SubGroupNotFound(subgroup_name.clone()))?;
+                if !subgroup.nodes.loaded {
+                    return Err(PathResolutionError::SubGroupNotLoaded(
+                        subgroup_name.clone(),
+                    ));
+                }
+                vec![&subgroup.nodes]
+            }
+            None => trees.clone(),
+        };
+        resolve_in_trees(&trees, &start_trees, &path)
+    }
 }
 
-impl<'a> Saveable for GroupAbstraction<'a> {
+impl Saveable for GroupAbstraction {
     fn save(&self) -> Result<(), diesel::result::Error> {
-        diesel::update(groups::table.filter(groups::id.eq(self.group.id)))
-            .set(groups::name.eq(&self.group.name))
-            .execute(self.conn)?;
+        self.storage.update_group(&self.group)?;
+        self.events.publish(|| Event::GroupUpdated {
+            group: self.group.clone(),
+        });
         Ok(())
     }
 }
 
 /// Groups
-
-pub struct Groups<'a> {
-    conn: &'a SqliteConnection,
-    pub groups_map: HashMap<i32, GroupAbstraction<'a>>,
+#[derive(Clone)]
+pub struct Groups {
+    storage: SharedStorage,
+    // Shared by all trees, so that the node types are loaded once
+    node_types_cache: NodeTypesCache,
+    events: Events,
+    pub groups_map: HashMap<i32, GroupAbstraction>,
+    // The last change of the database applied to the groups
+    synced_change: i32,
     pub loaded: bool,
+    // The groups added and removed by the open transactions
+    undo_log: UndoLog<i32, GroupAbstraction, GroupsState>,
 }
 
-impl<'a> Groups<'a> {
-    pub fn new(conn: &'a SqliteConnection) -> Self {
+// The groups besides the map, saved when the transaction begins
+#[derive(Clone)]
+struct GroupsState {
+    groups: Vec<GroupElement>,
+    node_types: Option<Arc<NodeTypesRegistry>>,
+    synced_change: i32,
+    loaded: bool,
+}
+
+impl Groups {
+    pub fn new(storage: SharedStorage) -> Self {
         Groups {
-            conn,
+            storage,
+            node_types_cache: NodeTypesCache::default(),
+            events: Events::default(),
             groups_map: HashMap::new(),
+            synced_change: 0,
             loaded: false,
+            undo_log: UndoLog::default(),
+        }
+    }
+    fn new_group(&self, group: GroupElement) -> GroupAbstraction {
+        GroupAbstraction::new(
+            self.storage.clone(),
+            group,
+            self.node_types_cache.clone(),
+            self.events.clone(),
+        )
+    }
+    /// The events of all groups, subgroups and trees of these groups
+    pub fn events(&self) -> &Events {
+        &self.events
+    }
+    // Only the groups are copied, their subgroups and trees keep their own undo logs
+    pub(crate) fn begin_transaction(&mut self, transaction_id: u64) {
+        self.undo_log.begin(transaction_id);
+        let Groups {
+            undo_log,
+            node_types_cache,
+            groups_map,
+            synced_change,
+            loaded,
+            ..
+        } = self;
+        undo_log.save_state(|| GroupsState {
+            groups: groups_map.values().map(|g| g.group.clone()).collect(),
+            node_types: node_types_cache.cached(),
+            synced_change: *synced_change,
+            loaded: *loaded,
+        });
+        for group in self.groups_map.values_mut() {
+            group.subgroups.begin_transaction(transaction_id);
+        }
+    }
+    pub(crate) fn commit_transaction(&mut self, transaction_id: u64) {
+        self.undo_log.commit(transaction_id);
+        for group in self.groups_map.values_mut() {
+            group.subgroups.commit_transaction(transaction_id);
+        }
+    }
+    pub(crate) fn rollback_transaction(&mut self, transaction_id: u64) {
+        if let Some(state) = self.undo_log.rollback(&mut self.groups_map, transaction_id) {
+            for group in state.groups {
+                if let Some(loaded) = self.groups_map.get_mut(&group.id) {
+                    loaded.group = group;
+                }
+            }
+            self.synced_change = state.synced_change;
+            self.loaded = state.loaded;
+            // The types saved in the transaction are gone from the database, the cache is
+            // loaded again instead of being put back, it could be reloaded by other workspaces
+            // sharing it meanwhile. The trees put back their own types.
+            let cached = self.node_types_cache.cached();
+            let unchanged = match (&cached, &state.node_types) {
+                (Some(cached), Some(saved)) => Arc::ptr_eq(cached, saved),
+                (cached, saved) => cached.is_none() && saved.is_none(),
+            };
+            if !unchanged {
+                self.node_types_cache.clear();
+            }
+        }
+        for group in self.groups_map.values_mut() {
+            group.subgroups.rollback_transaction(transaction_id);
         }
     }
     pub fn get_group_from_subgroup(&self, subgroup_id: i32) -> Option<i32> {
@@ -72,7 +232,7 @@ impl<'a> Groups<'a> {
     pub fn get_mut_subgroup_abstraction(
         &mut self,
         subgroup_id: i32,
-    ) -> Option<&mut SubGroupAbstraction<'a>> {
+    ) -> Option<&mut SubGroupAbstraction> {
         self.groups_map
             .get_mut(&self.get_group_from_subgroup(subgroup_id)?)?
             .subgroups
@@ -81,39 +241,522 @@ impl<'a> Groups<'a> {
     }
 }
 
-impl<'a> Loadable for Groups<'a> {
+impl Loadable for Groups {
     fn load(&mut self) -> Result<(), diesel::result::Error> {
-        let groups: Vec<GroupElement> = groups::table.load::<GroupElement>(self.conn)?;
-        self.groups_map = groups
+        let synced_change = self.storage.last_change()?;
+        let groups: Vec<GroupElement> = self.storage.load_groups()?;
+        let groups_map = groups
             .into_iter()
-            .map(|g| (*&g.id, GroupAbstraction::new(self.conn, g)))
+            .map(|g| (g.id, self.new_group(g)))
             .collect();
+        self.undo_log.replace(&mut self.groups_map, groups_map);
+        self.synced_change = synced_change;
         self.loaded = true;
         Ok(())
     }
 }
 
+// Applying the changes of the other processes
+impl Groups {
+    /// Applies the changes written since the groups were loaded or refreshed to the groups,
+    /// their loaded subgroups and trees. Only the changed rows are loaded again.
+    pub fn refresh(&mut self) -> Result<RefreshReport, diesel::result::Error> {
+        match changes_after(&*self.storage, self.synced_change)? {
+            Some(changes) => self.apply_changes(&changes),
+            None => self.reload(),
+        }
+    }
+
+    /// The last change applied to the loaded groups. The subgroups and trees synced at least up
+    /// to it, so the changes before it aren't needed by the model anymore.
+    pub fn synced_change(&self) -> Option<i32> {
+        Some(self.synced_change).filter(|_| self.loaded)
+    }
+
+    // Loads the groups again when the changes since they were synced were pruned from the log.
+    // The subgroups are refreshed from their own marks.
+    fn reload(&mut self) -> Result<RefreshReport, diesel::result::Error> {
+        let mut report = RefreshReport::default();
+        if !self.loaded {
+            return Ok(report);
+        }
+        let synced_change = self.storage.last_change()?;
+        let mut current = self
+            .storage
+            .load_groups()?
+            .into_iter()
+            .map(|group| (group.id, group))
+            .collect::<HashMap<i32, GroupElement>>();
+        let mut group_ids = self
+            .groups_map
+            .keys()
+            .chain(current.keys())
+            .copied()
+            .collect::<Vec<i32>>();
+        group_ids.sort_unstable();
+        group_ids.dedup();
+        for group_id in group_ids {
+            if self.apply_group(group_id, current.remove(&group_id)) {
+                report.groups.push(group_id);
+            }
+        }
+        for group in self.groups_map.values_mut() {
+            report.append(group.subgroups.refresh()?);
+        }
+        self.synced_change = synced_change;
+        report.reloaded = true;
+        Ok(report)
+    }
+
+    fn apply_changes(
+        &mut self,
+        changes: &[ChangeElement],
+    ) -> Result<RefreshReport, diesel::result::Error> {
+        let mut report = RefreshReport::default();
+        if !self.loaded {
+            return Ok(report);
+        }
+        for group_id in changed_rows(changes, self.synced_change, "groups", None) {
+            let current = self.storage.get_group(group_id)?;
+            if self.apply_group(group_id, current) {
+                report.groups.push(group_id);
+            }
+        }
+        for group in self.groups_map.values_mut() {
+            report.append(group.subgroups.apply_changes(changes)?);
+        }
+        if let Some(last) = changes.last() {
+            self.synced_change = self.synced_change.max(last.id);
+        }
+        Ok(report)
+    }
+
+    // Puts the current row of the group in the model, false if it was up to date
+    fn apply_group(&mut self, group_id: i32, current: Option<GroupElement>) -> bool {
+        match (current, self.groups_map.get_mut(&group_id)) {
+            (None, None) => return false,
+            (None, Some(_)) => {
+                self.undo_log.discard(&mut self.groups_map, group_id);
+                self.events.publish(|| Event::GroupDeleted { group_id });
+            }
+            (Some(group), Some(loaded)) => {
+                if loaded.group.name == group.name {
+                    return false;
+                }
+                loaded.group = group.clone();
+                self.events.publish(|| Event::GroupUpdated { group });
+            }
+            (Some(group), None) => {
+                let group_abstraction = self.new_group(group.clone());
+                self.undo_log
+                    .insert(&mut self.groups_map, group_id, group_abstraction);
+                self.events.publish(|| Event::GroupCreated { group });
+            }
+        }
+        true
+    }
+}
+
 // Adding new groups
-impl<'a> Groups<'a> {
-    pub fn create(&mut self, name: String) -> Result<&GroupAbstraction<'a>, diesel::result::Error> {
-        diesel::insert_into(groups::table)
-            .values((groups::name.eq(&name),))
-            .execute(self.conn)?;
-        let group = groups::table
-            .filter(groups::name.eq(&name))
-            .first::<GroupElement>(self.conn)?;
+impl Groups {
+    pub fn create(&mut self, name: String) -> Result<&GroupAbstraction, diesel::result::Error> {
+        let group = self.storage.create_group(&name)?;
         let group_id = group.id;
-        let group_abstraction = GroupAbstraction::new(self.conn, group);
-        self.groups_map.insert(group_id, group_abstraction);
+        let group_abstraction = self.new_group(group.clone());
+        self.undo_log
+            .insert(&mut self.groups_map, group_id, group_abstraction);
+        self.events.publish(|| Event::GroupCreated { group });
         Ok(self.groups_map.get(&group_id).unwrap())
     }
 }
 
+// Adding node types
+impl Groups {
+    /// Saves the node type and makes it available in all trees
+    pub fn save_node_type(
+        &mut self,
+        definition: &NodeTypeDefinition,
+    ) -> Result<i32, diesel::result::Error> {
+        let type_id = definition.save(&*self.storage)?;
+        self.node_types_cache.reload(&*self.storage)?;
+        for group in self.groups_map.values_mut() {
+            for subgroup in group.subgroups.subgroups_map.values_mut() {
+                subgroup.nodes.refresh_node_types()?;
+            }
+        }
+        Ok(type_id)
+    }
+}
+
 // Deleting existing groups
-impl<'a> Groups<'a> {
+impl Groups {
     pub fn delete(&mut self, group_id: i32) -> Result<(), diesel::result::Error> {
-        diesel::delete(groups::table.filter(groups::id.eq(group_id))).execute(self.conn)?;
-        self.groups_map.remove(&group_id); // Even if the group was not registered, not catching the error, because the removal was successful
+        self.storage.delete_group(group_id)?;
+        self.undo_log.discard(&mut self.groups_map, group_id); // Even if the group was not registered, not catching the error, because the removal was successful
+        self.events.publish(|| Event::GroupDeleted { group_id });
+        Ok(())
+    }
+}
+
+// Moving subtrees between subgroups
+impl Groups {
+    pub fn get_subgroup_of_node(&self, node_id: i32) -> Option<i32> {
+        self.groups_map.values().find_map(|g| {
+            g.subgroups
+                .subgroups_map
+                .values()
+                .find(|sg| sg.nodes.nodes_map.contains_key(&node_id))
+                .map(|sg| sg.subgroup.id)
+        })
+    }
+
+    /// Moves the node with all its descendants into the target subgroup (can be in another
+    /// group). Symlinks that become links inside one subgroup are replaced with their sources.
+    pub fn move_subtree(
+        &mut self,
+        node_id: i32,
+        target_subgroup_id: i32,
+        new_parent_id: Option<i32>,
+    ) -> Result<(), RelanotesError> {
+        let source_subgroup_id = self
+            .get_subgroup_of_node(node_id)
+            .ok_or(RelanotesError::NodeNotFound(node_id))?;
+        if source_subgroup_id == target_subgroup_id {
+            return self
+                .get_mut_subgroup_abstraction(source_subgroup_id)
+                .unwrap()
+                .nodes
+                .move_node(node_id, new_parent_id);
+        }
+        let target_group_id = self
+            .get_group_from_subgroup(target_subgroup_id)
+            .ok_or(RelanotesError::SubGroupNotFound(target_subgroup_id))?;
+        let target = self
+            .get_mut_subgroup_abstraction(target_subgroup_id)
+            .unwrap();
+        if !target.nodes.loaded {
+            target
+                .nodes
+                .load()
+                .map_err(RelanotesError::DBQueriesError)?;
+        }
+        let source_group_id = self.get_group_from_subgroup(source_subgroup_id).unwrap();
+        let source = &self
+            .get_subgroup_abstraction(source_subgroup_id)
+            .unwrap()
+            .nodes;
+        let target = &self
+            .get_subgroup_abstraction(target_subgroup_id)
+            .unwrap()
+            .nodes;
+
+        let mut subtree_ids = source.get_descendants(node_id);
+        subtree_ids.push(node_id);
+        let get_graph_node = |id: &i32| -> Option<&GraphNode> {
+            source
+                .nodes_map
+                .get(id)
+                .filter(|_| subtree_ids.contains(id))
+                .or_else(|| target.nodes_map.get(id))
+        };
+
+        let root = &source.nodes_map.get(&node_id).unwrap().node;
+        let root_is_symlink = root.get_node_type() == NodeType::SymLink;
+        if let Node::SymLink { .. } = root {
+            // The symlink stays linked to its source
+            if new_parent_id.is_some() {
+                return Err(RelanotesError::NodeMutationError(
+                    "Can't link the symlink to a new parent".into(),
+                ));
+            }
+        } else {
+            if let Some(new_parent_id) = new_parent_id {
+                if !target.nodes_map.contains_key(&new_parent_id) {
+                    return Err(RelanotesError::NodeNotFound(new_parent_id));
+                }
+            }
+        }
+
+        // Symlinks from the subtree to the target subgroup and from the target subgroup to the
+        // subtree will become links inside one subgroup
+        let symlink_source = |graph_node: &GraphNode| match graph_node.node {
+            Node::SymLink { source_node_id, .. } => Some(source_node_id),
+            _ => None,
+        };
+        let merged_symlinks = subtree_ids
+            .iter()
+            .filter(|id| {
+                symlink_source(source.nodes_map.get(id).unwrap())
+                    .is_some_and(|s| target.nodes_map.contains_key(&s))
+            })
+            .chain(
+                target
+                    .nodes_map
+                    .iter()
+                    .filter(|(_, graph_node)| {
+                        symlink_source(graph_node).is_some_and(|s| subtree_ids.contains(&s))
+                    })
+                    .map(|(id, _)| id),
+            )
+            .copied()
+            .collect::<Vec<i32>>();
+
+        let relinked_children = merged_symlinks
+            .iter()
+            .map(|symlink_id| {
+                let symlink = get_graph_node(symlink_id).unwrap();
+                (symlink.node.get_linked_to_id(), symlink.children.clone())
+            })
+            .collect::<Vec<(Option<i32>, Vec<i32>)>>();
+        let root_name = root.get_name().to_string();
+        let root_children = source.nodes_map.get(&node_id).unwrap().children.clone();
+        let relinked_ids = relinked_children
+            .iter()
+            .flat_map(|(_, children)| children.iter().copied())
+            .collect::<Vec<i32>>();
+        let written = subtree_ids
+            .iter()
+            .chain(&merged_symlinks)
+            .chain(&relinked_ids)
+            .filter_map(get_graph_node)
+            .collect::<Vec<&GraphNode>>();
+
+        // The validation reads the group namespace, so it's run in the writing transaction
+        let validate = || -> Result<(), RelanotesError> {
+            if !root_is_symlink {
+                target
+                    .validate_node_mutation_or_creation(
+                        Some(node_id),
+                        root.get_name(),
+                        root.get_description(),
+                        new_parent_id,
+                        target_subgroup_id,
+                        target_group_id,
+                        root.get_node_type(),
+                    )
+                    .map_err(RelanotesError::ValidationRejection)?;
+            }
+            if root.get_node_type() == NodeType::Divisor {
+                target
+                    .validate_divisor_children(
+                        &[],
+                        &source.get_namespace_nodes(node_id),
+                        new_parent_id,
+                    )
+                    .map_err(RelanotesError::ValidationRejection)?;
+            }
+
+            // Nodes unique in the group or subgroup namespaces can collide with the target ones
+            let mut group_unique = vec![];
+            for id in &subtree_ids {
+                let node = &source.nodes_map.get(id).unwrap().node;
+                if let Node::SymLink { source_node_id, .. } = node {
+                    if target.nodes_map.values().any(|other| {
+                        other.node.get_node_type() == NodeType::SymLink
+                            && other.node.get_linked_to_id() == Some(*source_node_id)
+                    }) {
+                        return Err(RelanotesError::ValidationRejection(
+                            RelanotesValidationRejection::DuplicateSymLink(*source_node_id),
+                        ));
+                    }
+                }
+                let node_type = node.get_node_type();
+                let rules = match source.get_node_types().get_rules(&node_type) {
+                    Some(rules) => rules,
+                    None => continue,
+                };
+                match rules.uniqueness_scope {
+                    UniquenessScope::Group if source_group_id != target_group_id => group_unique
+                        .push((
+                            source.get_node_type_id_from_type(&node_type),
+                            node.get_name().to_string(),
+                        )),
+                    UniquenessScope::Subgroup
+                        if target.nodes_map.values().any(|other| {
+                            other.node.get_node_type() == node_type
+                                && other.node.get_name() == node.get_name()
+                        }) =>
+                    {
+                        return Err(RelanotesError::ValidationRejection(
+                            RelanotesValidationRejection::duplicate(
+                                node_type,
+                                &rules.value,
+                                node.get_name(),
+                            ),
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+            if !group_unique.is_empty() {
+                let names = group_unique
+                    .iter()
+                    .map(|(_, name)| &name[..])
+                    .collect::<Vec<&str>>();
+                let existing = self
+                    .storage
+                    .find_nodes_in_group(target_group_id, &names)
+                    .map_err(RelanotesError::DBQueriesError)?
+                    .into_iter()
+                    .map(|node| (node.type_id, node.name))
+                    .collect::<Vec<(i32, String)>>();
+                if let Some((type_id, name)) = group_unique.iter().find(|n| existing.contains(n)) {
+                    let node_types = source.get_node_types();
+                    return Err(RelanotesError::ValidationRejection(
+                        RelanotesValidationRejection::duplicate(
+                            node_types.get_node_type(*type_id).unwrap(),
+                            &node_types.get(*type_id).unwrap().value,
+                            name,
+                        ),
+                    ));
+                }
+            }
+
+            // The children of merged symlinks join the namespace of the source, where the types
+            // unique in the owner can't repeat the names
+            let tree_of = |id: &i32| {
+                if subtree_ids.contains(id) {
+                    source
+                } else {
+                    target
+                }
+            };
+            let mut namespaces: HashMap<i32, Vec<i32>> = HashMap::new();
+            for symlink_id in &merged_symlinks {
+                let symlink = get_graph_node(symlink_id).unwrap();
+                let source_node_id = symlink.node.get_linked_to_id().unwrap();
+                let source_tree = tree_of(&source_node_id);
+                let owner_id = source_tree
+                    .get_namespace_owner(source_node_id)
+                    .unwrap_or(source_node_id);
+                let namespace = namespaces
+                    .entry(owner_id)
+                    .or_insert_with(|| source_tree.get_namespace_children(owner_id));
+                for child_id in tree_of(symlink_id).get_namespace_children(*symlink_id) {
+                    let child = &get_graph_node(&child_id).unwrap().node;
+                    let node_type = child.get_node_type();
+                    let unique_in_owner = source
+                        .get_node_types()
+                        .get_rules(&node_type)
+                        .is_some_and(|rules| rules.uniqueness_scope == UniquenessScope::Owner);
+                    if unique_in_owner
+                        && namespace.iter().any(|sibling_id| {
+                            let sibling = &get_graph_node(sibling_id).unwrap().node;
+                            sibling.get_node_type() == node_type
+                                && sibling.get_name() == child.get_name()
+                        })
+                    {
+                        return Err(RelanotesError::ChildCannotBeLifted(child_id));
+                    }
+                    namespace.push(child_id);
+                }
+            }
+            Ok(())
+        };
+
+        let storage = &*self.storage;
+        // The merged symlinks are removed first, the database doesn't allow links inside one
+        // subgroup
+        storage.transaction::<_, RelanotesError, _>(|| {
+            check_versions(storage, written)?;
+            // The children quarantined while loading or added by other processes would stay in
+            // the source subgroup, the symlinks of the other subgroups keep their sources
+            let referenced_by = storage
+                .find_linked_nodes(&subtree_ids)
+                .map_err(RelanotesError::DBQueriesError)?
+                .into_iter()
+                .filter(|node| {
+                    node.subgroup_id == source_subgroup_id && !subtree_ids.contains(&node.id)
+                })
+                .map(|node| node.id)
+                .collect::<Vec<i32>>();
+            if !referenced_by.is_empty() {
+                return Err(RelanotesError::NodeIsReferenced {
+                    node_id,
+                    referenced_by,
+                });
+            }
+            validate()?;
+            let write = || -> Result<(), diesel::result::Error> {
+                for (source_node_id, children) in &relinked_children {
+                    storage.set_linked_to_id(children, *source_node_id)?;
+                }
+                storage.delete_nodes(&merged_symlinks)?;
+                storage.set_subgroup_id(&subtree_ids, target_subgroup_id)?;
+                if !root_is_symlink {
+                    storage.set_linked_to_id(&[node_id], new_parent_id)?;
+                }
+                Ok(())
+            };
+            write().map_err(|e| RelanotesError::from_node_write(e, Some(node_id), &root_name))
+        })?;
+
+        let mut subtree = self
+            .get_mut_subgroup_abstraction(source_subgroup_id)
+            .unwrap()
+            .nodes
+            .detach_subtree(node_id);
+        if !root_is_symlink {
+            let root = subtree.get_mut(&node_id).unwrap();
+            root.parent_node_id = new_parent_id;
+            root.node.set_linked_to_id(new_parent_id);
+        }
+        let target = &mut self
+            .get_mut_subgroup_abstraction(target_subgroup_id)
+            .unwrap()
+            .nodes;
+        target.attach_subtree(node_id, subtree);
+        for symlink_id in &merged_symlinks {
+            target.merge_symlink_into_source(*symlink_id);
+        }
+        target.bump_versions(&relinked_ids);
+        target.bump_versions(&subtree_ids);
+        if !root_is_symlink {
+            target.bump_versions(&[node_id]);
+        }
+
+        // The merged symlinks are gone from the subgroups where the views have them
+        let events = target.events();
+        let (moved_symlinks, target_symlinks): (Vec<i32>, Vec<i32>) = merged_symlinks
+            .into_iter()
+            .partition(|id| subtree_ids.contains(id));
+        for (subgroup_id, node_ids) in [
+            (source_subgroup_id, moved_symlinks),
+            (target_subgroup_id, target_symlinks),
+        ] {
+            if !node_ids.is_empty() {
+                events.publish(|| Event::NodeDeleted {
+                    subgroup_id,
+                    node_ids,
+                });
+            }
+        }
+        // The root merged into its source is deleted, its children are moved in its place
+        let moved_ids = if target.nodes_map.contains_key(&node_id) {
+            vec![node_id]
+        } else {
+            root_children
+        };
+        for moved_id in moved_ids {
+            events.publish(|| Event::NodeMoved {
+                node_id: moved_id,
+                from_subgroup_id: source_subgroup_id,
+                to_subgroup_id: target_subgroup_id,
+                nodes: target.get_subtree(moved_id),
+            });
+        }
+        // The children of the merged symlinks are linked to the sources now
+        for child_id in relinked_ids
+            .into_iter()
+            .filter(|id| !subtree_ids.contains(id))
+        {
+            events.publish(|| Event::NodeMoved {
+                node_id: child_id,
+                from_subgroup_id: target_subgroup_id,
+                to_subgroup_id: target_subgroup_id,
+                nodes: target.get_subtree(child_id),
+            });
+        }
         Ok(())
     }
 }
@@ -1,58 +1,321 @@
-use crate::models::SubGroupElement;
-use crate::schema::subgroups;
-use diesel::SqliteConnection;
+use crate::events::{Event, Events};
+use crate::models::{ChangeElement, SubGroupElement};
+use crate::storage::SharedStorage;
 pub mod nodes_mod;
-use crate::abstracts::Loadable;
-use diesel::prelude::*;
+use crate::abstracts::{Loadable, Saveable};
 use diesel::result::Error;
-use nodes_mod::NodesTree;
+use nodes_mod::node_types::NodeTypesCache;
+use nodes_mod::refresh::{changed_rows, changes_after};
+use nodes_mod::undo::UndoLog;
+use nodes_mod::{Node, NodesTree, RefreshReport, RelanotesError};
 use std::collections::HashMap;
 
-pub struct SubGroupAbstraction<'a> {
-    conn: &'a SqliteConnection,
+#[derive(Clone)]
+pub struct SubGroupAbstraction {
+    storage: SharedStorage,
     pub subgroup: SubGroupElement,
-    pub nodes: NodesTree<'a>,
+    pub nodes: NodesTree,
+    events: Events,
 }
 
-impl<'a> SubGroupAbstraction<'a> {
-    pub fn new(conn: &'a SqliteConnection, subgroup: SubGroupElement) -> Self {
-        let nodes_tree = NodesTree::new(conn, subgroup.id);
+impl SubGroupAbstraction {
+    pub fn new(
+        storage: SharedStorage,
+        subgroup: SubGroupElement,
+        node_types_cache: NodeTypesCache,
+        events: Events,
+    ) -> Self {
+        let nodes_tree = NodesTree::in_workspace(
+            storage.clone(),
+            subgroup.id,
+            node_types_cache,
+            events.clone(),
+        );
         SubGroupAbstraction {
-            conn,
+            storage,
             subgroup,
             nodes: nodes_tree,
+            events,
         }
     }
+
+    /// The nodes of the subgroup, they are loaded on the first access
+    pub fn tree(&mut self) -> Result<&mut NodesTree, Error> {
+        if !self.nodes.loaded {
+            self.nodes.load()?;
+        }
+        Ok(&mut self.nodes)
+    }
+
+    pub fn node_by_path(&mut self, path: &str) -> Result<&Node, RelanotesError> {
+        let tree = self.tree()?;
+        let node_id = tree.resolve_path(path)?;
+        Ok(&tree.nodes_map[&node_id].node)
+    }
+}
+
+impl Saveable for SubGroupAbstraction {
+    fn save(&self) -> Result<(), Error> {
+        self.storage.update_subgroup(&self.subgroup)?;
+        self.events.publish(|| Event::SubGroupUpdated {
+            subgroup: self.subgroup.clone(),
+        });
+        Ok(())
+    }
 }
 
-pub struct SubGroups<'a> {
-    conn: &'a SqliteConnection,
+#[derive(Clone)]
+pub struct SubGroups {
+    storage: SharedStorage,
     group_id: i32,
-    pub subgroups_map: HashMap<i32, SubGroupAbstraction<'a>>,
+    node_types_cache: NodeTypesCache,
+    events: Events,
+    pub subgroups_map: HashMap<i32, SubGroupAbstraction>,
+    // The last change of the database applied to the subgroups
+    synced_change: i32,
     pub loaded: bool,
+    // The subgroups added and removed by the open transactions
+    undo_log: UndoLog<i32, SubGroupAbstraction, SubGroupsState>,
+}
+
+// The subgroups besides the map, saved when the transaction begins
+#[derive(Clone)]
+struct SubGroupsState {
+    subgroups: Vec<SubGroupElement>,
+    synced_change: i32,
+    loaded: bool,
 }
 
-impl<'a> SubGroups<'a> {
-    pub fn new(conn: &'a SqliteConnection, group_id: i32) -> Self {
+impl SubGroups {
+    pub fn new(storage: SharedStorage, group_id: i32) -> Self {
+        SubGroups::in_workspace(
+            storage,
+            group_id,
+            NodeTypesCache::default(),
+            Events::default(),
+        )
+    }
+
+    /// The subgroups that share the node types and the events with the other groups of the
+    /// workspace
+    pub fn in_workspace(
+        storage: SharedStorage,
+        group_id: i32,
+        node_types_cache: NodeTypesCache,
+        events: Events,
+    ) -> Self {
         SubGroups {
-            conn,
+            storage,
             group_id,
+            node_types_cache,
+            events,
             subgroups_map: HashMap::new(),
+            synced_change: 0,
             loaded: false,
+            undo_log: UndoLog::default(),
+        }
+    }
+
+    fn new_subgroup(&self, subgroup: SubGroupElement) -> SubGroupAbstraction {
+        SubGroupAbstraction::new(
+            self.storage.clone(),
+            subgroup,
+            self.node_types_cache.clone(),
+            self.events.clone(),
+        )
+    }
+
+    // Only the subgroups are copied, their trees keep their own undo logs
+    pub(crate) fn begin_transaction(&mut self, transaction_id: u64) {
+        self.undo_log.begin(transaction_id);
+        let SubGroups {
+            undo_log,
+            subgroups_map,
+            synced_change,
+            loaded,
+            ..
+        } = self;
+        undo_log.save_state(|| SubGroupsState {
+            subgroups: subgroups_map
+                .values()
+                .map(|sg| sg.subgroup.clone())
+                .collect(),
+            synced_change: *synced_change,
+            loaded: *loaded,
+        });
+        for subgroup in self.subgroups_map.values_mut() {
+            subgroup.nodes.begin_transaction(transaction_id);
+        }
+    }
+
+    pub(crate) fn commit_transaction(&mut self, transaction_id: u64) {
+        self.undo_log.commit(transaction_id);
+        for subgroup in self.subgroups_map.values_mut() {
+            subgroup.nodes.commit_transaction(transaction_id);
+        }
+    }
+
+    pub(crate) fn rollback_transaction(&mut self, transaction_id: u64) {
+        if let Some(state) = self
+            .undo_log
+            .rollback(&mut self.subgroups_map, transaction_id)
+        {
+            for subgroup in state.subgroups {
+                if let Some(loaded) = self.subgroups_map.get_mut(&subgroup.id) {
+                    loaded.subgroup = subgroup;
+                }
+            }
+            self.synced_change = state.synced_change;
+            self.loaded = state.loaded;
+        }
+        for subgroup in self.subgroups_map.values_mut() {
+            subgroup.nodes.rollback_transaction(transaction_id);
         }
     }
 }
 
-impl<'a> Loadable for SubGroups<'a> {
+impl Loadable for SubGroups {
     fn load(&mut self) -> Result<(), Error> {
-        let subgroups: Vec<SubGroupElement> = subgroups::table
-            .filter(subgroups::group_id.eq(self.group_id))
-            .load::<SubGroupElement>(self.conn)?;
-        self.subgroups_map = subgroups
+        let synced_change = self.storage.last_change()?;
+        let subgroups: Vec<SubGroupElement> = self.storage.load_subgroups(Some(self.group_id))?;
+        let subgroups_map = subgroups
             .into_iter()
-            .map(|g| (*&g.id, SubGroupAbstraction::new(self.conn, g)))
+            .map(|g| (g.id, self.new_subgroup(g)))
             .collect();
+        self.undo_log
+            .replace(&mut self.subgroups_map, subgroups_map);
+        self.synced_change = synced_change;
         self.loaded = true;
         Ok(())
     }
 }
+
+// Adding new subgroups
+impl SubGroups {
+    pub fn create(&mut self, name: String) -> Result<&SubGroupAbstraction, Error> {
+        let subgroup = self.storage.create_subgroup(self.group_id, &name)?;
+        let subgroup_id = subgroup.id;
+        let subgroup_abstraction = self.new_subgroup(subgroup.clone());
+        self.undo_log
+            .insert(&mut self.subgroups_map, subgroup_id, subgroup_abstraction);
+        self.events.publish(|| Event::SubGroupCreated { subgroup });
+        Ok(self.subgroups_map.get(&subgroup_id).unwrap())
+    }
+}
+
+// Deleting existing subgroups
+impl SubGroups {
+    pub fn delete(&mut self, subgroup_id: i32) -> Result<(), Error> {
+        self.storage.delete_subgroup(subgroup_id)?;
+        self.undo_log.discard(&mut self.subgroups_map, subgroup_id);
+        let group_id = self.group_id;
+        self.events.publish(|| Event::SubGroupDeleted {
+            group_id,
+            subgroup_id,
+        });
+        Ok(())
+    }
+}
+
+// Applying the changes of the other processes
+impl SubGroups {
+    /// Applies the changes written since the subgroups were loaded or refreshed to the
+    /// subgroups and to their loaded trees
+    pub fn refresh(&mut self) -> Result<RefreshReport, Error> {
+        match changes_after(&*self.storage, self.synced_change)? {
+            Some(changes) => self.apply_changes(&changes),
+            None => self.reload(),
+        }
+    }
+
+    // Loads the subgroups again when the changes since they were synced were pruned from the
+    // log. The trees are refreshed from their own marks, they could be synced later.
+    fn reload(&mut self) -> Result<RefreshReport, Error> {
+        let mut report = RefreshReport::default();
+        if !self.loaded {
+            return Ok(report);
+        }
+        let synced_change = self.storage.last_change()?;
+        let mut current = self
+            .storage
+            .load_subgroups(Some(self.group_id))?
+            .into_iter()
+            .map(|subgroup| (subgroup.id, subgroup))
+            .collect::<HashMap<i32, SubGroupElement>>();
+        let mut subgroup_ids = self
+            .subgroups_map
+            .keys()
+            .chain(current.keys())
+            .copied()
+            .collect::<Vec<i32>>();
+        subgroup_ids.sort_unstable();
+        subgroup_ids.dedup();
+        for subgroup_id in subgroup_ids {
+            if self.apply_subgroup(subgroup_id, current.remove(&subgroup_id)) {
+                report.subgroups.push(subgroup_id);
+            }
+        }
+        for subgroup in self.subgroups_map.values_mut() {
+            report.append(subgroup.nodes.refresh()?);
+        }
+        self.synced_change = synced_change;
+        report.reloaded = true;
+        Ok(report)
+    }
+
+    pub(crate) fn apply_changes(
+        &mut self,
+        changes: &[ChangeElement],
+    ) -> Result<RefreshReport, Error> {
+        let mut report = RefreshReport::default();
+        if !self.loaded {
+            return Ok(report);
+        }
+        let group_id = self.group_id;
+        for subgroup_id in changed_rows(changes, self.synced_change, "subgroups", Some(group_id)) {
+            let current = self
+                .storage
+                .get_subgroup(subgroup_id)?
+                .filter(|subgroup| subgroup.group_id == group_id);
+            if self.apply_subgroup(subgroup_id, current) {
+                report.subgroups.push(subgroup_id);
+            }
+        }
+        for subgroup in self.subgroups_map.values_mut() {
+            report.append(subgroup.nodes.apply_changes(changes)?);
+        }
+        if let Some(last) = changes.last() {
+            self.synced_change = self.synced_change.max(last.id);
+        }
+        Ok(report)
+    }
+
+    // Puts the current row of the subgroup in the model, false if it was up to date
+    fn apply_subgroup(&mut self, subgroup_id: i32, current: Option<SubGroupElement>) -> bool {
+        let group_id = self.group_id;
+        match (current, self.subgroups_map.get_mut(&subgroup_id)) {
+            (None, None) => return false,
+            (None, Some(_)) => {
+                self.undo_log.discard(&mut self.subgroups_map, subgroup_id);
+                self.events.publish(|| Event::SubGroupDeleted {
+                    group_id,
+                    subgroup_id,
+                });
+            }
+            (Some(subgroup), Some(loaded)) => {
+                if loaded.subgroup.name == subgroup.name {
+                    return false;
+                }
+                loaded.subgroup = subgroup.clone();
+                self.events.publish(|| Event::SubGroupUpdated { subgroup });
+            }
+            (Some(subgroup), None) => {
+                let subgroup_abstraction = self.new_subgroup(subgroup.clone());
+                self.undo_log
+                    .insert(&mut self.subgroups_map, subgroup_id, subgroup_abstraction);
+                self.events.publish(|| Event::SubGroupCreated { subgroup });
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,47 @@
+use crate::models::NodeElement;
+
+/// Why the node was left out of the tree
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum QuarantineReason {
+    UnknownNodeType(i32),
+    // Sticky notes and inherited nodes need an owner
+    MissingOwner,
+    MissingSymLinkSource(Option<i32>),
+    Cycle,
+    QueryFailed(String),
+}
+
+impl std::fmt::Display for QuarantineReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            QuarantineReason::UnknownNodeType(type_id) => {
+                write!(f, "Unknown node type ({})", type_id)
+            }
+            QuarantineReason::MissingOwner => write!(f, "The owner is missing"),
+            QuarantineReason::MissingSymLinkSource(source_id) => {
+                write!(f, "The source of the symlink is missing ({:?})", source_id)
+            }
+            QuarantineReason::Cycle => write!(f, "The node is linked into a cycle"),
+            QuarantineReason::QueryFailed(e) => write!(f, "Query failed ({})", e),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct QuarantinedNode {
+    pub node: NodeElement,
+    pub reason: QuarantineReason,
+}
+
+/// The result of loading the subgroup, the broken nodes are quarantined and the rest stay usable
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct LoadReport {
+    pub loaded: Vec<i32>,
+    pub quarantined: Vec<QuarantinedNode>,
+}
+
+impl LoadReport {
+    pub fn is_clean(&self) -> bool {
+        self.quarantined.is_empty()
+    }
+}
@@ -1,16 +1,32 @@
 use crate::abstracts::Loadable;
+use crate::events::{Event, Events};
 use crate::models::NodeElement;
-use crate::schema::{node_types, nodes, subgroups};
-use diesel::prelude::*;
+use crate::storage::{NewNode, SharedStorage};
 use diesel::result::Error;
-use diesel::SqliteConnection;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
+mod load_report;
+pub mod node_types;
+pub(crate) mod paths;
+pub(crate) mod refresh;
+pub(crate) mod undo;
 mod validation_errors;
 
-use validation_errors::RelanotesValidationRejection;
+use node_types::{
+    NamePolicy, NodeTypeDefinition, NodeTypesCache, NodeTypesRegistry, UniquenessScope,
+};
+use undo::{next_transaction_id, UndoLog};
 
-#[derive(Serialize, Deserialize, Clone, Copy)]
+pub use crate::errors::RelanotesError;
+pub use load_report::{LoadReport, QuarantineReason, QuarantinedNode};
+pub use paths::{
+    NodePath, ParsedPath, ParsedSegment, ParsedSegmentKind, PathResolutionError, PathSegment,
+};
+pub use refresh::{EditConflict, RefreshReport};
+pub use validation_errors::RelanotesValidationRejection;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
 #[serde(tag = "node_type")]
 pub enum NodeType {
     // Just the type
@@ -18,75 +34,105 @@ pub enum NodeType {
     StickyNotes,
     Inherited,
     SymLink,
+    Divisor,
+    // Defined by the user in the node_types table
+    Custom { type_id: i32 },
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Debug, Clone)]
 #[serde(tag = "current_node_type")]
-pub enum Node<'a> {
+pub enum Node {
     Regular {
-        #[serde(skip_serializing)]
-        conn: &'a SqliteConnection,
         id: i32,
         name: String,
         description: Option<String>,
         associated_node_id: Option<i32>,
     },
     StickyNotes {
-        #[serde(skip_serializing)]
-        conn: &'a SqliteConnection,
         id: i32,
         name: String,
         description: Option<String>,
         owner_id: i32,
     },
     Inherited {
-        #[serde(skip_serializing)]
-        conn: &'a SqliteConnection,
         id: i32,
         name: String,
         description: Option<String>,
         parent_node_id: i32,
     },
     SymLink {
-        #[serde(skip_serializing)]
-        conn: &'a SqliteConnection,
         id: i32,
         source_node_id: i32,
         source_node_name: String, // Is not loaded from this node's name field
     },
+    // Only groups the children, is transparent for paths and namespaces
+    Divisor {
+        id: i32,
+        name: String,
+        parent_node_id: Option<i32>,
+    },
+    // The node of a user-defined type, behaves like sticky notes in paths
+    Custom {
+        id: i32,
+        type_id: i32,
+        name: String,
+        description: Option<String>,
+        linked_to_id: Option<i32>,
+    },
 }
 
-#[derive(Debug)]
-pub enum RelanotesError {
-    NodeMutationError(String),
-    DBQueriesError,
-}
-
-impl std::fmt::Display for RelanotesError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            RelanotesError::NodeMutationError(e) => write!(f, "{}", e),
-            RelanotesError::DBQueriesError => write!(f, "DBQueriesError"),
-        }
-    }
+/// What to do with the children of a node that is being deleted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeletionStrategy {
+    /// Delete only if the node has no children
+    RefuseIfHasChildren,
+    /// Delete the node together with all its descendants
+    Cascade,
+    /// Link the children to the parent of the deleted node
+    LiftChildrenToParent,
 }
 
-impl std::error::Error for RelanotesError {
-    fn description(&self) -> &str {
-        match self {
-            RelanotesError::NodeMutationError(e) => e.as_str(),
-            RelanotesError::DBQueriesError => "Got problems with running queries",
+// Rejections of the database are kept, the other errors are reported as mutation errors
+fn mutation_error(e: diesel::result::Error, id: i32, name: &str) -> RelanotesError {
+    match RelanotesError::from_node_write(e, Some(id), name) {
+        RelanotesError::DBQueriesError(_) => {
+            RelanotesError::NodeMutationError("Got DB error while mutating the node.".into())
         }
+        e => e,
     }
 }
 
-impl<'a> Node<'a> {
-    fn get_node_id(&self) -> i32 {
+impl Node {
+    pub fn get_node_id(&self) -> i32 {
         match self {
             Node::Regular { id, .. } => *id,
             Node::StickyNotes { id, .. } => *id,
             Node::Inherited { id, .. } => *id,
             Node::SymLink { id, .. } => *id,
+            Node::Divisor { id, .. } => *id,
+            Node::Custom { id, .. } => *id,
+        }
+    }
+    // For symlinks this is the name of the source node
+    pub fn get_name(&self) -> &str {
+        match self {
+            Node::Regular { name, .. } => name,
+            Node::StickyNotes { name, .. } => name,
+            Node::Inherited { name, .. } => name,
+            Node::SymLink {
+                source_node_name, ..
+            } => source_node_name,
+            Node::Divisor { name, .. } => name,
+            Node::Custom { name, .. } => name,
+        }
+    }
+    pub fn get_description(&self) -> Option<&str> {
+        match self {
+            Node::Regular { description, .. }
+            | Node::StickyNotes { description, .. }
+            | Node::Inherited { description, .. }
+            | Node::Custom { description, .. } => description.as_deref(),
+            Node::SymLink { .. } | Node::Divisor { .. } => None,
         }
     }
     pub fn get_linked_to_id(&self) -> Option<i32> {
@@ -97,6 +143,24 @@ impl<'a> Node<'a> {
             Node::StickyNotes { owner_id, .. } => Some(*owner_id),
             Node::Inherited { parent_node_id, .. } => Some(*parent_node_id),
             Node::SymLink { source_node_id, .. } => Some(*source_node_id),
+            Node::Divisor { parent_node_id, .. } => *parent_node_id,
+            Node::Custom { linked_to_id, .. } => *linked_to_id,
+        }
+    }
+    pub(crate) fn set_linked_to_id(&mut self, linked_to_id: Option<i32>) {
+        match self {
+            Node::Regular {
+                associated_node_id, ..
+            } => *associated_node_id = linked_to_id,
+            // Owned nodes can't lose their owner, the callers have to check this
+            Node::StickyNotes { owner_id, .. } => *owner_id = linked_to_id.unwrap(),
+            Node::Inherited { parent_node_id, .. } => *parent_node_id = linked_to_id.unwrap(),
+            Node::SymLink { source_node_id, .. } => *source_node_id = linked_to_id.unwrap(),
+            Node::Divisor { parent_node_id, .. } => *parent_node_id = linked_to_id,
+            Node::Custom {
+                linked_to_id: current,
+                ..
+            } => *current = linked_to_id,
         }
     }
     pub fn get_node_type(&self) -> NodeType {
@@ -105,147 +169,110 @@ impl<'a> Node<'a> {
             Node::StickyNotes { .. } => NodeType::StickyNotes,
             Node::Inherited { .. } => NodeType::Inherited,
             Node::SymLink { .. } => NodeType::SymLink,
+            Node::Divisor { .. } => NodeType::Divisor,
+            Node::Custom { type_id, .. } => NodeType::Custom { type_id: *type_id },
         }
     }
-    pub fn update_name_and_description(
+    // Only in memory, the name of the symlinks and the description of the divisors are not set
+    pub(crate) fn set_name_and_description(
         &mut self,
-        name: String,
-        description: Option<String>,
-    ) -> Result<(), RelanotesError> {
-        match &self {
+        new_name: String,
+        new_description: Option<String>,
+    ) {
+        match self {
             Node::Regular {
-                id,
-                conn,
-                name: _,
-                description: _,
-                associated_node_id,
-            } => {
-                let id = *id;
-                diesel::update(nodes::table.filter(nodes::id.eq(id)))
-                    .set((nodes::name.eq(&name), nodes::description.eq(&description)))
-                    .execute(*conn)
-                    .map_err(|_| {
-                        RelanotesError::NodeMutationError(
-                            "Got DB error while mutating the node.".into(),
-                        )
-                    })?;
-                let new_node = Node::Regular {
-                    name,
-                    description,
-                    id,
-                    conn,
-                    associated_node_id: *associated_node_id,
-                };
-                take_mut::take(self, |_| new_node);
-            }
-            Node::StickyNotes {
-                id,
-                conn,
-                name: _,
-                description: _,
-                owner_id,
-            } => {
-                let id = *id;
-                diesel::update(nodes::table.filter(nodes::id.eq(id)))
-                    .set((nodes::name.eq(&name), nodes::description.eq(&description)))
-                    .execute(*conn)
-                    .map_err(|_| {
-                        RelanotesError::NodeMutationError(
-                            "Got DB error while mutating the node.".into(),
-                        )
-                    })?;
-                let new_node = Node::StickyNotes {
-                    name,
-                    description,
-                    id,
-                    conn,
-                    owner_id: *owner_id,
-                };
-                take_mut::take(self, |_| new_node);
-            }
-            Node::Inherited {
-                id,
-                conn,
-                name: _,
-                description: _,
-                parent_node_id,
-            } => {
-                let id = *id;
-                diesel::update(nodes::table.filter(nodes::id.eq(id)))
-                    .set((nodes::name.eq(&name), nodes::description.eq(&description)))
-                    .execute(*conn)
-                    .map_err(|_| {
-                        RelanotesError::NodeMutationError(
-                            "Got DB error while mutating the node.".into(),
-                        )
-                    })?;
-                let new_node = Node::Inherited {
-                    name,
-                    description,
-                    id,
-                    conn,
-                    parent_node_id: *parent_node_id,
-                };
-                take_mut::take(self, |_| new_node);
+                name, description, ..
             }
-            Node::SymLink { .. } => {
-                return Err(RelanotesError::NodeMutationError(
-                    "Can't change name/description of the symlink".into(),
-                ));
+            | Node::StickyNotes {
+                name, description, ..
             }
-        };
-        Ok(())
+            | Node::Inherited {
+                name, description, ..
+            }
+            | Node::Custom {
+                name, description, ..
+            } => {
+                *name = new_name;
+                *description = new_description;
+            }
+            Node::Divisor { name, .. } => *name = new_name,
+            Node::SymLink { .. } => {}
+        }
     }
 }
 
-pub struct GraphNode<'a> {
-    pub node: Node<'a>,
+#[derive(Serialize, Debug, Clone)]
+pub struct GraphNode {
+    pub node: Node,
     pub parent_node_id: Option<i32>,
     pub children: Vec<i32>,
+    // Ids from the nearest regular node among parents to the current one, maintained by
+    // NodesTree::update_path. Empty for sticky notes, divisors are skipped.
+    pub unique_path: Vec<i32>,
+    // The version of the row, the writes of the stale nodes are refused
+    pub version: i32,
 }
 
-impl<'a> GraphNode<'a> {
-    pub fn new(conn: &'a SqliteConnection, node_element: NodeElement, node_type: NodeType) -> Self {
+impl GraphNode {
+    /// The names of the symlink sources are looked up in the given map
+    pub fn new(
+        node_element: NodeElement,
+        node_type: NodeType,
+        source_names: &HashMap<i32, String>,
+    ) -> Result<Self, QuarantineReason> {
         let linked_to_id = node_element.linked_to_id;
+        let version = node_element.version;
         let node = match node_type {
             NodeType::Regular => Node::Regular {
-                conn,
                 id: node_element.id,
                 name: node_element.name,
                 description: node_element.description,
                 associated_node_id: linked_to_id,
             },
             NodeType::StickyNotes => Node::StickyNotes {
-                conn,
                 id: node_element.id,
                 name: node_element.name,
                 description: node_element.description,
-                owner_id: linked_to_id.unwrap(),
+                owner_id: linked_to_id.ok_or(QuarantineReason::MissingOwner)?,
             },
             NodeType::Inherited => Node::Inherited {
-                conn,
                 id: node_element.id,
                 name: node_element.name,
                 description: node_element.description,
-                parent_node_id: linked_to_id.unwrap(),
+                parent_node_id: linked_to_id.ok_or(QuarantineReason::MissingOwner)?,
+            },
+            NodeType::SymLink => {
+                let source_node_id =
+                    linked_to_id.ok_or(QuarantineReason::MissingSymLinkSource(None))?;
+                Node::SymLink {
+                    id: node_element.id,
+                    source_node_id,
+                    source_node_name: source_names
+                        .get(&source_node_id)
+                        .cloned()
+                        .ok_or(QuarantineReason::MissingSymLinkSource(Some(source_node_id)))?,
+                }
+            }
+            NodeType::Divisor => Node::Divisor {
+                id: node_element.id,
+                name: node_element.name,
+                parent_node_id: linked_to_id,
             },
-            NodeType::SymLink => Node::SymLink {
-                conn,
+            NodeType::Custom { type_id } => Node::Custom {
                 id: node_element.id,
-                source_node_id: linked_to_id.unwrap(),
-                source_node_name: nodes::table
-                    .filter(nodes::id.eq(linked_to_id.unwrap()))
-                    .select(nodes::name)
-                    .first::<(String)>(conn)
-                    .unwrap(),
+                type_id,
+                name: node_element.name,
+                description: node_element.description,
+                linked_to_id,
             },
         };
-        let graph_node = GraphNode {
+        Ok(GraphNode {
             node,
             parent_node_id: linked_to_id,
             children: vec![],
-        };
-        graph_node
+            unique_path: vec![],
+            version,
+        })
     }
 
     pub fn add_child(&mut self, node_id: i32) {
@@ -260,30 +287,178 @@ impl<'a> GraphNode<'a> {
     }
 }
 
-pub struct NodesTree<'a> {
-    pub nodes_map: HashMap<i32, GraphNode<'a>>,
-    conn: &'a SqliteConnection,
+#[derive(Clone)]
+pub struct NodesTree {
+    pub nodes_map: HashMap<i32, GraphNode>,
+    storage: SharedStorage,
     subgroup_id: i32,
-    node_types_mapping: HashMap<i32, String>,
+    node_types: Arc<NodeTypesRegistry>,
+    node_types_cache: NodeTypesCache,
+    events: Events,
+    load_report: LoadReport,
+    // The last change of the database applied to the tree
+    synced_change: i32,
     pub loaded: bool,
+    // The changes of the open transactions, made through the methods of the tree
+    undo_log: UndoLog<i32, GraphNode, TreeState>,
 }
 
-impl<'a> NodesTree<'a> {
-    pub fn new(conn: &'a SqliteConnection, subgroup_id: i32) -> Self {
+// The tree besides its nodes, restored when the transaction that reloaded it fails
+#[derive(Clone)]
+struct TreeState {
+    node_types: Arc<NodeTypesRegistry>,
+    load_report: LoadReport,
+    synced_change: i32,
+    loaded: bool,
+}
+
+impl NodesTree {
+    pub fn new(storage: SharedStorage, subgroup_id: i32) -> Self {
+        NodesTree::in_workspace(
+            storage,
+            subgroup_id,
+            NodeTypesCache::default(),
+            Events::default(),
+        )
+    }
+
+    /// The tree that shares the node types and the events with the other trees of the workspace
+    pub fn in_workspace(
+        storage: SharedStorage,
+        subgroup_id: i32,
+        node_types_cache: NodeTypesCache,
+        events: Events,
+    ) -> Self {
         NodesTree {
             nodes_map: HashMap::new(),
-            conn,
+            // Loaded again with the tree, where the errors are reported
+            node_types: node_types_cache.get(&*storage).unwrap_or_default(),
+            node_types_cache,
+            events,
+            storage,
             subgroup_id,
-            node_types_mapping: node_types::table
-                .select((node_types::id, node_types::value))
-                .load::<(i32, String)>(conn)
-                .unwrap()
-                .into_iter()
-                .collect(),
+            load_report: LoadReport::default(),
+            synced_change: 0,
             loaded: false,
+            undo_log: UndoLog::default(),
+        }
+    }
+
+    // Takes the types from the cache again, after they were reloaded there
+    pub(crate) fn refresh_node_types(&mut self) -> Result<(), Error> {
+        self.save_state();
+        self.node_types = self.node_types_cache.get(&*self.storage)?;
+        Ok(())
+    }
+
+    pub fn events(&self) -> &Events {
+        &self.events
+    }
+
+    // The node with its descendants, as published in the events
+    pub(crate) fn get_subtree(&self, node_id: i32) -> Vec<GraphNode> {
+        std::iter::once(node_id)
+            .chain(self.get_descendants(node_id))
+            .filter_map(|id| self.nodes_map.get(&id).cloned())
+            .collect()
+    }
+
+    /// Forgets the loaded nodes, the tree is loaded again on the next access
+    pub fn unload(&mut self) {
+        self.save_state();
+        self.undo_log.replace(&mut self.nodes_map, HashMap::new());
+        self.load_report = LoadReport::default();
+        self.loaded = false;
+        let subgroup_id = self.subgroup_id;
+        self.events
+            .publish(|| Event::SubGroupUnloaded { subgroup_id });
+    }
+
+    // The names of the symlink sources, without a query when there are no symlinks
+    fn load_source_names(&self, source_ids: &[i32]) -> Result<HashMap<i32, String>, Error> {
+        if source_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        Ok(self
+            .storage
+            .get_nodes(source_ids)?
+            .into_iter()
+            .map(|source| (source.id, source.name))
+            .collect())
+    }
+
+    /// Runs the mutations in one transaction. If anything fails, neither the database nor the
+    /// tree are changed, and the events are published only after the commit. The nodes are
+    /// copied only before their first change, the changes made to `nodes_map` directly are not
+    /// undone.
+    pub fn transaction<T, E, F>(&mut self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut NodesTree) -> Result<T, E>,
+        E: From<Error>,
+    {
+        let transaction_id = next_transaction_id();
+        self.begin_transaction(transaction_id);
+        let storage = self.storage.clone();
+        let events = self.events.clone();
+        let result = events.transaction(|| storage.transaction(|| f(self)));
+        match result {
+            Ok(_) => self.commit_transaction(transaction_id),
+            Err(_) => self.rollback_transaction(transaction_id),
+        }
+        result
+    }
+
+    // The tree side of the transaction, the outer models pass their transactions down
+    pub(crate) fn begin_transaction(&mut self, transaction_id: u64) {
+        self.undo_log.begin(transaction_id);
+    }
+
+    pub(crate) fn commit_transaction(&mut self, transaction_id: u64) {
+        self.undo_log.commit(transaction_id);
+    }
+
+    pub(crate) fn rollback_transaction(&mut self, transaction_id: u64) {
+        if let Some(state) = self.undo_log.rollback(&mut self.nodes_map, transaction_id) {
+            self.node_types = state.node_types;
+            self.load_report = state.load_report;
+            self.synced_change = state.synced_change;
+            self.loaded = state.loaded;
         }
     }
 
+    fn save_state(&mut self) {
+        let NodesTree {
+            undo_log,
+            node_types,
+            load_report,
+            synced_change,
+            loaded,
+            ..
+        } = self;
+        undo_log.save_state(|| TreeState {
+            node_types: node_types.clone(),
+            load_report: load_report.clone(),
+            synced_change: *synced_change,
+            loaded: *loaded,
+        });
+    }
+
+    // The nodes are changed only through these, so that the transactions can undo the changes
+    pub(crate) fn graph_node_mut(&mut self, node_id: i32) -> Option<&mut GraphNode> {
+        self.undo_log.get_mut(&mut self.nodes_map, node_id)
+    }
+
+    fn insert_graph_node(&mut self, graph_node: GraphNode) {
+        let node_id = graph_node.node.get_node_id();
+        self.undo_log
+            .insert(&mut self.nodes_map, node_id, graph_node);
+    }
+
+    fn take_graph_node(&mut self, node_id: i32) -> Option<GraphNode> {
+        self.undo_log.remove(&mut self.nodes_map, node_id)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn validate_node_mutation_or_creation(
         &self,
         // Id has to be Some(i32) if you are mutating an existing node
@@ -294,6 +469,31 @@ impl<'a> NodesTree<'a> {
         subgroup_id: i32,
         group_id: i32,
         node_type: NodeType,
+    ) -> Result<(), RelanotesValidationRejection> {
+        self.validate_without(
+            &[],
+            id,
+            name,
+            description,
+            linked_to_id,
+            subgroup_id,
+            group_id,
+            node_type,
+        )
+    }
+
+    // The validation of the node as if the excluded nodes were already deleted
+    #[allow(clippy::too_many_arguments)]
+    fn validate_without(
+        &self,
+        excluded_ids: &[i32],
+        id: Option<i32>,
+        name: &str,
+        description: Option<&str>,
+        linked_to_id: Option<i32>,
+        subgroup_id: i32,
+        group_id: i32,
+        node_type: NodeType,
     ) -> Result<(), RelanotesValidationRejection> {
         if subgroup_id != self.subgroup_id {
             return Err(RelanotesValidationRejection::TryingToMutateOtherSubgroup {
@@ -301,345 +501,252 @@ impl<'a> NodesTree<'a> {
                 checking: subgroup_id,
             });
         }
-        if let Some(id) = id {
-            if let Some(linked_to_id) = linked_to_id {
-                if id == linked_to_id {
-                    return Err(RelanotesValidationRejection::LinkedToItself(id));
-                }
+        if let (Some(id), Some(linked_to_id)) = (id, linked_to_id) {
+            if id == linked_to_id {
+                return Err(RelanotesValidationRejection::LinkedToItself(id));
             }
         }
-        if let Some(id) = id {
-            // Mutating existing node and the user can change the name/owner of the node, so this
-            // has to be validated too
-            match node_type {
-                NodeType::Regular => {
-                    if name.len() == 0 {
-                        return Err(RelanotesValidationRejection::EmptyName);
-                    }
-                    // There can be only one regular node with given name in the group
-                    if nodes::table
-                        .inner_join(subgroups::table)
-                        .filter(nodes::name.eq(name))
-                        .filter(nodes::id.ne(&id))
-                        .filter(subgroups::group_id.eq(group_id))
-                        .count()
-                        .get_result::<i64>(self.conn)
-                        .map_err(|e| RelanotesValidationRejection::TechnicalError(e.to_string()))?
-                        != 0
-                    {
-                        RelanotesValidationRejection::DuplicateRegularNode(name.into());
-                    }
-                }
-                NodeType::StickyNotes => {
-                    if name.len() == 0 {
-                        return Err(RelanotesValidationRejection::EmptyName);
-                    }
-                    // Sticky-notes are unique in the owner node scope
-                    // They can't be without owner
-                    if linked_to_id.is_none() {
-                        return Err(RelanotesValidationRejection::StickyNoteWithoutOwner);
-                    }
-                    let linked_to_id = linked_to_id.unwrap();
-                    if !self.nodes_map.contains_key(&linked_to_id) {
-                        return Err(RelanotesValidationRejection::InvalidStickyNoteOwner);
-                    }
-                    if self
-                        .nodes_map
-                        .get(&linked_to_id)
-                        .unwrap()
-                        .children
-                        .iter()
-                        .find(|c| {
-                            if let Node::StickyNotes {
-                                id: current_id,
-                                name: current_name,
-                                ..
-                            } = &self.nodes_map.get(c).unwrap().node
-                            {
-                                if current_name == name && *current_id != id {
-                                    return true;
-                                }
-                            }
-                            false
-                        })
-                        .is_some()
-                    {
-                        // Found sticky note with same name
-                        return Err(RelanotesValidationRejection::DuplicateStickyNote(
-                            name.into(),
-                        ));
-                    }
-                }
-                NodeType::Inherited => {
-                    if name.len() == 0 {
-                        return Err(RelanotesValidationRejection::EmptyName);
-                    }
-                    // Name is unique in the owner's namespace
-                    // Can't be without owner
-                    if linked_to_id.is_none() {
-                        return Err(RelanotesValidationRejection::InheritedNodeWithoutOwner);
-                    }
-                    let linked_to_id = linked_to_id.unwrap();
-                    if !self.nodes_map.contains_key(&linked_to_id) {
-                        return Err(RelanotesValidationRejection::InvalidInheritedNodeOwner);
-                    }
-                    if self
-                        .nodes_map
-                        .get(&linked_to_id)
-                        .unwrap()
-                        .children
-                        .iter()
-                        .find(|c| {
-                            if let Node::Inherited {
-                                id: current_id,
-                                name: current_name,
-                                ..
-                            } = &self.nodes_map.get(c).unwrap().node
-                            {
-                                if current_name == name && id != *current_id {
-                                    return true;
-                                }
-                            }
-                            false
-                        })
-                        .is_some()
-                    {
-                        // Found sticky note with same name
-                        return Err(RelanotesValidationRejection::DuplicateInheritedNode(
-                            name.into(),
-                        ));
-                    }
-                }
-                NodeType::SymLink => {
-                    // Can't have description
-                    if name.len() != 0 {
-                        return Err(RelanotesValidationRejection::SymLinkWithName);
-                    }
-                    if description.is_some() {
-                        return Err(RelanotesValidationRejection::SymLinkWithDescription);
-                    }
-                    if linked_to_id.is_none() {
-                        return Err(RelanotesValidationRejection::SymLinkWithoutOwner);
-                    }
-                    let linked_to_id = linked_to_id.unwrap();
-                    if self.nodes_map.contains_key(&linked_to_id) {
-                        return Err(RelanotesValidationRejection::SymLinkToSameSubgroup);
-                    }
-                    if nodes::table
-                        .filter(nodes::id.eq(&linked_to_id))
-                        .first::<NodeElement>(self.conn)
-                        .is_ok()
-                    {
-                        return Err(RelanotesValidationRejection::InvalidSymLinkOwner);
-                    }
-                }
+        let type_id = self.node_types.get_type_id(&node_type).ok_or_else(|| {
+            RelanotesValidationRejection::TechnicalError(format!(
+                "Unknown node type {:?}",
+                node_type
+            ))
+        })?;
+        let rules = self.node_types.get(type_id).unwrap();
+        match rules.name_policy {
+            NamePolicy::Required if name.is_empty() => {
+                return Err(RelanotesValidationRejection::EmptyName)
             }
-        } else {
-            // Creating a new node
-            match node_type {
-                NodeType::Regular => {
-                    if name.len() == 0 {
-                        return Err(RelanotesValidationRejection::EmptyName);
-                    }
-                    // There can be only one regular node with given name in the group
-                    if nodes::table
-                        .inner_join(subgroups::table)
-                        .filter(nodes::name.eq(name))
-                        .filter(subgroups::group_id.eq(group_id))
-                        .count()
-                        .get_result::<i64>(self.conn)
-                        .map_err(|e| RelanotesValidationRejection::TechnicalError(e.to_string()))?
-                        != 0
-                    {
-                        RelanotesValidationRejection::DuplicateRegularNode(name.into());
-                    }
-                }
-                NodeType::StickyNotes => {
-                    if name.len() == 0 {
-                        return Err(RelanotesValidationRejection::EmptyName);
-                    }
-                    // Sticky-notes are unique in the owner node scope
-                    // They can't be without owner
-                    if linked_to_id.is_none() {
-                        return Err(RelanotesValidationRejection::StickyNoteWithoutOwner);
-                    }
-                    let linked_to_id = linked_to_id.unwrap();
-                    if !self.nodes_map.contains_key(&linked_to_id) {
-                        return Err(RelanotesValidationRejection::InvalidStickyNoteOwner);
-                    }
-                    if self
-                        .nodes_map
-                        .get(&linked_to_id)
-                        .unwrap()
-                        .children
-                        .iter()
-                        .find(|c| {
-                            if let Node::StickyNotes {
-                                name: current_name, ..
-                            } = &self.nodes_map.get(c).unwrap().node
-                            {
-                                if current_name == name {
-                                    return true;
-                                }
-                            }
-                            false
-                        })
-                        .is_some()
-                    {
-                        // Found sticky note with same name
-                        return Err(RelanotesValidationRejection::DuplicateStickyNote(
-                            name.into(),
-                        ));
-                    }
-                }
-                NodeType::Inherited => {
-                    if name.len() == 0 {
-                        return Err(RelanotesValidationRejection::EmptyName);
-                    }
-                    // Name is unique in the owner's namespace
-                    // Can't be without owner
-                    if linked_to_id.is_none() {
-                        return Err(RelanotesValidationRejection::InheritedNodeWithoutOwner);
-                    }
-                    let linked_to_id = linked_to_id.unwrap();
-                    if !self.nodes_map.contains_key(&linked_to_id) {
-                        return Err(RelanotesValidationRejection::InvalidInheritedNodeOwner);
-                    }
-                    if self
-                        .nodes_map
-                        .get(&linked_to_id)
-                        .unwrap()
-                        .children
-                        .iter()
-                        .find(|c| {
-                            if let Node::Inherited {
-                                name: current_name, ..
-                            } = &self.nodes_map.get(c).unwrap().node
-                            {
-                                if current_name == name {
-                                    return true;
-                                }
-                            }
-                            false
-                        })
-                        .is_some()
-                    {
-                        // Found sticky note with same name
-                        return Err(RelanotesValidationRejection::DuplicateInheritedNode(
-                            name.into(),
-                        ));
-                    }
-                }
-                NodeType::SymLink => {
-                    // Can't have description
-                    if name.len() != 0 {
-                        return Err(RelanotesValidationRejection::SymLinkWithName);
-                    }
-                    if description.is_some() {
-                        return Err(RelanotesValidationRejection::SymLinkWithDescription);
-                    }
-                    if linked_to_id.is_none() {
-                        return Err(RelanotesValidationRejection::SymLinkWithoutOwner);
-                    }
-                    let linked_to_id = linked_to_id.unwrap();
-                    if self.nodes_map.contains_key(&linked_to_id) {
-                        return Err(RelanotesValidationRejection::SymLinkToSameSubgroup);
-                    }
-                    if nodes::table
-                        .filter(nodes::id.eq(&linked_to_id))
-                        .first::<NodeElement>(self.conn)
-                        .is_ok()
-                    {
-                        return Err(RelanotesValidationRejection::InvalidSymLinkOwner);
-                    }
-                }
+            NamePolicy::Forbidden if !name.is_empty() => {
+                return Err(RelanotesValidationRejection::name_not_allowed(
+                    node_type,
+                    &rules.value,
+                ))
             }
+            _ => {}
+        }
+        if description.is_some() && !rules.allows_description {
+            return Err(RelanotesValidationRejection::description_not_allowed(
+                node_type,
+                &rules.value,
+            ));
+        }
+        if node_type == NodeType::SymLink {
+            // Symlinks are linked to their sources, not to their owners
+            return self.validate_symlink_source(id, linked_to_id, rules);
+        }
+
+        // Checking the owner with the rules of both types
+        if let Some(linked_to_id) = linked_to_id {
+            let owner_rules = self
+                .nodes_map
+                .get(&linked_to_id)
+                .and_then(|owner| self.node_types.get_rules(&owner.node.get_node_type()))
+                .ok_or_else(|| {
+                    RelanotesValidationRejection::invalid_owner(node_type, &rules.value)
+                })?;
+            if !rules.parent_types.contains(&owner_rules.value)
+                || !owner_rules.child_types.contains(&rules.value)
+            {
+                return Err(RelanotesValidationRejection::invalid_owner(
+                    node_type,
+                    &rules.value,
+                ));
+            }
+        }
+        // Divisors are transparent, so the divisor without an owner doesn't own anything
+        let namespace_owner = linked_to_id.and_then(|l| self.get_namespace_owner(l));
+        if namespace_owner.is_none() && !rules.allows_root {
+            return Err(match linked_to_id {
+                Some(_) => RelanotesValidationRejection::invalid_owner(node_type, &rules.value),
+                None => RelanotesValidationRejection::missing_owner(node_type, &rules.value),
+            });
+        }
+
+        let is_duplicate = |other_id: &i32| {
+            Some(*other_id) != id
+                && !excluded_ids.contains(other_id)
+                && self.nodes_map.get(other_id).is_some_and(|other| {
+                    other.node.get_node_type() == node_type && other.node.get_name() == name
+                })
+        };
+        let has_duplicate = match rules.uniqueness_scope {
+            UniquenessScope::Group => self
+                .storage
+                .find_nodes_in_group(group_id, &[name])
+                .map_err(|e| RelanotesValidationRejection::TechnicalError(e.to_string()))?
+                .iter()
+                .any(|other| {
+                    other.type_id == type_id
+                        && Some(other.id) != id
+                        && !excluded_ids.contains(&other.id)
+                }),
+            UniquenessScope::Subgroup => self.nodes_map.keys().any(is_duplicate),
+            UniquenessScope::Owner => self.get_namespace(namespace_owner).iter().any(is_duplicate),
+            UniquenessScope::None => false,
+        };
+        if has_duplicate {
+            return Err(RelanotesValidationRejection::duplicate(
+                node_type,
+                &rules.value,
+                name,
+            ));
         }
         Ok(())
     }
 
-    pub fn create_node(
-        &mut self,
-        name: &str,
-        description: Option<&str>,
-        parent_node_id: Option<i32>,
-        subgroup_id: i32,
-        type_id: i32,
-    ) -> Result<&Node, diesel::result::Error> {
-        // Some more checking here!
-        if parent_node_id.is_none() {
-            if nodes::table
-                .filter(nodes::name.eq(name))
-                .filter(nodes::linked_to_id.is_null())
-                .first::<NodeElement>(self.conn)
-                .is_ok()
+    /// Validates the namespace children of the divisor as if it was linked to the new parent,
+    /// they join the namespace of its new owner. The excluded nodes are treated as deleted.
+    pub(crate) fn validate_divisor_children(
+        &self,
+        excluded_ids: &[i32],
+        children: &[&Node],
+        new_parent_id: Option<i32>,
+    ) -> Result<(), RelanotesValidationRejection> {
+        let namespace_owner = new_parent_id.and_then(|id| self.get_namespace_owner(id));
+        let namespace = self.get_namespace(namespace_owner);
+        for child in children {
+            let node_type = child.get_node_type();
+            let rules = match self.node_types.get_rules(&node_type) {
+                Some(rules) => rules,
+                None => continue,
+            };
+            if namespace_owner.is_none() && !rules.allows_root {
+                return Err(RelanotesValidationRejection::invalid_owner(
+                    node_type,
+                    &rules.value,
+                ));
+            }
+            // The children which are already in the namespace are moved with the divisor
+            if rules.uniqueness_scope == UniquenessScope::Owner
+                && namespace.iter().any(|other_id| {
+                    !excluded_ids.contains(other_id)
+                        && children.iter().all(|c| c.get_node_id() != *other_id)
+                        && self.nodes_map.get(other_id).is_some_and(|other| {
+                            other.node.get_node_type() == node_type
+                                && other.node.get_name() == child.get_name()
+                        })
+                })
             {
-                return Err(diesel::result::Error::DatabaseError(
-                    diesel::result::DatabaseErrorKind::UniqueViolation,
-                    Box::new(String::from("Duplicate name")),
+                return Err(RelanotesValidationRejection::duplicate(
+                    node_type,
+                    &rules.value,
+                    child.get_name(),
                 ));
             }
         }
-        diesel::insert_into(nodes::table)
-            .values((
-                nodes::name.eq(name),
-                nodes::description.eq(description),
-                nodes::type_id.eq(type_id),
-                nodes::linked_to_id.eq(parent_node_id),
-                nodes::subgroup_id.eq(subgroup_id),
-            ))
-            .execute(self.conn)?;
-
-        let mut filter_to_get_model = nodes::table
-            .filter(nodes::name.eq(name))
-            .filter(nodes::subgroup_id.eq(subgroup_id))
-            .into_boxed();
+        Ok(())
+    }
 
-        if parent_node_id.is_some() {
-            filter_to_get_model =
-                filter_to_get_model.filter(nodes::linked_to_id.eq(parent_node_id));
-        } else {
-            filter_to_get_model = filter_to_get_model.filter(nodes::linked_to_id.is_null());
+    fn validate_symlink_source(
+        &self,
+        id: Option<i32>,
+        linked_to_id: Option<i32>,
+        rules: &NodeTypeDefinition,
+    ) -> Result<(), RelanotesValidationRejection> {
+        if linked_to_id.is_none() {
+            return Err(RelanotesValidationRejection::SymLinkWithoutOwner);
+        }
+        let linked_to_id = linked_to_id.unwrap();
+        if self.nodes_map.contains_key(&linked_to_id) {
+            return Err(RelanotesValidationRejection::SymLinkToSameSubgroup);
+        }
+        let source_type_id = self
+            .storage
+            .get_node(linked_to_id)
+            .map_err(|e| RelanotesValidationRejection::TechnicalError(e.to_string()))?
+            .ok_or(RelanotesValidationRejection::InvalidSymLinkOwner)?
+            .type_id;
+        if !self
+            .node_types
+            .get(source_type_id)
+            .is_some_and(|source_rules| rules.parent_types.contains(&source_rules.value))
+        {
+            return Err(RelanotesValidationRejection::InvalidSymLinkOwner);
+        }
+        // Only one symlink to the same source in the subgroup
+        if self.nodes_map.values().any(|other| {
+            Some(other.node.get_node_id()) != id
+                && other.node.get_node_type() == NodeType::SymLink
+                && other.node.get_linked_to_id() == Some(linked_to_id)
+        }) {
+            return Err(RelanotesValidationRejection::DuplicateSymLink(linked_to_id));
         }
+        Ok(())
+    }
 
-        let new_node = filter_to_get_model.first::<NodeElement>(self.conn)?;
+    /// Validates and creates the node in the current subgroup, the owner of the symlink is its
+    /// source
+    pub fn create_node(
+        &mut self,
+        name: &str,
+        description: Option<&str>,
+        linked_to_id: Option<i32>,
+        node_type: NodeType,
+    ) -> Result<&Node, RelanotesError> {
+        let group_id = self.get_group_id()?;
+        // The validation reads the group namespace, so it's done in the same transaction as the
+        // write. The graph node is built inside too, so that the row is not kept if it fails.
+        let graph_node = self.storage.transaction::<_, RelanotesError, _>(|| {
+            self.validate_node_mutation_or_creation(
+                None,
+                name,
+                description,
+                linked_to_id,
+                self.subgroup_id,
+                group_id,
+                node_type,
+            )?;
+            let type_id = self.get_node_type_id_from_type(&node_type);
+            let new_node = self
+                .storage
+                .create_node(&NewNode {
+                    name,
+                    description,
+                    type_id,
+                    linked_to_id,
+                    subgroup_id: self.subgroup_id,
+                })
+                .map_err(|e| RelanotesError::from_node_write(e, None, name))?;
+            let source_ids = match node_type {
+                NodeType::SymLink => linked_to_id.into_iter().collect(),
+                _ => vec![],
+            };
+            GraphNode::new(new_node, node_type, &self.load_source_names(&source_ids)?)
+                .map_err(|reason| RelanotesError::NodeMutationError(reason.to_string()))
+        })?;
 
-        let new_node_id = new_node.id;
-        let graph_node = GraphNode::new(self.conn, new_node, self.get_node_type(&type_id).unwrap());
-        self.nodes_map.insert(new_node_id, graph_node);
+        let new_node_id = graph_node.node.get_node_id();
+        self.insert_graph_node(graph_node);
 
-        if let Some(linked_to_id) = parent_node_id {
-            if let Some(parent_graph_node) = self.nodes_map.get_mut(&linked_to_id) {
+        if let Some(linked_to_id) = linked_to_id {
+            if let Some(parent_graph_node) = self.graph_node_mut(linked_to_id) {
                 parent_graph_node.add_child(new_node_id);
             }
         }
-        Ok(&self.nodes_map.get(&new_node_id).unwrap().node)
+        self.update_path(new_node_id);
+        let graph_node = self.nodes_map.get(&new_node_id).unwrap();
+        self.events.publish(|| Event::NodeCreated {
+            subgroup_id: self.subgroup_id,
+            node: graph_node.clone(),
+        });
+        Ok(&graph_node.node)
     }
 
     fn get_node_type(&self, type_id: &i32) -> Option<NodeType> {
-        let type_value = self.node_types_mapping.get(type_id)?;
-        match &type_value[..] {
-            "regular" => Some(NodeType::Regular),
-            "sticky_notes" => Some(NodeType::StickyNotes),
-            "inherited" => Some(NodeType::Inherited),
-            "symlinks" => Some(NodeType::SymLink),
-            _ => None,
-        }
+        self.node_types.get_node_type(*type_id)
     }
 
     pub fn get_node_type_id_from_type(&self, node_type: &NodeType) -> i32 {
-        let type_value = match node_type {
-            NodeType::Regular => "regular",
-            NodeType::StickyNotes => "sticky_notes",
-            NodeType::Inherited => "inherited",
-            NodeType::SymLink => "symlinks",
-        };
-        *self
-            .node_types_mapping
-            .iter()
-            .find(|(i, e)| *e == type_value)
-            .unwrap()
-            .0
+        self.node_types.get_type_id(node_type).unwrap()
+    }
+
+    pub fn get_node_types(&self) -> &NodeTypesRegistry {
+        &self.node_types
+    }
+
+    pub fn get_subgroup_id(&self) -> i32 {
+        self.subgroup_id
     }
 
     // O(1)
@@ -659,30 +766,451 @@ impl<'a> NodesTree<'a> {
         self.nodes_map.get(&graph_node.parent_node_id?)
     }
 
-    //    pub fn delete_node(&mut self, conn: &SqliteConnection, node_id: i32) -> Option<i32> {
-    //        let graph_node = self.nodes_map.get(&node_id)?;
-    //        if !graph_node.children.is_empty() {
-    //            None
-    //        } else {
-    //            diesel::delete(nodes::table.filter(nodes::id.eq(node_id)))
-    //                .execute(conn)
-    //                .ok()?;
-    //            // remove from children
-    //            if let Some(parent_node_id) = graph_node.parent_node_id {
-    //                if let Some(parent) = self.nodes_map.get_mut(&parent_node_id) {
-    //                    parent.remove_child(node_id);
-    //                }
-    //            }
-    //            Some(node_id)
-    //        }
-    //    }
+    // Divisors are transparent, so the namespace belongs to the nearest non-divisor parent
+    pub(crate) fn get_namespace_owner(&self, node_id: i32) -> Option<i32> {
+        let mut current = self.nodes_map.get(&node_id)?;
+        while let Node::Divisor { parent_node_id, .. } = current.node {
+            current = self.nodes_map.get(&parent_node_id?)?;
+        }
+        Some(current.node.get_node_id())
+    }
+
+    /// Children of the node, where the divisors are replaced with their children
+    pub fn get_namespace_children(&self, node_id: i32) -> Vec<i32> {
+        let mut children = vec![];
+        if let Some(graph_node) = self.nodes_map.get(&node_id) {
+            for child_id in &graph_node.children {
+                match self.nodes_map.get(child_id).map(|c| &c.node) {
+                    Some(Node::Divisor { .. }) => {
+                        children.append(&mut self.get_namespace_children(*child_id))
+                    }
+                    _ => children.push(*child_id),
+                }
+            }
+        }
+        children
+    }
+
+    pub(crate) fn get_namespace_nodes(&self, node_id: i32) -> Vec<&Node> {
+        self.get_namespace_children(node_id)
+            .iter()
+            .map(|id| &self.nodes_map.get(id).unwrap().node)
+            .collect()
+    }
+
+    // The namespace of the owner, the roots of the subgroup with the children of the divisors
+    // among them without one
+    fn get_namespace(&self, namespace_owner: Option<i32>) -> Vec<i32> {
+        match namespace_owner {
+            Some(owner_id) => self.get_namespace_children(owner_id),
+            None => self
+                .get_roots()
+                .into_iter()
+                .flat_map(|root_id| match self.nodes_map.get(&root_id).unwrap().node {
+                    Node::Divisor { .. } => self.get_namespace_children(root_id),
+                    _ => vec![root_id],
+                })
+                .collect(),
+        }
+    }
+
+    /// Recomputes the unique path of the node and of all inherited nodes below it
+    pub fn update_path(&mut self, node_id: i32) {
+        let graph_node = match self.nodes_map.get(&node_id) {
+            Some(graph_node) => graph_node,
+            None => return,
+        };
+        let unique_path = match graph_node.node.get_node_type() {
+            NodeType::StickyNotes | NodeType::Custom { .. } => vec![],
+            NodeType::Divisor => self
+                .get_graph_node_parent(graph_node)
+                .map(|parent| parent.unique_path.clone())
+                .unwrap_or_default(),
+            NodeType::Inherited => {
+                let mut unique_path = self
+                    .get_graph_node_parent(graph_node)
+                    .map(|parent| parent.unique_path.clone())
+                    .unwrap_or_default();
+                unique_path.push(node_id);
+                unique_path
+            }
+            // Regular nodes and symlinks start new unique paths
+            _ => vec![node_id],
+        };
+        let dependent_children = graph_node
+            .children
+            .iter()
+            .filter(|c| {
+                matches!(
+                    self.nodes_map.get(c).map(|n| &n.node),
+                    Some(Node::Inherited { .. }) | Some(Node::Divisor { .. })
+                )
+            })
+            .copied()
+            .collect::<Vec<i32>>();
+        self.graph_node_mut(node_id).unwrap().unique_path = unique_path;
+        for child_id in dependent_children {
+            self.update_path(child_id);
+        }
+    }
+
+    fn get_group_id(&self) -> Result<i32, Error> {
+        self.storage
+            .get_subgroup(self.subgroup_id)?
+            .map(|subgroup| subgroup.group_id)
+            .ok_or(Error::NotFound)
+    }
+
+    pub fn update_name_and_description(
+        &mut self,
+        node_id: i32,
+        name: String,
+        description: Option<String>,
+    ) -> Result<(), RelanotesError> {
+        let graph_node = self
+            .nodes_map
+            .get(&node_id)
+            .ok_or(RelanotesError::NodeNotFound(node_id))?;
+        match graph_node.node {
+            Node::SymLink { .. } => {
+                return Err(RelanotesError::NodeMutationError(
+                    "Can't change name/description of the symlink".into(),
+                ));
+            }
+            Node::Divisor { .. } if description.is_some() => {
+                return Err(RelanotesError::NodeMutationError(
+                    "Can't set description of the divisor".into(),
+                ));
+            }
+            _ => {}
+        }
+        let node_type = graph_node.node.get_node_type();
+        let linked_to_id = graph_node.node.get_linked_to_id();
+        let group_id = self
+            .get_group_id()
+            .map_err(RelanotesError::DBQueriesError)?;
+        self.storage.transaction::<_, RelanotesError, _>(|| {
+            self.check_versions(&[node_id])?;
+            self.validate_node_mutation_or_creation(
+                Some(node_id),
+                &name,
+                description.as_deref(),
+                linked_to_id,
+                self.subgroup_id,
+                group_id,
+                node_type,
+            )
+            .map_err(RelanotesError::ValidationRejection)?;
+            self.storage
+                .update_node_content(node_id, &name, description.as_deref())
+                .map_err(|e| mutation_error(e, node_id, &name))
+        })?;
+        self.bump_versions(&[node_id]);
+        self.graph_node_mut(node_id)
+            .unwrap()
+            .node
+            .set_name_and_description(name, description);
+        let graph_node = &self.nodes_map[&node_id];
+        self.events.publish(|| Event::NodeUpdated {
+            subgroup_id: self.subgroup_id,
+            node: graph_node.clone(),
+        });
+        Ok(())
+    }
+
+    /// Links the node to the new parent inside the current subgroup
+    pub fn move_node(
+        &mut self,
+        node_id: i32,
+        new_parent_id: Option<i32>,
+    ) -> Result<(), RelanotesError> {
+        let graph_node = self
+            .nodes_map
+            .get(&node_id)
+            .ok_or(RelanotesError::NodeNotFound(node_id))?;
+        let node_type = graph_node.node.get_node_type();
+        if node_type == NodeType::SymLink {
+            return Err(RelanotesError::NodeMutationError(
+                "Can't move the symlink, it is linked to its source".into(),
+            ));
+        }
+        let name = graph_node.node.get_name().to_string();
+        let description = graph_node.node.get_description().map(String::from);
+        let old_parent_id = graph_node.parent_node_id;
+        if old_parent_id == new_parent_id {
+            return Ok(());
+        }
+        if let Some(new_parent_id) = new_parent_id {
+            if !self.nodes_map.contains_key(&new_parent_id) {
+                return Err(RelanotesError::NodeNotFound(new_parent_id));
+            }
+            if new_parent_id == node_id || self.get_descendants(node_id).contains(&new_parent_id) {
+                return Err(RelanotesError::CyclicMove {
+                    node_id,
+                    new_parent_id,
+                });
+            }
+        }
+        let group_id = self
+            .get_group_id()
+            .map_err(RelanotesError::DBQueriesError)?;
+
+        // The cycle check above holds only while the new parent and its ancestors stay where
+        // they were loaded
+        let mut checked = vec![node_id];
+        let mut ancestor_id = new_parent_id;
+        while let Some(id) = ancestor_id {
+            checked.push(id);
+            ancestor_id = self
+                .nodes_map
+                .get(&id)
+                .and_then(|ancestor| ancestor.parent_node_id);
+        }
+        self.storage.transaction::<_, RelanotesError, _>(|| {
+            self.check_versions(&checked)?;
+            // The validation reads the group namespace, so it's done in the same transaction as
+            // the write
+            self.validate_node_mutation_or_creation(
+                Some(node_id),
+                &name,
+                description.as_deref(),
+                new_parent_id,
+                self.subgroup_id,
+                group_id,
+                node_type,
+            )
+            .map_err(RelanotesError::ValidationRejection)?;
+            if node_type == NodeType::Divisor {
+                self.validate_divisor_children(
+                    &[],
+                    &self.get_namespace_nodes(node_id),
+                    new_parent_id,
+                )
+                .map_err(RelanotesError::ValidationRejection)?;
+            }
+            self.storage
+                .set_linked_to_id(&[node_id], new_parent_id)
+                .map_err(|e| RelanotesError::from_node_write(e, Some(node_id), &name))
+        })?;
+        self.bump_versions(&[node_id]);
+
+        if let Some(old_parent) = old_parent_id.and_then(|id| self.graph_node_mut(id)) {
+            old_parent.remove_child(node_id);
+        }
+        if let Some(new_parent) = new_parent_id.and_then(|id| self.graph_node_mut(id)) {
+            new_parent.add_child(node_id);
+        }
+        let graph_node = self.graph_node_mut(node_id).unwrap();
+        graph_node.parent_node_id = new_parent_id;
+        graph_node.node.set_linked_to_id(new_parent_id);
+        self.update_path(node_id);
+        self.publish_move(node_id);
+        Ok(())
+    }
+
+    // The move inside the subgroup
+    fn publish_move(&self, node_id: i32) {
+        self.events.publish(|| Event::NodeMoved {
+            node_id,
+            from_subgroup_id: self.subgroup_id,
+            to_subgroup_id: self.subgroup_id,
+            nodes: self.get_subtree(node_id),
+        });
+    }
+
+    // Children first, so that the nodes can be deleted in the returned order
+    pub(crate) fn get_descendants(&self, node_id: i32) -> Vec<i32> {
+        let mut descendants = vec![];
+        if let Some(graph_node) = self.nodes_map.get(&node_id) {
+            for child_id in &graph_node.children {
+                descendants.append(&mut self.get_descendants(*child_id));
+                descendants.push(*child_id);
+            }
+        }
+        descendants
+    }
+
+    // Removes the node with its descendants from the graph, the DB is not touched
+    pub(crate) fn detach_subtree(&mut self, node_id: i32) -> HashMap<i32, GraphNode> {
+        let mut subtree_ids = self.get_descendants(node_id);
+        subtree_ids.push(node_id);
+        if let Some(parent_node_id) = self.nodes_map.get(&node_id).and_then(|n| n.parent_node_id) {
+            if let Some(parent) = self.graph_node_mut(parent_node_id) {
+                parent.remove_child(node_id);
+            }
+        }
+        subtree_ids
+            .into_iter()
+            .filter_map(|id| Some((id, self.take_graph_node(id)?)))
+            .collect()
+    }
+
+    // Adds the subtree detached from another tree, the root has to be already linked to its
+    // new parent
+    pub(crate) fn attach_subtree(&mut self, root_id: i32, subtree: HashMap<i32, GraphNode>) {
+        let parent_node_id = subtree.get(&root_id).and_then(|n| n.parent_node_id);
+        for graph_node in subtree.into_values() {
+            self.insert_graph_node(graph_node);
+        }
+        if let Some(parent) = parent_node_id.and_then(|id| self.graph_node_mut(id)) {
+            parent.add_child(root_id);
+        }
+        self.update_path(root_id);
+    }
+
+    // Replaces the symlink with its source located in the same tree - the children of the
+    // symlink are linked to the source, the DB is not touched
+    pub(crate) fn merge_symlink_into_source(&mut self, symlink_id: i32) {
+        let symlink = match self.take_graph_node(symlink_id) {
+            Some(symlink) => symlink,
+            None => return,
+        };
+        let source_node_id = symlink.node.get_linked_to_id();
+        if let Some(source) = source_node_id.and_then(|id| self.graph_node_mut(id)) {
+            source.remove_child(symlink_id);
+            source.children.extend(symlink.children.iter().copied());
+        }
+        for child_id in &symlink.children {
+            if let Some(child) = self.graph_node_mut(*child_id) {
+                child.parent_node_id = source_node_id;
+                child.node.set_linked_to_id(source_node_id);
+            }
+        }
+        for child_id in symlink.children {
+            self.update_path(child_id);
+        }
+    }
+
+    /// Deletes the node and returns the ids of all deleted nodes
+    pub fn delete_node(
+        &mut self,
+        node_id: i32,
+        strategy: DeletionStrategy,
+    ) -> Result<Vec<i32>, RelanotesError> {
+        let children = self
+            .get_node_loaded_children(&node_id)
+            .ok_or(RelanotesError::NodeNotFound(node_id))?;
+        // Symlinks are linked to nodes from other subgroups, so their children become roots
+        let graph_node = self.nodes_map.get(&node_id).unwrap();
+        let stored_parent_id = match graph_node.node {
+            Node::SymLink { .. } => None,
+            _ => graph_node.parent_node_id,
+        };
+        let new_parent_id = self.get_node_loaded_parent(&node_id);
+
+        let (deleted_ids, lifted_ids) = match strategy {
+            DeletionStrategy::RefuseIfHasChildren => {
+                if !children.is_empty() {
+                    return Err(RelanotesError::NodeHasChildren(node_id));
+                }
+                (vec![node_id], vec![])
+            }
+            DeletionStrategy::Cascade => {
+                let mut deleted_ids = self.get_descendants(node_id);
+                deleted_ids.push(node_id);
+                (deleted_ids, vec![])
+            }
+            DeletionStrategy::LiftChildrenToParent => (vec![node_id], children),
+        };
+        let group_id = self
+            .get_group_id()
+            .map_err(RelanotesError::DBQueriesError)?;
+
+        // The checks read the other subgroups, so they are done in the same transaction as the
+        // writes
+        let lifted = self.storage.transaction::<_, RelanotesError, _>(|| {
+            self.check_versions(&[&deleted_ids[..], &lifted_ids[..]].concat())?;
+            // The checked version has the stored parent, the children of the node under a
+            // quarantined parent would become roots instead
+            if let Some(parent_id) = stored_parent_id {
+                if !lifted_ids.is_empty() && new_parent_id.is_none() {
+                    return Err(RelanotesError::ParentNotLoaded { node_id, parent_id });
+                }
+            }
+            // The deleted node is not a sibling of its lifted children anymore
+            for child_id in &lifted_ids {
+                let child = &self.nodes_map.get(child_id).unwrap().node;
+                self.validate_without(
+                    &[node_id],
+                    Some(*child_id),
+                    child.get_name(),
+                    child.get_description(),
+                    new_parent_id,
+                    self.subgroup_id,
+                    group_id,
+                    child.get_node_type(),
+                )
+                .map_err(|_| RelanotesError::ChildCannotBeLifted(*child_id))?;
+                if child.get_node_type() == NodeType::Divisor {
+                    self.validate_divisor_children(
+                        &[node_id],
+                        &self.get_namespace_nodes(*child_id),
+                        new_parent_id,
+                    )
+                    .map_err(|_| RelanotesError::ChildCannotBeLifted(*child_id))?;
+                }
+            }
+            // The symlinks of the other subgroups would keep the deleted nodes, so they are
+            // reported instead of failing on the foreign key
+            let referenced_by = self
+                .storage
+                .find_linked_nodes(&deleted_ids)
+                .map_err(RelanotesError::DBQueriesError)?
+                .into_iter()
+                .map(|node| node.id)
+                .filter(|id| !deleted_ids.contains(id) && !lifted_ids.contains(id))
+                .collect::<Vec<i32>>();
+            if !referenced_by.is_empty() {
+                return Err(RelanotesError::NodeIsReferenced {
+                    node_id,
+                    referenced_by,
+                });
+            }
+            // The lifted children are detached before the node is deleted and linked to the new
+            // parent after it, so that the triggers don't count the node as their sibling
+            self.storage.set_linked_to_id(&lifted_ids, None)?;
+            self.storage.delete_nodes(&deleted_ids)?;
+            self.storage.set_linked_to_id(&lifted_ids, new_parent_id)?;
+            Ok(self.storage.get_nodes(&lifted_ids)?)
+        })?;
+        self.set_versions(&lifted);
+
+        for child_id in &lifted_ids {
+            let child = self.graph_node_mut(*child_id).unwrap();
+            child.parent_node_id = new_parent_id;
+            child.node.set_linked_to_id(new_parent_id);
+        }
+        for child_id in &lifted_ids {
+            self.update_path(*child_id);
+        }
+        if let Some(parent) = new_parent_id.and_then(|id| self.graph_node_mut(id)) {
+            let position = parent.children.iter().position(|&c| c == node_id);
+            parent.remove_child(node_id);
+            if let Some(position) = position {
+                parent
+                    .children
+                    .splice(position..position, lifted_ids.iter().copied());
+            }
+        }
+        for id in &deleted_ids {
+            self.take_graph_node(*id);
+        }
+        self.events.publish(|| Event::NodeDeleted {
+            subgroup_id: self.subgroup_id,
+            node_ids: deleted_ids.clone(),
+        });
+        for child_id in &lifted_ids {
+            self.publish_move(*child_id);
+        }
+        Ok(deleted_ids)
+    }
 
     pub fn get_roots(&self) -> Vec<i32> {
         let mut roots = self
             .nodes_map
             .keys()
             .filter(|id| !self.node_has_loaded_parent(**id))
-            .map(|e| *e)
+            .copied()
             .collect::<Vec<i32>>();
         roots.sort();
         roots
@@ -703,41 +1231,135 @@ impl<'a> NodesTree<'a> {
     }
 }
 
-impl<'a> Loadable for NodesTree<'a> {
+impl Loadable for NodesTree {
     fn load(&mut self) -> Result<(), Error> {
-        let nodes: Vec<NodeElement> = nodes::table
-            .filter(nodes::subgroup_id.eq(self.subgroup_id))
-            .load::<NodeElement>(self.conn)?;
+        self.load_with_report()?;
+        Ok(())
+    }
+}
 
-        let mut nodes_map = HashMap::new();
-        let mut children_map: HashMap<i32, Vec<i32>> = HashMap::new();
+impl NodesTree {
+    /// Loads the subgroup, the nodes that can't be loaded are quarantined and reported instead of
+    /// failing the whole subgroup
+    pub fn load_with_report(&mut self) -> Result<&LoadReport, Error> {
+        // Read first, the changes written during the loading are applied again by the refresh
+        let synced_change = self.storage.last_change()?;
+        self.save_state();
+        // The types are loaded once for all trees sharing the cache
+        self.refresh_node_types()?;
+        let nodes: Vec<NodeElement> = self.storage.load_nodes(Some(self.subgroup_id))?;
+        // All sources of the symlinks are looked up at once instead of for every symlink
+        let source_ids = nodes
+            .iter()
+            .filter(|node| self.get_node_type(&node.type_id) == Some(NodeType::SymLink))
+            .filter_map(|node| node.linked_to_id)
+            .collect::<Vec<i32>>();
+        let source_names = self.load_source_names(&source_ids)?;
 
+        let cycles = find_cycles(&nodes);
+        let mut nodes_map = HashMap::new();
+        let mut quarantined = vec![];
         for node in nodes {
-            let node_id = node.id;
-            let linked_to_id = node.linked_to_id;
-            let node_type = self.get_node_type(&node.type_id).unwrap();
-            let graph_node = GraphNode::new(self.conn, node, node_type);
-            nodes_map.insert(node_id, graph_node);
-            if let Some(parent) = linked_to_id {
-                // nodes_map.get(k: &Q)
-                if let Some(parent_children_vec) = children_map.get_mut(&parent) {
-                    parent_children_vec.push(node_id);
-                } else {
-                    children_map.insert(parent, vec![node_id]);
+            let graph_node = if cycles.contains(&node.id) {
+                Err(QuarantineReason::Cycle)
+            } else {
+                match self.get_node_type(&node.type_id) {
+                    Some(node_type) => GraphNode::new(node.clone(), node_type, &source_names),
+                    None => Err(QuarantineReason::UnknownNodeType(node.type_id)),
                 }
+            };
+            match graph_node {
+                Ok(graph_node) => {
+                    nodes_map.insert(node.id, graph_node);
+                }
+                Err(reason) => quarantined.push(QuarantinedNode { node, reason }),
             }
         }
 
+        let mut children_map: HashMap<i32, Vec<i32>> = HashMap::new();
+        for (node_id, graph_node) in &nodes_map {
+            if let Some(parent) = graph_node.parent_node_id {
+                children_map.entry(parent).or_default().push(*node_id);
+            }
+        }
         for (node_id, mut parent_children_vec) in children_map {
-            nodes_map.get_mut(&node_id).and_then(|graph_node| {
+            if let Some(graph_node) = nodes_map.get_mut(&node_id) {
                 graph_node.children.append(&mut parent_children_vec);
-                Some(())
-            });
+            }
         }
 
-        self.nodes_map = nodes_map;
+        self.undo_log.replace(&mut self.nodes_map, nodes_map);
+        // Inherited nodes and divisors get their paths from the nearest regular parents
+        let path_starts = self
+            .nodes_map
+            .iter()
+            .filter(|(id, graph_node)| match graph_node.node {
+                Node::Inherited { .. } => false,
+                Node::Divisor { .. } => !self.node_has_loaded_parent(**id),
+                _ => true,
+            })
+            .map(|(id, _)| *id)
+            .collect::<Vec<i32>>();
+        for node_id in path_starts {
+            self.update_path(node_id);
+        }
+        let mut loaded = self.nodes_map.keys().copied().collect::<Vec<i32>>();
+        loaded.sort_unstable();
+        quarantined.sort_unstable_by_key(|q| q.node.id);
+        self.load_report = LoadReport {
+            loaded,
+            quarantined,
+        };
+        self.synced_change = synced_change;
         self.loaded = true;
+        self.events.publish(|| Event::SubGroupLoaded {
+            subgroup_id: self.subgroup_id,
+            nodes: self
+                .load_report
+                .loaded
+                .iter()
+                .map(|id| self.nodes_map[id].clone())
+                .collect(),
+        });
 
-        Ok(())
+        Ok(&self.load_report)
+    }
+
+    pub fn get_load_report(&self) -> &LoadReport {
+        &self.load_report
+    }
+}
+
+// The nodes of the cycles of links inside the subgroup
+fn find_cycles(nodes: &[NodeElement]) -> HashSet<i32> {
+    let links = nodes
+        .iter()
+        .map(|node| (node.id, node.linked_to_id))
+        .collect::<HashMap<i32, Option<i32>>>();
+    // false while the node is on the current path
+    let mut visited: HashMap<i32, bool> = HashMap::new();
+    let mut cycles = HashSet::new();
+    for node in nodes {
+        let mut path = vec![];
+        let mut current_id = node.id;
+        loop {
+            if let Some(done) = visited.get(&current_id) {
+                if !done {
+                    let start = path.iter().position(|id| *id == current_id).unwrap();
+                    cycles.extend(&path[start..]);
+                }
+                break;
+            }
+            visited.insert(current_id, false);
+            path.push(current_id);
+            match links[&current_id].filter(|id| links.contains_key(id)) {
+                Some(linked_to_id) => current_id = linked_to_id,
+                None => break,
+            }
+        }
+        for id in path {
+            visited.insert(id, true);
+        }
     }
+    cycles
 }
@@ -0,0 +1,315 @@
+use super::NodeType;
+use crate::models::NodeTypeElement;
+use crate::storage::Storage;
+use diesel::result::Error;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum NamePolicy {
+    Required,
+    Optional,
+    Forbidden,
+}
+
+impl NamePolicy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            NamePolicy::Required => "required",
+            NamePolicy::Optional => "optional",
+            NamePolicy::Forbidden => "forbidden",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "required" => Some(NamePolicy::Required),
+            "optional" => Some(NamePolicy::Optional),
+            "forbidden" => Some(NamePolicy::Forbidden),
+            _ => None,
+        }
+    }
+}
+
+/// The namespace in which nodes of the same type can't have the same name
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum UniquenessScope {
+    Group,
+    Subgroup,
+    // Children of the owner, divisors are transparent
+    Owner,
+    None,
+}
+
+impl UniquenessScope {
+    fn as_str(&self) -> &'static str {
+        match self {
+            UniquenessScope::Group => "group",
+            UniquenessScope::Subgroup => "subgroup",
+            UniquenessScope::Owner => "owner",
+            UniquenessScope::None => "none",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "group" => Some(UniquenessScope::Group),
+            "subgroup" => Some(UniquenessScope::Subgroup),
+            "owner" => Some(UniquenessScope::Owner),
+            "none" => Some(UniquenessScope::None),
+            _ => None,
+        }
+    }
+}
+
+/// Values of the node types, saved as comma separated values or '*'
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum NodeTypesSet {
+    Any,
+    Only(Vec<String>),
+}
+
+impl NodeTypesSet {
+    pub fn only(values: &[&str]) -> Self {
+        NodeTypesSet::Only(values.iter().map(|v| v.to_string()).collect())
+    }
+
+    pub fn contains(&self, value: &str) -> bool {
+        match self {
+            NodeTypesSet::Any => true,
+            NodeTypesSet::Only(values) => values.iter().any(|v| v == value),
+        }
+    }
+
+    fn to_db_value(&self) -> String {
+        match self {
+            NodeTypesSet::Any => "*".into(),
+            NodeTypesSet::Only(values) => values.join(","),
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value.trim() {
+            "*" => NodeTypesSet::Any,
+            value => NodeTypesSet::Only(
+                value
+                    .split(',')
+                    .map(|v| v.trim().to_string())
+                    .filter(|v| !v.is_empty())
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// The declarative constraints of a node type, saved in the node_types table
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NodeTypeDefinition {
+    pub name: String,
+    pub value: String,
+    pub name_policy: NamePolicy,
+    pub allows_description: bool,
+    // Can be created without an owner
+    pub allows_root: bool,
+    pub parent_types: NodeTypesSet,
+    pub child_types: NodeTypesSet,
+    pub uniqueness_scope: UniquenessScope,
+}
+
+impl NodeTypeDefinition {
+    /// The node types added by the first seed data, the later seeds change their rules
+    pub fn built_in() -> Vec<Self> {
+        vec![
+            NodeTypeDefinition {
+                name: "Regular".into(),
+                value: "regular".into(),
+                name_policy: NamePolicy::Required,
+                allows_description: true,
+                allows_root: true,
+                parent_types: NodeTypesSet::Any,
+                child_types: NodeTypesSet::Any,
+                uniqueness_scope: UniquenessScope::Group,
+            },
+            NodeTypeDefinition {
+                name: "Sticky Notes".into(),
+                value: "sticky_notes".into(),
+                name_policy: NamePolicy::Required,
+                allows_description: true,
+                allows_root: false,
+                parent_types: NodeTypesSet::only(&[
+                    "regular",
+                    "sticky_notes",
+                    "inherited",
+                    "symlinks",
+                ]),
+                child_types: NodeTypesSet::only(&["sticky_notes"]),
+                uniqueness_scope: UniquenessScope::Owner,
+            },
+            NodeTypeDefinition {
+                name: "Inherited".into(),
+                value: "inherited".into(),
+                name_policy: NamePolicy::Required,
+                allows_description: true,
+                allows_root: false,
+                parent_types: NodeTypesSet::only(&["regular", "inherited", "symlinks", "divisor"]),
+                child_types: NodeTypesSet::Any,
+                uniqueness_scope: UniquenessScope::Owner,
+            },
+            // The owner of the symlink is its source, so the parent types are the types of the
+            // source, which is validated separately
+            NodeTypeDefinition {
+                name: "Symlinks".into(),
+                value: "symlinks".into(),
+                name_policy: NamePolicy::Forbidden,
+                allows_description: false,
+                allows_root: false,
+                parent_types: NodeTypesSet::only(&["regular", "inherited"]),
+                child_types: NodeTypesSet::Any,
+                uniqueness_scope: UniquenessScope::None,
+            },
+            NodeTypeDefinition {
+                name: "Divisor".into(),
+                value: "divisor".into(),
+                name_policy: NamePolicy::Required,
+                allows_description: false,
+                allows_root: true,
+                parent_types: NodeTypesSet::only(&["regular", "inherited", "symlinks"]),
+                child_types: NodeTypesSet::only(&["regular", "inherited"]),
+                uniqueness_scope: UniquenessScope::None,
+            },
+        ]
+    }
+
+    fn from_element(element: NodeTypeElement) -> Result<Self, Error> {
+        let invalid_rule = |rule: &str| {
+            Error::DeserializationError(
+                format!("Invalid {} of the node type {}", rule, element.value).into(),
+            )
+        };
+        Ok(NodeTypeDefinition {
+            name_policy: NamePolicy::parse(&element.name_policy)
+                .ok_or_else(|| invalid_rule("name_policy"))?,
+            uniqueness_scope: UniquenessScope::parse(&element.uniqueness_scope)
+                .ok_or_else(|| invalid_rule("uniqueness_scope"))?,
+            allows_description: element.allows_description,
+            allows_root: element.allows_root,
+            parent_types: NodeTypesSet::parse(&element.parent_types),
+            child_types: NodeTypesSet::parse(&element.child_types),
+            name: element.name,
+            value: element.value,
+        })
+    }
+
+    /// Adds the node type or updates the rules of the existing one with the same value
+    pub fn save(&self, storage: &dyn Storage) -> Result<i32, Error> {
+        storage.save_node_type(&NodeTypeElement {
+            id: 0,
+            name: self.name.clone(),
+            value: self.value.clone(),
+            name_policy: self.name_policy.as_str().into(),
+            allows_description: self.allows_description,
+            allows_root: self.allows_root,
+            parent_types: self.parent_types.to_db_value(),
+            child_types: self.child_types.to_db_value(),
+            uniqueness_scope: self.uniqueness_scope.as_str().into(),
+        })
+    }
+}
+
+/// All node types of the database with their rules
+#[derive(Debug, Clone, Default)]
+pub struct NodeTypesRegistry {
+    types: HashMap<i32, NodeTypeDefinition>,
+}
+
+impl NodeTypesRegistry {
+    pub fn load(storage: &dyn Storage) -> Result<Self, Error> {
+        let types = storage
+            .load_node_types()?
+            .into_iter()
+            .map(|element| Ok((element.id, NodeTypeDefinition::from_element(element)?)))
+            .collect::<Result<HashMap<i32, NodeTypeDefinition>, Error>>()?;
+        Ok(NodeTypesRegistry { types })
+    }
+
+    pub fn get(&self, type_id: i32) -> Option<&NodeTypeDefinition> {
+        self.types.get(&type_id)
+    }
+
+    pub fn get_type_id_by_value(&self, value: &str) -> Option<i32> {
+        self.types
+            .iter()
+            .find(|(_, definition)| definition.value == value)
+            .map(|(id, _)| *id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&i32, &NodeTypeDefinition)> {
+        self.types.iter()
+    }
+
+    /// Types without a built-in value are custom
+    pub fn get_node_type(&self, type_id: i32) -> Option<NodeType> {
+        let definition = self.types.get(&type_id)?;
+        Some(match &definition.value[..] {
+            "regular" => NodeType::Regular,
+            "sticky_notes" => NodeType::StickyNotes,
+            "inherited" => NodeType::Inherited,
+            "symlinks" => NodeType::SymLink,
+            "divisor" => NodeType::Divisor,
+            _ => NodeType::Custom { type_id },
+        })
+    }
+
+    pub fn get_type_id(&self, node_type: &NodeType) -> Option<i32> {
+        let type_value = match node_type {
+            NodeType::Regular => "regular",
+            NodeType::StickyNotes => "sticky_notes",
+            NodeType::Inherited => "inherited",
+            NodeType::SymLink => "symlinks",
+            NodeType::Divisor => "divisor",
+            NodeType::Custom { type_id } => {
+                return Some(*type_id).filter(|id| self.types.contains_key(id))
+            }
+        };
+        self.get_type_id_by_value(type_value)
+    }
+
+    pub fn get_rules(&self, node_type: &NodeType) -> Option<&NodeTypeDefinition> {
+        self.get(self.get_type_id(node_type)?)
+    }
+}
+
+/// The node types shared by the trees of the workspace, so that they are loaded once instead of
+/// with every subgroup
+#[derive(Debug, Clone, Default)]
+pub struct NodeTypesCache {
+    registry: Arc<RwLock<Option<Arc<NodeTypesRegistry>>>>,
+}
+
+impl NodeTypesCache {
+    /// The cached types, they are loaded on the first call
+    pub fn get(&self, storage: &dyn Storage) -> Result<Arc<NodeTypesRegistry>, Error> {
+        if let Some(registry) = &*self.registry.read() {
+            return Ok(registry.clone());
+        }
+        self.reload(storage)
+    }
+
+    /// Loads the types again, for the types saved after the first load
+    pub fn reload(&self, storage: &dyn Storage) -> Result<Arc<NodeTypesRegistry>, Error> {
+        let registry = Arc::new(NodeTypesRegistry::load(storage)?);
+        *self.registry.write() = Some(registry.clone());
+        Ok(registry)
+    }
+
+    // The types as they are cached now, without loading them
+    pub(crate) fn cached(&self) -> Option<Arc<NodeTypesRegistry>> {
+        self.registry.read().clone()
+    }
+
+    /// Forgets the types, they are loaded again on the next call of `get`
+    pub fn clear(&self) {
+        *self.registry.write() = None;
+    }
+}
@@ -0,0 +1,371 @@
+use super::{GraphNode, Node, NodeType, NodesTree};
+
+// Prefixes of the path segments in the string form, regular nodes and symlinks start the path
+const INHERITED_SEPARATOR: &str = ">";
+const STICKY_NOTE_SEPARATOR: &str = "::";
+// Separates the subgroup name from the path - Anatomy/Heart>Left ventricle::mnemonic
+const SUBGROUP_SEPARATOR: char = '/';
+const ESCAPE_CHARACTER: char = '\\';
+const ESCAPED_CHARACTERS: [char; 4] = ['\\', '>', ':', '/'];
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PathSegment {
+    pub node_id: i32,
+    pub name: String,
+    pub node_type: NodeType,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct NodePath {
+    pub segments: Vec<PathSegment>,
+}
+
+impl NodePath {
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn node_ids(&self) -> Vec<i32> {
+        self.segments.iter().map(|s| s.node_id).collect()
+    }
+
+    pub fn escape_name(name: &str) -> String {
+        let mut escaped = String::with_capacity(name.len());
+        for c in name.chars() {
+            if ESCAPED_CHARACTERS.contains(&c) {
+                escaped.push(ESCAPE_CHARACTER);
+            }
+            escaped.push(c);
+        }
+        escaped
+    }
+}
+
+/// The canonical string form - Heart>Left ventricle::mnemonic
+impl std::fmt::Display for NodePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for segment in &self.segments {
+            match segment.node_type {
+                NodeType::StickyNotes | NodeType::Custom { .. } => {
+                    write!(f, "{}", STICKY_NOTE_SEPARATOR)?
+                }
+                NodeType::Inherited => write!(f, "{}", INHERITED_SEPARATOR)?,
+                _ => {}
+            }
+            write!(f, "{}", NodePath::escape_name(&segment.name))?;
+        }
+        Ok(())
+    }
+}
+
+// Sticky notes and nodes of user-defined types are in the relative path of their owners
+fn is_relative(node: &Node) -> bool {
+    matches!(node, Node::StickyNotes { .. } | Node::Custom { .. })
+}
+
+impl NodesTree {
+    fn get_path_segment(&self, node_id: i32) -> Option<PathSegment> {
+        let node = &self.nodes_map.get(&node_id)?.node;
+        Some(PathSegment {
+            node_id,
+            name: node.get_name().into(),
+            node_type: node.get_node_type(),
+        })
+    }
+
+    // The node itself or the nearest parent which is not a sticky note
+    fn get_path_owner(&self, node_id: i32) -> Option<i32> {
+        let mut current = self.nodes_map.get(&node_id)?;
+        while is_relative(&current.node) {
+            current = self.nodes_map.get(&current.parent_node_id?)?;
+        }
+        Some(current.node.get_node_id())
+    }
+
+    /// From the nearest regular node among parents to the node. For sticky notes this is the
+    /// unique path of their owner.
+    pub fn unique_path(&self, node_id: i32) -> Option<NodePath> {
+        let graph_node = self.nodes_map.get(&node_id)?;
+        let segments = match self.get_path_owner(node_id) {
+            Some(owner_id) => self
+                .nodes_map
+                .get(&owner_id)?
+                .unique_path
+                .iter()
+                .map(|id| self.get_path_segment(*id))
+                .collect::<Option<Vec<PathSegment>>>()?,
+            // The owner of the sticky note is not loaded
+            None if is_relative(&graph_node.node) => vec![],
+            None => return None,
+        };
+        Some(NodePath { segments })
+    }
+
+    /// From the owner of the sticky notes to the node. Empty for other node types.
+    pub fn relative_path(&self, node_id: i32) -> Option<NodePath> {
+        let mut segments = vec![];
+        let mut current = self.nodes_map.get(&node_id)?;
+        while is_relative(&current.node) {
+            segments.push(self.get_path_segment(current.node.get_node_id())?);
+            current = match current
+                .parent_node_id
+                .and_then(|id| self.nodes_map.get(&id))
+            {
+                Some(owner) => owner,
+                None => break,
+            };
+        }
+        segments.reverse();
+        Some(NodePath { segments })
+    }
+
+    /// Path = Unique Path + Relative Path
+    pub fn full_path(&self, node_id: i32) -> Option<NodePath> {
+        let mut path = self.unique_path(node_id)?;
+        path.segments
+            .append(&mut self.relative_path(node_id)?.segments);
+        Some(path)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParsedSegmentKind {
+    // Regular node or symlink
+    Start,
+    Inherited,
+    // Sticky notes and nodes of user-defined types
+    StickyNote,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedSegment {
+    pub kind: ParsedSegmentKind,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedPath {
+    pub subgroup_name: Option<String>,
+    pub segments: Vec<ParsedSegment>,
+}
+
+#[derive(Debug)]
+pub enum PathResolutionError {
+    InvalidPath(String),
+    SubGroupNotFound(String),
+    SubGroupNotLoaded(String),
+    // Segments are counted from 0
+    SegmentNotFound {
+        segment: usize,
+        name: String,
+    },
+    AmbiguousSegment {
+        segment: usize,
+        name: String,
+        candidates: Vec<i32>,
+    },
+}
+
+impl std::fmt::Display for PathResolutionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PathResolutionError::InvalidPath(e) => write!(f, "Invalid path ({})", e),
+            PathResolutionError::SubGroupNotFound(name) => {
+                write!(f, "Subgroup not found ({})", name)
+            }
+            PathResolutionError::SubGroupNotLoaded(name) => {
+                write!(f, "Subgroup is not loaded ({})", name)
+            }
+            PathResolutionError::SegmentNotFound { segment, name } => {
+                write!(f, "Node not found for segment {} ({})", segment, name)
+            }
+            PathResolutionError::AmbiguousSegment {
+                segment,
+                name,
+                candidates,
+            } => write!(
+                f,
+                "Ambiguous segment {} ({}), candidates {:?}",
+                segment, name, candidates
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PathResolutionError {}
+
+impl ParsedPath {
+    /// Parses the canonical string form, the subgroup prefix is optional
+    pub fn parse(path: &str) -> Result<Self, PathResolutionError> {
+        let mut subgroup_name = None;
+        let mut segments = vec![];
+        let mut kind = ParsedSegmentKind::Start;
+        let mut name = String::new();
+        let mut chars = path.chars().peekable();
+        let push_segment = |segments: &mut Vec<ParsedSegment>, kind, name: &mut String| {
+            let trimmed = name.trim();
+            if trimmed.is_empty() {
+                return Err(PathResolutionError::InvalidPath(format!(
+                    "empty segment {}",
+                    segments.len()
+                )));
+            }
+            segments.push(ParsedSegment {
+                kind,
+                name: trimmed.into(),
+            });
+            name.clear();
+            Ok(())
+        };
+        while let Some(c) = chars.next() {
+            match c {
+                ESCAPE_CHARACTER => match chars.next() {
+                    Some(escaped) => name.push(escaped),
+                    None => {
+                        return Err(PathResolutionError::InvalidPath(
+                            "dangling escape character".into(),
+                        ))
+                    }
+                },
+                SUBGROUP_SEPARATOR => {
+                    if subgroup_name.is_some() || !segments.is_empty() {
+                        return Err(PathResolutionError::InvalidPath(
+                            "the subgroup can only be the first part of the path".into(),
+                        ));
+                    }
+                    if name.trim().is_empty() {
+                        return Err(PathResolutionError::InvalidPath(
+                            "empty subgroup name".into(),
+                        ));
+                    }
+                    subgroup_name = Some(name.trim().to_string());
+                    name.clear();
+                }
+                '>' => {
+                    push_segment(&mut segments, kind, &mut name)?;
+                    kind = ParsedSegmentKind::Inherited;
+                }
+                ':' if chars.peek() == Some(&':') => {
+                    chars.next();
+                    // The path of the sticky note without a loaded owner starts with ::
+                    if !(segments.is_empty() && name.trim().is_empty()) {
+                        push_segment(&mut segments, kind, &mut name)?;
+                    }
+                    kind = ParsedSegmentKind::StickyNote;
+                }
+                _ => name.push(c),
+            }
+        }
+        push_segment(&mut segments, kind, &mut name)?;
+        if let Some(position) = segments
+            .windows(2)
+            .position(|w| w[0].kind == ParsedSegmentKind::StickyNote && w[1].kind != w[0].kind)
+        {
+            return Err(PathResolutionError::InvalidPath(format!(
+                "sticky notes can only have sticky notes after them (segment {})",
+                position + 1
+            )));
+        }
+        Ok(ParsedPath {
+            subgroup_name,
+            segments,
+        })
+    }
+}
+
+// Resolves the parsed path segment by segment, all candidates are kept until the end, so that
+// later segments can disambiguate the earlier ones. When the node is a symlink and the source
+// is in one of the trees, the search continues from the source too.
+pub(crate) fn resolve_in_trees(
+    trees: &[&NodesTree],
+    start_trees: &[&NodesTree],
+    path: &ParsedPath,
+) -> Result<i32, PathResolutionError> {
+    let get_graph_node = |id: &i32| trees.iter().find_map(|t| t.nodes_map.get(id));
+    // Divisors are transparent, so their children are searched instead of them
+    let get_children = |id: &i32| {
+        trees
+            .iter()
+            .find(|t| t.nodes_map.contains_key(id))
+            .map(|t| t.get_namespace_children(*id))
+            .unwrap_or_default()
+    };
+    let mut candidates: Vec<i32> = vec![];
+    for (position, segment) in path.segments.iter().enumerate() {
+        let matches = |graph_node: &GraphNode| match (&graph_node.node, segment.kind) {
+            (Node::Regular { name, .. }, ParsedSegmentKind::Start)
+            | (Node::Inherited { name, .. }, ParsedSegmentKind::Inherited) => *name == segment.name,
+            (node, ParsedSegmentKind::StickyNote) if is_relative(node) => {
+                // Only the relative nodes without loaded owners can start the path
+                node.get_name() == segment.name
+                    && (position > 0
+                        || graph_node
+                            .parent_node_id
+                            .and_then(|id| get_graph_node(&id))
+                            .is_none())
+            }
+            (
+                Node::SymLink {
+                    source_node_name, ..
+                },
+                ParsedSegmentKind::Start,
+            ) => *source_node_name == segment.name,
+            _ => false,
+        };
+        candidates = if position == 0 {
+            start_trees
+                .iter()
+                .flat_map(|t| t.nodes_map.iter())
+                .filter(|(_, graph_node)| matches(graph_node))
+                .map(|(id, _)| *id)
+                .collect()
+        } else {
+            let mut next = vec![];
+            for candidate in &candidates {
+                let graph_node = get_graph_node(candidate).unwrap();
+                let mut children = get_children(candidate);
+                // Symlink hop
+                if let Node::SymLink { source_node_id, .. } = graph_node.node {
+                    children.extend(get_children(&source_node_id));
+                }
+                next.extend(
+                    children
+                        .into_iter()
+                        .filter(|c| get_graph_node(c).is_some_and(&matches)),
+                );
+            }
+            next.sort();
+            next.dedup();
+            next
+        };
+        if candidates.is_empty() {
+            return Err(PathResolutionError::SegmentNotFound {
+                segment: position,
+                name: segment.name.clone(),
+            });
+        }
+    }
+    // A symlink found together with its source points to the same node
+    let found = candidates.clone();
+    candidates.retain(|c| match get_graph_node(c).map(|n| &n.node) {
+        Some(Node::SymLink { source_node_id, .. }) => !found.contains(source_node_id),
+        _ => true,
+    });
+    if candidates.len() > 1 {
+        candidates.sort();
+        let last = path.segments.len() - 1;
+        return Err(PathResolutionError::AmbiguousSegment {
+            segment: last,
+            name: path.segments[last].name.clone(),
+            candidates,
+        });
+    }
+    Ok(candidates[0])
+}
+
+impl NodesTree {
+    /// Resolves the path inside this tree, the subgroup prefix is ignored
+    pub fn resolve_path(&self, path: &str) -> Result<i32, PathResolutionError> {
+        resolve_in_trees(&[self], &[self], &ParsedPath::parse(path)?)
+    }
+}
@@ -0,0 +1,364 @@
+use super::{GraphNode, Node, NodeType, NodesTree, QuarantineReason, QuarantinedNode};
+use crate::errors::RelanotesError;
+use crate::events::Event;
+use crate::models::{ChangeElement, NodeElement};
+use crate::storage::Storage;
+use diesel::result::Error;
+use std::collections::{HashMap, HashSet};
+
+/// The node that was written by another process after it was loaded. The edit of the loaded
+/// node is refused instead of overwriting that write, the tree has to be refreshed first.
+#[derive(Serialize, Debug, Clone)]
+pub struct EditConflict {
+    pub node_id: i32,
+    pub loaded_version: i32,
+    // None if the node was deleted
+    pub current: Option<NodeElement>,
+}
+
+/// The rows changed by the refresh. The rows written through the refreshed model itself are
+/// already up to date, so they are left out.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct RefreshReport {
+    pub groups: Vec<i32>,
+    pub subgroups: Vec<i32>,
+    // Created, updated, moved or deleted
+    pub nodes: Vec<i32>,
+    pub quarantined: Vec<QuarantinedNode>,
+    // The changes were pruned from the log before they were applied, so the groups, subgroups
+    // or trees were loaded again - the views have to be rebuilt from the model
+    pub reloaded: bool,
+}
+
+impl RefreshReport {
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+            && self.subgroups.is_empty()
+            && self.nodes.is_empty()
+            && self.quarantined.is_empty()
+            && !self.reloaded
+    }
+
+    // The node moved between subgroups is reported by both trees
+    pub(crate) fn append(&mut self, other: RefreshReport) {
+        self.groups.extend(other.groups);
+        self.subgroups.extend(other.subgroups);
+        self.nodes.extend(other.nodes);
+        self.nodes.sort_unstable();
+        self.nodes.dedup();
+        self.quarantined.extend(other.quarantined);
+        self.reloaded |= other.reloaded;
+    }
+}
+
+/// The changes logged after the given one, None if some of them were already pruned. The mark
+/// of the pruned changes is read after them, so that the pruning in between is noticed too.
+pub(crate) fn changes_after(
+    storage: &dyn Storage,
+    after: i32,
+) -> Result<Option<Vec<ChangeElement>>, Error> {
+    let changes = storage.load_changes(after)?;
+    if storage.pruned_change()? > after {
+        return Ok(None);
+    }
+    Ok(Some(changes))
+}
+
+/// The ids of the rows of the table under the parent, changed after the given change
+pub(crate) fn changed_rows(
+    changes: &[ChangeElement],
+    after: i32,
+    table_name: &str,
+    parent_id: Option<i32>,
+) -> Vec<i32> {
+    let mut row_ids = changes
+        .iter()
+        .filter(|change| {
+            change.id > after && change.table_name == table_name && change.parent_id == parent_id
+        })
+        .map(|change| change.row_id)
+        .collect::<Vec<i32>>();
+    row_ids.sort_unstable();
+    row_ids.dedup();
+    row_ids
+}
+
+/// Locks the rows of the loaded nodes until the end of the transaction and fails with the
+/// conflicts if any of them was written since it was loaded
+pub(crate) fn check_versions<'a>(
+    storage: &dyn Storage,
+    loaded: impl IntoIterator<Item = &'a GraphNode>,
+) -> Result<(), RelanotesError> {
+    let loaded = loaded
+        .into_iter()
+        .map(|graph_node| (graph_node.node.get_node_id(), graph_node.version))
+        .collect::<HashMap<i32, i32>>();
+    let node_ids = loaded.keys().copied().collect::<Vec<i32>>();
+    let mut current = storage
+        .lock_nodes(&node_ids)?
+        .into_iter()
+        .map(|node| (node.id, node))
+        .collect::<HashMap<i32, NodeElement>>();
+    let stale = loaded
+        .into_iter()
+        .filter(|(node_id, version)| {
+            current.get(node_id).map(|node| node.version) != Some(*version)
+        })
+        .collect::<Vec<(i32, i32)>>();
+    let mut conflicts = stale
+        .into_iter()
+        .map(|(node_id, loaded_version)| EditConflict {
+            node_id,
+            loaded_version,
+            current: current.remove(&node_id),
+        })
+        .collect::<Vec<EditConflict>>();
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+    conflicts.sort_unstable_by_key(|conflict| conflict.node_id);
+    Err(RelanotesError::EditConflicts(conflicts))
+}
+
+impl NodesTree {
+    /// Applies the changes written since the tree was loaded or refreshed, by this process or by
+    /// the other ones. Only the changed nodes are loaded again, unless the changes were pruned
+    /// from the log meanwhile - then the whole tree is.
+    pub fn refresh(&mut self) -> Result<RefreshReport, Error> {
+        match changes_after(&*self.storage, self.synced_change)? {
+            Some(changes) => self.apply_changes(&changes),
+            None => self.reload(),
+        }
+    }
+
+    // The views get the whole tree with the loaded event
+    fn reload(&mut self) -> Result<RefreshReport, Error> {
+        let mut report = RefreshReport::default();
+        if !self.loaded {
+            return Ok(report);
+        }
+        report.quarantined = self.load_with_report()?.quarantined.clone();
+        report.reloaded = true;
+        Ok(report)
+    }
+
+    pub(crate) fn check_versions(&self, node_ids: &[i32]) -> Result<(), RelanotesError> {
+        check_versions(
+            &*self.storage,
+            node_ids.iter().filter_map(|id| self.nodes_map.get(id)),
+        )
+    }
+
+    /// The versions bumped by the database for the written rows
+    pub(crate) fn bump_versions(&mut self, node_ids: &[i32]) {
+        for node_id in node_ids {
+            if let Some(graph_node) = self.graph_node_mut(*node_id) {
+                graph_node.version += 1;
+            }
+        }
+    }
+
+    /// The versions of the rows read back after they were written
+    pub(crate) fn set_versions(&mut self, written: &[NodeElement]) {
+        for node in written {
+            if let Some(graph_node) = self.graph_node_mut(node.id) {
+                graph_node.version = node.version;
+            }
+        }
+    }
+
+    // The changes of the other trees are passed too, they can rename the sources of the symlinks
+    pub(crate) fn apply_changes(
+        &mut self,
+        changes: &[ChangeElement],
+    ) -> Result<RefreshReport, Error> {
+        let mut report = RefreshReport::default();
+        if !self.loaded {
+            return Ok(report);
+        }
+        self.save_state();
+        let subgroup_id = self.subgroup_id;
+        let mut node_ids = changed_rows(changes, self.synced_change, "nodes", Some(subgroup_id));
+        let changed_elsewhere = changes
+            .iter()
+            .filter(|change| {
+                change.id > self.synced_change
+                    && change.table_name == "nodes"
+                    && change.parent_id != Some(subgroup_id)
+            })
+            .map(|change| change.row_id)
+            .collect::<HashSet<i32>>();
+        let symlinks = self
+            .nodes_map
+            .values()
+            .filter_map(|graph_node| match graph_node.node {
+                Node::SymLink {
+                    id, source_node_id, ..
+                } if changed_elsewhere.contains(&source_node_id) => Some(id),
+                _ => None,
+            })
+            .collect::<HashSet<i32>>();
+        node_ids.extend(&symlinks);
+        node_ids.sort_unstable();
+        node_ids.dedup();
+        if let Some(last) = changes.last() {
+            self.synced_change = self.synced_change.max(last.id);
+        }
+        if node_ids.is_empty() {
+            return Ok(report);
+        }
+
+        let rows = self
+            .storage
+            .get_nodes(&node_ids)?
+            .into_iter()
+            .filter(|node| node.subgroup_id == subgroup_id)
+            .collect::<Vec<NodeElement>>();
+        let source_ids = rows
+            .iter()
+            .filter(|node| self.get_node_type(&node.type_id) == Some(NodeType::SymLink))
+            .filter_map(|node| node.linked_to_id)
+            .collect::<Vec<i32>>();
+        let source_names = self.load_source_names(&source_ids)?;
+
+        let current_ids = rows.iter().map(|node| node.id).collect::<HashSet<i32>>();
+        let mut deleted = node_ids
+            .iter()
+            .filter(|id| !current_ids.contains(id) && self.nodes_map.contains_key(id))
+            .copied()
+            .collect::<Vec<i32>>();
+        for node_id in &deleted {
+            self.remove_graph_node(*node_id);
+        }
+        let (mut created, mut updated, mut moved) = (vec![], vec![], vec![]);
+        for row in rows {
+            let graph_node = match self.get_node_type(&row.type_id) {
+                Some(node_type) => GraphNode::new(row.clone(), node_type, &source_names),
+                None => Err(QuarantineReason::UnknownNodeType(row.type_id)),
+            };
+            let mut graph_node = match graph_node {
+                Ok(graph_node) => graph_node,
+                Err(reason) => {
+                    if self.nodes_map.contains_key(&row.id) {
+                        self.remove_graph_node(row.id);
+                        deleted.push(row.id);
+                    }
+                    report
+                        .quarantined
+                        .push(QuarantinedNode { node: row, reason });
+                    continue;
+                }
+            };
+            let node_id = row.id;
+            // Written through this tree, or a symlink whose source wasn't renamed
+            let unchanged = self.nodes_map.get(&node_id).is_some_and(|loaded| {
+                loaded.version == graph_node.version
+                    && loaded.node.get_name() == graph_node.node.get_name()
+            });
+            if unchanged {
+                continue;
+            }
+            match self.take_graph_node(node_id) {
+                Some(loaded) => {
+                    graph_node.children = loaded.children;
+                    if loaded.parent_node_id == graph_node.parent_node_id {
+                        updated.push(node_id);
+                    } else {
+                        if let Some(parent) =
+                            loaded.parent_node_id.and_then(|id| self.graph_node_mut(id))
+                        {
+                            parent.remove_child(node_id);
+                        }
+                        self.add_to_parent(node_id, graph_node.parent_node_id);
+                        moved.push(node_id);
+                    }
+                }
+                None => {
+                    // The children that were loaded before the node
+                    graph_node.children = self
+                        .nodes_map
+                        .iter()
+                        .filter(|(_, child)| child.parent_node_id == Some(node_id))
+                        .map(|(id, _)| *id)
+                        .collect();
+                    self.add_to_parent(node_id, graph_node.parent_node_id);
+                    created.push(node_id);
+                }
+            }
+            self.insert_graph_node(graph_node);
+        }
+        for node_id in created.iter().chain(&updated).chain(&moved) {
+            self.update_path(*node_id);
+        }
+
+        let load_report = &mut self.load_report;
+        load_report.loaded.retain(|id| !deleted.contains(id));
+        load_report.loaded.extend(&created);
+        load_report.loaded.sort_unstable();
+        load_report
+            .quarantined
+            .retain(|quarantined| !node_ids.contains(&quarantined.node.id));
+        load_report
+            .quarantined
+            .extend(report.quarantined.iter().cloned());
+        self.publish_refresh(&deleted, &created, &updated, &moved);
+
+        report.nodes = deleted;
+        report
+            .nodes
+            .extend(created.iter().chain(&updated).chain(&moved));
+        report.nodes.sort_unstable();
+        Ok(report)
+    }
+
+    // Removes the node from the graph, its loaded children stay as roots until they are
+    // changed too
+    fn remove_graph_node(&mut self, node_id: i32) {
+        if let Some(graph_node) = self.take_graph_node(node_id) {
+            if let Some(parent) = graph_node
+                .parent_node_id
+                .and_then(|id| self.graph_node_mut(id))
+            {
+                parent.remove_child(node_id);
+            }
+        }
+    }
+
+    fn add_to_parent(&mut self, node_id: i32, parent_node_id: Option<i32>) {
+        if let Some(parent) = parent_node_id.and_then(|id| self.graph_node_mut(id)) {
+            if !parent.children.contains(&node_id) {
+                parent.add_child(node_id);
+            }
+        }
+    }
+
+    fn publish_refresh(&self, deleted: &[i32], created: &[i32], updated: &[i32], moved: &[i32]) {
+        let subgroup_id = self.subgroup_id;
+        if !deleted.is_empty() {
+            self.events.publish(|| Event::NodeDeleted {
+                subgroup_id,
+                node_ids: deleted.to_vec(),
+            });
+        }
+        for node_id in created {
+            self.events.publish(|| Event::NodeCreated {
+                subgroup_id,
+                node: self.nodes_map[node_id].clone(),
+            });
+        }
+        for node_id in updated {
+            self.events.publish(|| Event::NodeUpdated {
+                subgroup_id,
+                node: self.nodes_map[node_id].clone(),
+            });
+        }
+        for node_id in moved {
+            self.events.publish(|| Event::NodeMoved {
+                node_id: *node_id,
+                from_subgroup_id: subgroup_id,
+                to_subgroup_id: subgroup_id,
+                nodes: self.get_subtree(*node_id),
+            });
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The id of the new transaction of the model, the undo logs of all models changed in it are
+/// committed or rolled back by this id
+pub(crate) fn next_transaction_id() -> u64 {
+    static TRANSACTIONS: AtomicU64 = AtomicU64::new(1);
+    TRANSACTIONS.fetch_add(1, Ordering::Relaxed)
+}
+
+// The model before one transaction - the entries before their first change in it (None for the
+// entries added by it) and the rest of the model, saved before it's first changed
+#[derive(Clone)]
+struct Savepoint<K, V, S> {
+    transaction_id: u64,
+    entries: HashMap<K, Option<V>>,
+    state: Option<S>,
+}
+
+/// What the open transactions changed in the map of the model, so that the failed transaction is
+/// undone without copying the whole model up front. The models created inside the transaction
+/// have no savepoint of it, they are dropped by the models they were added to.
+#[derive(Clone)]
+pub(crate) struct UndoLog<K, V, S> {
+    // The innermost transaction last
+    savepoints: Vec<Savepoint<K, V, S>>,
+}
+
+impl<K, V, S> Default for UndoLog<K, V, S> {
+    fn default() -> Self {
+        UndoLog { savepoints: vec![] }
+    }
+}
+
+impl<K: Copy + Eq + Hash, V: Clone, S> UndoLog<K, V, S> {
+    pub(crate) fn begin(&mut self, transaction_id: u64) {
+        self.savepoints.push(Savepoint {
+            transaction_id,
+            entries: HashMap::new(),
+            state: None,
+        });
+    }
+
+    /// The rest of the model is saved only once in the innermost transaction
+    pub(crate) fn save_state(&mut self, state: impl FnOnce() -> S) {
+        if let Some(savepoint) = self.savepoints.last_mut() {
+            if savepoint.state.is_none() {
+                savepoint.state = Some(state());
+            }
+        }
+    }
+
+    // The entry is copied before its first change in the innermost transaction
+    fn save_entry(&mut self, entries: &HashMap<K, V>, key: K) {
+        if let Some(savepoint) = self.savepoints.last_mut() {
+            savepoint
+                .entries
+                .entry(key)
+                .or_insert_with(|| entries.get(&key).cloned());
+        }
+    }
+
+    pub(crate) fn get_mut<'a>(
+        &mut self,
+        entries: &'a mut HashMap<K, V>,
+        key: K,
+    ) -> Option<&'a mut V> {
+        if entries.contains_key(&key) {
+            self.save_entry(entries, key);
+        }
+        entries.get_mut(&key)
+    }
+
+    pub(crate) fn insert(&mut self, entries: &mut HashMap<K, V>, key: K, value: V) {
+        self.save_entry(entries, key);
+        entries.insert(key, value);
+    }
+
+    pub(crate) fn remove(&mut self, entries: &mut HashMap<K, V>, key: K) -> Option<V> {
+        self.save_entry(entries, key);
+        entries.remove(&key)
+    }
+
+    /// Removes the entry without copying it, the removed value is kept by the log
+    pub(crate) fn discard(&mut self, entries: &mut HashMap<K, V>, key: K) {
+        let value = entries.remove(&key);
+        match self.savepoints.last_mut() {
+            Some(savepoint) => {
+                savepoint.entries.entry(key).or_insert(value);
+            }
+            None => drop(value),
+        }
+    }
+
+    /// Replaces all entries without copying them, the replaced values are kept by the log
+    pub(crate) fn replace(&mut self, entries: &mut HashMap<K, V>, new_entries: HashMap<K, V>) {
+        let old_entries = std::mem::replace(entries, new_entries);
+        if let Some(savepoint) = self.savepoints.last_mut() {
+            for (key, value) in old_entries {
+                savepoint.entries.entry(key).or_insert(Some(value));
+            }
+            for key in entries.keys() {
+                savepoint.entries.entry(*key).or_insert(None);
+            }
+        }
+    }
+
+    /// The changes are kept for the outer transaction, if there is one
+    pub(crate) fn commit(&mut self, transaction_id: u64) {
+        while self
+            .savepoints
+            .last()
+            .is_some_and(|savepoint| savepoint.transaction_id >= transaction_id)
+        {
+            let savepoint = self.savepoints.pop().unwrap();
+            if let Some(outer) = self.savepoints.last_mut() {
+                for (key, value) in savepoint.entries {
+                    outer.entries.entry(key).or_insert(value);
+                }
+                if outer.state.is_none() {
+                    outer.state = savepoint.state;
+                }
+            }
+        }
+    }
+
+    /// Puts the entries back and returns the rest of the model as it was before the transaction,
+    /// if it was changed
+    pub(crate) fn rollback(
+        &mut self,
+        entries: &mut HashMap<K, V>,
+        transaction_id: u64,
+    ) -> Option<S> {
+        let mut state = None;
+        while self
+            .savepoints
+            .last()
+            .is_some_and(|savepoint| savepoint.transaction_id >= transaction_id)
+        {
+            let savepoint = self.savepoints.pop().unwrap();
+            for (key, value) in savepoint.entries {
+                match value {
+                    Some(value) => entries.insert(key, value),
+                    None => entries.remove(&key),
+                };
+            }
+            if savepoint.state.is_some() {
+                state = savepoint.state;
+            }
+        }
+        state
+    }
+}
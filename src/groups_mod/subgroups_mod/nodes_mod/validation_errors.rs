@@ -1,9 +1,12 @@
+use super::NodeType;
+
 #[derive(Debug)]
 pub enum RelanotesValidationRejection {
     TechnicalError(String),
     TryingToMutateOtherSubgroup { current: i32, checking: i32 },
     EmptyName,
     LinkedToItself(i32),
+    CyclicLink(i32),
     DuplicateRegularNode(String),
     StickyNoteWithoutOwner,
     InvalidStickyNoteOwner,
@@ -16,6 +19,15 @@ pub enum RelanotesValidationRejection {
     SymLinkWithoutOwner,
     InvalidSymLinkOwner,
     SymLinkToSameSubgroup,
+    DuplicateSymLink(i32),
+    DivisorWithDescription,
+    InvalidDivisorOwner,
+    // Rejections of the user-defined node types, with the value of the type
+    NameNotAllowed(String),
+    DescriptionNotAllowed(String),
+    NodeWithoutOwner(String),
+    InvalidNodeOwner(String),
+    DuplicateNode { node_type: String, name: String },
 }
 
 impl std::fmt::Display for RelanotesValidationRejection {
@@ -25,6 +37,7 @@ impl std::fmt::Display for RelanotesValidationRejection {
             RelanotesValidationRejection::TryingToMutateOtherSubgroup { current: _, checking } => write!(f, "Trying to mutate other subgroup ({})", checking),
             RelanotesValidationRejection::EmptyName => write!(f, "The name is empty"),
             RelanotesValidationRejection::LinkedToItself(id) => write!(f, "Linked to itself ({})", id),
+            RelanotesValidationRejection::CyclicLink(id) => write!(f, "Linking creates a cycle ({})", id),
             RelanotesValidationRejection::DuplicateRegularNode(e) => write!(f, "Duplicate regular node ({})", e),
             RelanotesValidationRejection::StickyNoteWithoutOwner => write!(f, "Sticky note without an owner"),
             RelanotesValidationRejection::InvalidStickyNoteOwner => write!(f, "Invalid sticky note owner"),
@@ -37,9 +50,80 @@ impl std::fmt::Display for RelanotesValidationRejection {
             RelanotesValidationRejection::SymLinkWithoutOwner => write!(f, "SymLink without an owner"),
             RelanotesValidationRejection::InvalidSymLinkOwner => write!(f, "Invalid SymLink owner"),
             RelanotesValidationRejection::SymLinkToSameSubgroup => write!(f, "SymLink targetting to the same group"),
+            RelanotesValidationRejection::DuplicateSymLink(source_id) => write!(f, "Duplicate SymLink to the node ({})", source_id),
+            RelanotesValidationRejection::DivisorWithDescription => write!(f, "Divisor with a description"),
+            RelanotesValidationRejection::InvalidDivisorOwner => write!(f, "Invalid divisor owner"),
+            RelanotesValidationRejection::NameNotAllowed(t) => write!(f, "Node of type {} with a name", t),
+            RelanotesValidationRejection::DescriptionNotAllowed(t) => write!(f, "Node of type {} with a description", t),
+            RelanotesValidationRejection::NodeWithoutOwner(t) => write!(f, "Node of type {} without an owner", t),
+            RelanotesValidationRejection::InvalidNodeOwner(t) => write!(f, "Invalid owner of the node of type {}", t),
+            RelanotesValidationRejection::DuplicateNode { node_type, name } => write!(f, "Duplicate node of type {} ({})", node_type, name),
         }
     }
 }
 
 impl std::error::Error for RelanotesValidationRejection {
 }
+
+// The built-in node types keep their own rejections
+impl RelanotesValidationRejection {
+    pub fn name_not_allowed(node_type: NodeType, type_value: &str) -> Self {
+        match node_type {
+            NodeType::SymLink => RelanotesValidationRejection::SymLinkWithName,
+            _ => RelanotesValidationRejection::NameNotAllowed(type_value.into()),
+        }
+    }
+
+    pub fn description_not_allowed(node_type: NodeType, type_value: &str) -> Self {
+        match node_type {
+            NodeType::SymLink => RelanotesValidationRejection::SymLinkWithDescription,
+            NodeType::Divisor => RelanotesValidationRejection::DivisorWithDescription,
+            _ => RelanotesValidationRejection::DescriptionNotAllowed(type_value.into()),
+        }
+    }
+
+    pub fn missing_owner(node_type: NodeType, type_value: &str) -> Self {
+        match node_type {
+            NodeType::StickyNotes => RelanotesValidationRejection::StickyNoteWithoutOwner,
+            NodeType::Inherited => RelanotesValidationRejection::InheritedNodeWithoutOwner,
+            NodeType::SymLink => RelanotesValidationRejection::SymLinkWithoutOwner,
+            _ => RelanotesValidationRejection::NodeWithoutOwner(type_value.into()),
+        }
+    }
+
+    pub fn invalid_owner(node_type: NodeType, type_value: &str) -> Self {
+        match node_type {
+            NodeType::StickyNotes => RelanotesValidationRejection::InvalidStickyNoteOwner,
+            NodeType::Inherited => RelanotesValidationRejection::InvalidInheritedNodeOwner,
+            NodeType::SymLink => RelanotesValidationRejection::InvalidSymLinkOwner,
+            NodeType::Divisor => RelanotesValidationRejection::InvalidDivisorOwner,
+            _ => RelanotesValidationRejection::InvalidNodeOwner(type_value.into()),
+        }
+    }
+
+    pub fn duplicate(node_type: NodeType, type_value: &str, name: &str) -> Self {
+        match node_type {
+            NodeType::Regular => RelanotesValidationRejection::DuplicateRegularNode(name.into()),
+            NodeType::StickyNotes => RelanotesValidationRejection::DuplicateStickyNote(name.into()),
+            NodeType::Inherited => RelanotesValidationRejection::DuplicateInheritedNode(name.into()),
+            _ => RelanotesValidationRejection::DuplicateNode {
+                node_type: type_value.into(),
+                name: name.into(),
+            },
+        }
+    }
+
+    // The messages of the triggers are the names of the variants, the triggers can't report the
+    // node, so it's taken from the write that failed
+    pub fn from_constraint_violation(message: &str, node_id: Option<i32>, name: &str) -> Option<Self> {
+        Some(match message {
+            "DuplicateRegularNode" => RelanotesValidationRejection::DuplicateRegularNode(name.into()),
+            "DuplicateStickyNote" => RelanotesValidationRejection::DuplicateStickyNote(name.into()),
+            "DuplicateInheritedNode" => RelanotesValidationRejection::DuplicateInheritedNode(name.into()),
+            "SymLinkToSameSubgroup" => RelanotesValidationRejection::SymLinkToSameSubgroup,
+            "LinkedToItself" => RelanotesValidationRejection::LinkedToItself(node_id?),
+            "CyclicLink" => RelanotesValidationRejection::CyclicLink(node_id?),
+            _ => return None,
+        })
+    }
+}
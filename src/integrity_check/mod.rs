@@ -0,0 +1,598 @@
+use crate::groups_mod::subgroups_mod::nodes_mod::node_types::{
+    NodeTypeDefinition, NodeTypesRegistry, UniquenessScope,
+};
+use crate::models::{NodeElement, SubGroupElement};
+use crate::storage::{NewNode, Storage};
+use diesel::result::Error;
+use std::collections::{HashMap, HashSet};
+
+// The regular root node of the subgroup, where the nodes without a valid owner are moved. The
+// group with this name keeps the subgroups without a group and the nodes without a subgroup.
+const LOST_AND_FOUND_NAME: &str = "lost+found";
+// Repairs can leave new problems behind (children of deleted symlinks), so they run in passes
+const MAX_REPAIR_PASSES: usize = 8;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum IntegrityProblem {
+    SubGroupWithoutGroup {
+        subgroup_id: i32,
+        group_id: i32,
+    },
+    NodeWithoutSubGroup {
+        node_id: i32,
+        subgroup_id: i32,
+    },
+    TypelessNode {
+        node_id: i32,
+        type_id: i32,
+    },
+    // The linked node doesn't exist
+    OrphanedLink {
+        node_id: i32,
+        linked_to_id: i32,
+    },
+    Cycle {
+        node_ids: Vec<i32>,
+    },
+    // Sticky notes, inherited nodes and the other types that can't be roots
+    NodeWithoutOwner {
+        node_id: i32,
+    },
+    DanglingSymLink {
+        node_id: i32,
+        source_node_id: Option<i32>,
+    },
+    SymLinkToSameSubGroup {
+        node_id: i32,
+        source_node_id: i32,
+    },
+    DuplicateName {
+        node_ids: Vec<i32>,
+        name: String,
+    },
+}
+
+impl std::fmt::Display for IntegrityProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            IntegrityProblem::SubGroupWithoutGroup {
+                subgroup_id,
+                group_id,
+            } => write!(
+                f,
+                "Subgroup ({}) of a missing group ({})",
+                subgroup_id, group_id
+            ),
+            IntegrityProblem::NodeWithoutSubGroup {
+                node_id,
+                subgroup_id,
+            } => write!(
+                f,
+                "Node ({}) of a missing subgroup ({})",
+                node_id, subgroup_id
+            ),
+            IntegrityProblem::TypelessNode { node_id, type_id } => {
+                write!(f, "Node ({}) of a missing type ({})", node_id, type_id)
+            }
+            IntegrityProblem::OrphanedLink {
+                node_id,
+                linked_to_id,
+            } => write!(
+                f,
+                "Node ({}) linked to a missing node ({})",
+                node_id, linked_to_id
+            ),
+            IntegrityProblem::Cycle { node_ids } => write!(f, "Cycle of links ({:?})", node_ids),
+            IntegrityProblem::NodeWithoutOwner { node_id } => {
+                write!(f, "Node without an owner ({})", node_id)
+            }
+            IntegrityProblem::DanglingSymLink {
+                node_id,
+                source_node_id,
+            } => write!(
+                f,
+                "SymLink ({}) to a missing node ({:?})",
+                node_id, source_node_id
+            ),
+            IntegrityProblem::SymLinkToSameSubGroup {
+                node_id,
+                source_node_id,
+            } => write!(
+                f,
+                "SymLink ({}) to a node of the same subgroup ({})",
+                node_id, source_node_id
+            ),
+            IntegrityProblem::DuplicateName { node_ids, name } => {
+                write!(f, "Duplicate name {} ({:?})", name, node_ids)
+            }
+        }
+    }
+}
+
+impl IntegrityProblem {
+    // The nodes the repair of the problem reads from the snapshot
+    fn get_node_ids(&self) -> Vec<i32> {
+        match self {
+            IntegrityProblem::SubGroupWithoutGroup { .. } => vec![],
+            IntegrityProblem::NodeWithoutSubGroup { node_id, .. }
+            | IntegrityProblem::TypelessNode { node_id, .. }
+            | IntegrityProblem::OrphanedLink { node_id, .. }
+            | IntegrityProblem::NodeWithoutOwner { node_id }
+            | IntegrityProblem::DanglingSymLink { node_id, .. }
+            | IntegrityProblem::SymLinkToSameSubGroup { node_id, .. } => vec![*node_id],
+            IntegrityProblem::Cycle { node_ids }
+            | IntegrityProblem::DuplicateName { node_ids, .. } => node_ids.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum RepairAction {
+    Detached,
+    Deleted,
+    MovedToLostAndFound { lost_and_found_id: i32 },
+    MovedToLostAndFoundGroup { group_id: i32 },
+    MovedToLostAndFoundSubGroup { subgroup_id: i32 },
+    // Can't be fixed without the user, like the duplicate names
+    NotRepaired,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Repair {
+    pub problem: IntegrityProblem,
+    pub action: RepairAction,
+}
+
+// The namespace where the node of the given type has to have a unique name
+#[derive(Hash, PartialEq, Eq)]
+enum Namespace {
+    Group(i32),
+    SubGroup(i32),
+    Owner(i32),
+    Roots(i32),
+}
+
+struct DatabaseSnapshot {
+    group_ids: HashSet<i32>,
+    subgroups: HashMap<i32, SubGroupElement>,
+    nodes: HashMap<i32, NodeElement>,
+    node_types: NodeTypesRegistry,
+}
+
+impl DatabaseSnapshot {
+    fn load(storage: &dyn Storage) -> Result<Self, Error> {
+        Ok(DatabaseSnapshot {
+            group_ids: storage.load_groups()?.into_iter().map(|g| g.id).collect(),
+            subgroups: storage
+                .load_subgroups(None)?
+                .into_iter()
+                .map(|s| (s.id, s))
+                .collect(),
+            nodes: storage
+                .load_nodes(None)?
+                .into_iter()
+                .map(|n| (n.id, n))
+                .collect(),
+            node_types: NodeTypesRegistry::load(storage)?,
+        })
+    }
+
+    fn get_rules(&self, node: &NodeElement) -> Option<&NodeTypeDefinition> {
+        self.node_types.get(node.type_id)
+    }
+
+    fn is_divisor(&self, node: &NodeElement) -> bool {
+        self.get_rules(node).is_some_and(|r| r.value == "divisor")
+    }
+
+    // Divisors are transparent, stops on cycles of divisors
+    fn get_namespace_owner(&self, node: &NodeElement) -> Option<i32> {
+        let mut owner_id = node.linked_to_id?;
+        for _ in 0..self.nodes.len() {
+            let owner = self.nodes.get(&owner_id)?;
+            if !self.is_divisor(owner) {
+                return Some(owner_id);
+            }
+            owner_id = owner.linked_to_id?;
+        }
+        None
+    }
+
+    fn get_sorted_node_ids(&self) -> Vec<i32> {
+        let mut node_ids = self.nodes.keys().copied().collect::<Vec<i32>>();
+        node_ids.sort_unstable();
+        node_ids
+    }
+
+    fn find_problems(&self) -> Vec<IntegrityProblem> {
+        let mut problems = vec![];
+        let mut subgroups = self.subgroups.values().collect::<Vec<&SubGroupElement>>();
+        subgroups.sort_unstable_by_key(|s| s.id);
+        for subgroup in subgroups {
+            if !self.group_ids.contains(&subgroup.group_id) {
+                problems.push(IntegrityProblem::SubGroupWithoutGroup {
+                    subgroup_id: subgroup.id,
+                    group_id: subgroup.group_id,
+                });
+            }
+        }
+        for node_id in self.get_sorted_node_ids() {
+            if let Some(problem) = self.check_node(&self.nodes[&node_id]) {
+                problems.push(problem);
+            }
+        }
+        problems.extend(self.find_cycles());
+        problems.extend(self.find_duplicates());
+        problems
+    }
+
+    fn check_node(&self, node: &NodeElement) -> Option<IntegrityProblem> {
+        let node_id = node.id;
+        if !self.subgroups.contains_key(&node.subgroup_id) {
+            return Some(IntegrityProblem::NodeWithoutSubGroup {
+                node_id,
+                subgroup_id: node.subgroup_id,
+            });
+        }
+        let rules = match self.get_rules(node) {
+            Some(rules) => rules,
+            None => {
+                return Some(IntegrityProblem::TypelessNode {
+                    node_id,
+                    type_id: node.type_id,
+                })
+            }
+        };
+        let linked_node = node.linked_to_id.and_then(|id| self.nodes.get(&id));
+        if rules.value == "symlinks" {
+            return match linked_node {
+                None => Some(IntegrityProblem::DanglingSymLink {
+                    node_id,
+                    source_node_id: node.linked_to_id,
+                }),
+                Some(source) if source.subgroup_id == node.subgroup_id => {
+                    Some(IntegrityProblem::SymLinkToSameSubGroup {
+                        node_id,
+                        source_node_id: source.id,
+                    })
+                }
+                Some(_) => None,
+            };
+        }
+        match (node.linked_to_id, linked_node) {
+            (Some(linked_to_id), None) => Some(IntegrityProblem::OrphanedLink {
+                node_id,
+                linked_to_id,
+            }),
+            _ if !rules.allows_root && self.get_namespace_owner(node).is_none() => {
+                Some(IntegrityProblem::NodeWithoutOwner { node_id })
+            }
+            _ => None,
+        }
+    }
+
+    fn find_cycles(&self) -> Vec<IntegrityProblem> {
+        // false while the node is on the current path
+        let mut visited: HashMap<i32, bool> = HashMap::new();
+        let mut cycles = vec![];
+        for node_id in self.get_sorted_node_ids() {
+            let mut path = vec![];
+            let mut current_id = node_id;
+            loop {
+                if let Some(done) = visited.get(&current_id) {
+                    if !done {
+                        let start = path.iter().position(|id| *id == current_id).unwrap();
+                        cycles.push(IntegrityProblem::Cycle {
+                            node_ids: path[start..].to_vec(),
+                        });
+                    }
+                    break;
+                }
+                visited.insert(current_id, false);
+                path.push(current_id);
+                match self.nodes[&current_id]
+                    .linked_to_id
+                    .filter(|id| self.nodes.contains_key(id))
+                {
+                    Some(linked_to_id) => current_id = linked_to_id,
+                    None => break,
+                }
+            }
+            for id in path {
+                visited.insert(id, true);
+            }
+        }
+        cycles
+    }
+
+    fn get_namespace(&self, node: &NodeElement, scope: UniquenessScope) -> Option<Namespace> {
+        let subgroup = self.subgroups.get(&node.subgroup_id)?;
+        Some(match scope {
+            UniquenessScope::Group => Namespace::Group(subgroup.group_id),
+            UniquenessScope::Subgroup => Namespace::SubGroup(subgroup.id),
+            UniquenessScope::Owner => match self.get_namespace_owner(node) {
+                Some(owner_id) => Namespace::Owner(owner_id),
+                None => Namespace::Roots(subgroup.id),
+            },
+            UniquenessScope::None => return None,
+        })
+    }
+
+    fn find_duplicates(&self) -> Vec<IntegrityProblem> {
+        let mut namespaces: HashMap<(i32, Namespace, &str), Vec<i32>> = HashMap::new();
+        for node_id in self.get_sorted_node_ids() {
+            let node = &self.nodes[&node_id];
+            let namespace = self
+                .get_rules(node)
+                .and_then(|rules| self.get_namespace(node, rules.uniqueness_scope));
+            if let Some(namespace) = namespace {
+                namespaces
+                    .entry((node.type_id, namespace, &node.name))
+                    .or_default()
+                    .push(node_id);
+            }
+        }
+        let mut duplicates = namespaces
+            .into_iter()
+            .filter(|(_, node_ids)| node_ids.len() > 1)
+            .map(|((_, _, name), node_ids)| IntegrityProblem::DuplicateName {
+                node_ids,
+                name: name.to_string(),
+            })
+            .collect::<Vec<IntegrityProblem>>();
+        duplicates.sort_unstable_by_key(|problem| match problem {
+            IntegrityProblem::DuplicateName { node_ids, .. } => node_ids[0],
+            _ => unreachable!(),
+        });
+        duplicates
+    }
+
+    fn repair(
+        &self,
+        storage: &dyn Storage,
+        problem: &IntegrityProblem,
+    ) -> Result<RepairAction, Error> {
+        let result = storage.transaction::<_, Error, _>(|| match problem {
+            IntegrityProblem::SubGroupWithoutGroup { subgroup_id, .. } => {
+                self.move_to_lost_and_found_group(storage, *subgroup_id)
+            }
+            IntegrityProblem::NodeWithoutSubGroup {
+                node_id,
+                subgroup_id,
+            } => {
+                let lost_and_found_id = self.get_lost_and_found_subgroup(storage, *subgroup_id)?;
+                storage.set_subgroup_id(&[*node_id], lost_and_found_id)?;
+                Ok(RepairAction::MovedToLostAndFoundSubGroup {
+                    subgroup_id: lost_and_found_id,
+                })
+            }
+            IntegrityProblem::DanglingSymLink { node_id, .. } => {
+                self.delete_symlink(storage, *node_id)
+            }
+            IntegrityProblem::SymLinkToSameSubGroup {
+                node_id,
+                source_node_id,
+            } => {
+                let children = storage
+                    .find_linked_nodes(&[*node_id])?
+                    .into_iter()
+                    .map(|n| n.id)
+                    .collect::<Vec<i32>>();
+                storage.set_linked_to_id(&children, Some(*source_node_id))?;
+                storage.delete_nodes(&[*node_id])?;
+                Ok(RepairAction::Deleted)
+            }
+            IntegrityProblem::OrphanedLink { node_id, .. }
+            | IntegrityProblem::NodeWithoutOwner { node_id } => self.rehome(storage, *node_id),
+            IntegrityProblem::Cycle { node_ids } => {
+                self.rehome(storage, *node_ids.iter().min().unwrap())
+            }
+            IntegrityProblem::TypelessNode { .. } | IntegrityProblem::DuplicateName { .. } => {
+                Ok(RepairAction::NotRepaired)
+            }
+        });
+        match result {
+            // Rejected by the database constraints, or a part of the repair wasn't possible
+            Err(Error::DatabaseError(..)) | Err(Error::RollbackTransaction) => {
+                Ok(RepairAction::NotRepaired)
+            }
+            result => result,
+        }
+    }
+
+    // Detaches the node if it can be a root, otherwise moves it into the lost+found node. The
+    // symlinks are deleted, they can't be linked to anything else and have no content.
+    fn rehome(&self, storage: &dyn Storage, node_id: i32) -> Result<RepairAction, Error> {
+        let node = match self.nodes.get(&node_id) {
+            Some(node) => node,
+            None => return Ok(RepairAction::NotRepaired),
+        };
+        let rules = match self.get_rules(node) {
+            Some(rules) => rules,
+            None => return Ok(RepairAction::NotRepaired),
+        };
+        if rules.value == "symlinks" {
+            return self.delete_symlink(storage, node_id);
+        }
+        if rules.allows_root {
+            storage.set_linked_to_id(&[node_id], None)?;
+            return Ok(RepairAction::Detached);
+        }
+        let regular_rules = self
+            .node_types
+            .get_type_id_by_value("regular")
+            .and_then(|type_id| self.node_types.get(type_id));
+        let accepts_regular_owner = regular_rules.is_some_and(|regular_rules| {
+            rules.parent_types.contains(&regular_rules.value)
+                && regular_rules.child_types.contains(&rules.value)
+        });
+        if !accepts_regular_owner {
+            return Ok(RepairAction::NotRepaired);
+        }
+        let subgroup = match self.subgroups.get(&node.subgroup_id) {
+            Some(subgroup) => subgroup,
+            None => return Ok(RepairAction::NotRepaired),
+        };
+        let lost_and_found_id = match self.get_lost_and_found(storage, subgroup)? {
+            Some(lost_and_found_id) => lost_and_found_id,
+            None => return Ok(RepairAction::NotRepaired),
+        };
+        storage.set_linked_to_id(&[node_id], Some(lost_and_found_id))?;
+        Ok(RepairAction::MovedToLostAndFound { lost_and_found_id })
+    }
+
+    // The children are rehomed first, the whole repair is rolled back if one of them can't be
+    fn delete_symlink(&self, storage: &dyn Storage, node_id: i32) -> Result<RepairAction, Error> {
+        for child in storage.find_linked_nodes(&[node_id])? {
+            if !self.nodes.contains_key(&child.id)
+                || self.rehome(storage, child.id)? == RepairAction::NotRepaired
+            {
+                return Err(Error::RollbackTransaction);
+            }
+        }
+        storage.delete_nodes(&[node_id])?;
+        Ok(RepairAction::Deleted)
+    }
+
+    // The subgroup keeps its nodes, unless their names are taken in the lost+found group
+    fn move_to_lost_and_found_group(
+        &self,
+        storage: &dyn Storage,
+        subgroup_id: i32,
+    ) -> Result<RepairAction, Error> {
+        let group_id = self.get_lost_and_found_group(storage)?;
+        let group_unique = self
+            .nodes
+            .values()
+            .filter(|n| n.subgroup_id == subgroup_id)
+            .filter(|n| {
+                self.get_rules(n)
+                    .is_some_and(|r| r.uniqueness_scope == UniquenessScope::Group)
+            })
+            .collect::<Vec<&NodeElement>>();
+        let names = group_unique
+            .iter()
+            .map(|n| &n.name[..])
+            .collect::<Vec<&str>>();
+        let taken = storage.find_nodes_in_group(group_id, &names)?;
+        if group_unique.iter().any(|node| {
+            taken
+                .iter()
+                .any(|other| other.type_id == node.type_id && other.name == node.name)
+        }) {
+            return Err(Error::RollbackTransaction);
+        }
+        let mut subgroup = self.subgroups[&subgroup_id].clone();
+        subgroup.group_id = group_id;
+        storage.update_subgroup(&subgroup)?;
+        Ok(RepairAction::MovedToLostAndFoundGroup { group_id })
+    }
+
+    // Created by the first repair that needs it
+    fn get_lost_and_found_group(&self, storage: &dyn Storage) -> Result<i32, Error> {
+        let existing = storage
+            .load_groups()?
+            .into_iter()
+            .find(|g| g.name == LOST_AND_FOUND_NAME);
+        match existing {
+            Some(group) => Ok(group.id),
+            None => Ok(storage.create_group(LOST_AND_FOUND_NAME)?.id),
+        }
+    }
+
+    // The nodes of one missing subgroup are kept together, so that the links between them stay
+    fn get_lost_and_found_subgroup(
+        &self,
+        storage: &dyn Storage,
+        missing_subgroup_id: i32,
+    ) -> Result<i32, Error> {
+        let group_id = self.get_lost_and_found_group(storage)?;
+        let name = format!("{} ({})", LOST_AND_FOUND_NAME, missing_subgroup_id);
+        let existing = storage
+            .load_subgroups(Some(group_id))?
+            .into_iter()
+            .find(|s| s.name == name);
+        match existing {
+            Some(subgroup) => Ok(subgroup.id),
+            None => Ok(storage.create_subgroup(group_id, &name)?.id),
+        }
+    }
+
+    // None without the regular node type to create it with
+    fn get_lost_and_found(
+        &self,
+        storage: &dyn Storage,
+        subgroup: &SubGroupElement,
+    ) -> Result<Option<i32>, Error> {
+        // Regular names are unique in the group, so each subgroup has its own
+        let name = format!("{} ({})", LOST_AND_FOUND_NAME, subgroup.name);
+        let type_id = match self.node_types.get_type_id_by_value("regular") {
+            Some(type_id) => type_id,
+            None => return Ok(None),
+        };
+        // Created by the earlier repairs of the pass
+        let existing = storage
+            .load_nodes(Some(subgroup.id))?
+            .into_iter()
+            .find(|n| n.type_id == type_id && n.name == name && n.linked_to_id.is_none());
+        if let Some(node) = existing {
+            return Ok(Some(node.id));
+        }
+        let node = storage.create_node(&NewNode {
+            name: &name,
+            description: None,
+            type_id,
+            linked_to_id: None,
+            subgroup_id: subgroup.id,
+        })?;
+        Ok(Some(node.id))
+    }
+
+    // The nodes of the problem with their children, which the repair can move or delete
+    fn get_touched_node_ids(&self, problem: &IntegrityProblem) -> Vec<i32> {
+        let node_ids = problem.get_node_ids();
+        let children = self
+            .nodes
+            .values()
+            .filter(|n| n.linked_to_id.is_some_and(|id| node_ids.contains(&id)))
+            .map(|n| n.id)
+            .collect::<Vec<i32>>();
+        node_ids.into_iter().chain(children).collect()
+    }
+}
+
+/// Scans all groups, subgroups and nodes of the database
+pub fn check_database(storage: &dyn Storage) -> Result<Vec<IntegrityProblem>, Error> {
+    Ok(DatabaseSnapshot::load(storage)?.find_problems())
+}
+
+/// Fixes the problems that can be fixed safely, the remaining ones are returned as not repaired
+pub fn repair_database(storage: &dyn Storage) -> Result<Vec<Repair>, Error> {
+    let mut repairs = vec![];
+    for _ in 0..MAX_REPAIR_PASSES {
+        let snapshot = DatabaseSnapshot::load(storage)?;
+        let mut repaired_any = false;
+        // The snapshot is stale for the nodes repaired earlier in the pass, their other problems
+        // are left to the next pass
+        let mut touched = HashSet::new();
+        for problem in snapshot.find_problems() {
+            if problem.get_node_ids().iter().any(|id| touched.contains(id)) {
+                continue;
+            }
+            let action = snapshot.repair(storage, &problem)?;
+            if action != RepairAction::NotRepaired {
+                repaired_any = true;
+                touched.extend(snapshot.get_touched_node_ids(&problem));
+                repairs.push(Repair { problem, action });
+            }
+        }
+        if !repaired_any {
+            break;
+        }
+    }
+    repairs.extend(check_database(storage)?.into_iter().map(|problem| Repair {
+        problem,
+        action: RepairAction::NotRepaired,
+    }));
+    Ok(repairs)
+}
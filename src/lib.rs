@@ -13,16 +13,27 @@ use dotenv::dotenv;
 //pub mod groups_representation;
 //pub mod nodes_representation;
 pub mod abstracts;
+pub mod backup; // Use this to snapshot the notebooks and restore them
 pub mod database_setup; // Use this to setup the database
+pub mod errors;
+pub mod events; // Use this to follow the changes of the model in the views
 pub mod groups_mod;
+pub mod integrity_check; // Use this to find and repair the broken trees
+#[allow(non_local_definitions)] // diesel 1.4 derives expand to non-local impls
 pub mod models;
+pub mod notebooks; // Use this to open several notebooks and remember the recent ones
+#[allow(non_local_definitions)]
 pub mod schema;
+pub mod storage; // The queries of the models, SQLite or in-memory
+pub mod workspace; // Use this to open the notebook and navigate it
 
 //use nodes_representation::NodesRepresentation;
 //
 //use self::models::*;
 //use self::schema::*;
 
+/// The connection to the database of DATABASE_URL, the notebooks of other paths are opened with
+/// `notebooks::Notebooks` or `workspace::Workspace::open`
 pub fn establish_connection() -> SqliteConnection {
     dotenv().ok();
 
@@ -30,3 +41,12 @@ pub fn establish_connection() -> SqliteConnection {
         std::env::var("DATABASE_URL").expect("DATABASE_URL must be set - the path to the db!");
     SqliteConnection::establish(&url).expect("Could not connect to the DB")
 }
+
+#[cfg(feature = "postgres")]
+pub fn establish_postgres_connection() -> diesel::pg::PgConnection {
+    dotenv().ok();
+
+    let url = std::env::var("DATABASE_URL")
+        .expect("DATABASE_URL must be set - the url of the PostgreSQL database!");
+    diesel::pg::PgConnection::establish(&url).expect("Could not connect to the DB")
+}
@@ -1,3 +1,4 @@
+#![allow(clippy::four_forward_slashes)] // Keeping the old CLI around for reference
 //use regex::RegexSet;
 //use relanotes_rs::nodes_representation::NodesRepresentation;
 //use relanotes_rs::*;
@@ -207,7 +208,6 @@
 ////    }
 //}
 
-use relanotes_rs;
 fn main() {
     //    let conn = relanotes_rs::establish_connection();
 }
@@ -8,6 +8,12 @@ pub struct NodeTypeElement {
     pub id: i32,
     pub name: String,
     pub value: String,
+    pub name_policy: String,
+    pub allows_description: bool,
+    pub allows_root: bool,
+    pub parent_types: String,
+    pub child_types: String,
+    pub uniqueness_scope: String,
 }
 
 #[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize)]
@@ -38,4 +44,17 @@ pub struct NodeElement {
     pub name: String,
     pub description: Option<String>,
     pub subgroup_id: i32,
+    // Bumped by the database with every write
+    pub version: i32,
+}
+
+/// The row written by any process, logged by the triggers
+#[derive(Queryable, Identifiable, Debug, Clone, Serialize, Deserialize)]
+#[table_name = "changes"]
+pub struct ChangeElement {
+    pub id: i32,
+    pub table_name: String,
+    pub row_id: i32,
+    // The group of the subgroup or the subgroup of the node
+    pub parent_id: Option<i32>,
 }
@@ -0,0 +1,116 @@
+use crate::errors::RelanotesError;
+use crate::workspace::Workspace;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How many notebooks are kept in the registry
+pub const MAX_RECENT_NOTEBOOKS: usize = 10;
+
+// The first bytes of every SQLite database file
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// The notebook files in the directory, sorted by path. The notebooks are recognized by the
+/// header of the database, whatever their extension is.
+pub fn list_notebooks(directory: impl AsRef<Path>) -> Result<Vec<PathBuf>, RelanotesError> {
+    let mut notebooks = vec![];
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.is_file() && is_notebook(&path)? {
+            notebooks.push(path);
+        }
+    }
+    notebooks.sort();
+    Ok(notebooks)
+}
+
+pub(crate) fn is_notebook(path: &Path) -> Result<bool, RelanotesError> {
+    let mut header = [0; 16];
+    match fs::File::open(path)?.read_exact(&mut header) {
+        Ok(()) => Ok(&header == SQLITE_HEADER),
+        Err(ref e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The notebook in the registry, with the time when it was opened the last time
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecentNotebook {
+    pub path: PathBuf,
+    // Seconds since the unix epoch
+    pub opened_at: u64,
+}
+
+/// Opens and creates the notebooks by path and remembers the recently used ones in the registry
+/// file, the latest one first. Every opened notebook is a separate workspace, so several of them
+/// can be used side by side.
+pub struct Notebooks {
+    registry_path: PathBuf,
+    recent: Vec<RecentNotebook>,
+}
+
+impl Notebooks {
+    /// Reads the registry file, it's created with the first opened notebook if it's missing
+    pub fn load(registry_path: impl AsRef<Path>) -> Result<Self, RelanotesError> {
+        let registry_path = registry_path.as_ref().to_path_buf();
+        let recent = match fs::read(&registry_path) {
+            Ok(content) => serde_json::from_slice(&content).map_err(std::io::Error::from)?,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Notebooks {
+            registry_path,
+            recent,
+        })
+    }
+
+    pub fn recent(&self) -> &[RecentNotebook] {
+        &self.recent
+    }
+
+    pub fn open(&mut self, path: impl AsRef<Path>) -> Result<Workspace, RelanotesError> {
+        let workspace = Workspace::open(&path)?;
+        self.remember(path.as_ref())?;
+        Ok(workspace)
+    }
+
+    pub fn create(&mut self, path: impl AsRef<Path>) -> Result<Workspace, RelanotesError> {
+        let workspace = Workspace::create(&path)?;
+        self.remember(path.as_ref())?;
+        Ok(workspace)
+    }
+
+    /// Removes the notebook from the registry, the file is kept
+    pub fn forget(&mut self, path: impl AsRef<Path>) -> Result<(), RelanotesError> {
+        let path = canonical(path.as_ref());
+        self.recent.retain(|notebook| notebook.path != path);
+        self.save()
+    }
+
+    fn remember(&mut self, path: &Path) -> Result<(), RelanotesError> {
+        // The same file opened with other paths is one notebook
+        let path = canonical(path);
+        self.recent.retain(|notebook| notebook.path != path);
+        let opened_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.recent.insert(0, RecentNotebook { path, opened_at });
+        self.recent.truncate(MAX_RECENT_NOTEBOOKS);
+        self.save()
+    }
+
+    // Written to a temporary file first, so that the registry is not left half-written
+    fn save(&self) -> Result<(), RelanotesError> {
+        let content = serde_json::to_vec_pretty(&self.recent).map_err(std::io::Error::from)?;
+        let temporary_path = self.registry_path.with_extension("tmp");
+        fs::write(&temporary_path, content)?;
+        fs::rename(&temporary_path, &self.registry_path)?;
+        Ok(())
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
@@ -1,3 +1,12 @@
+table! {
+    changes (id) {
+        id -> Integer,
+        table_name -> Text,
+        row_id -> Integer,
+        parent_id -> Nullable<Integer>,
+    }
+}
+
 table! {
     groups (id) {
         id -> Integer,
@@ -10,6 +19,12 @@ table! {
         id -> Integer,
         name -> Text,
         value -> Text,
+        name_policy -> Text,
+        allows_description -> Bool,
+        allows_root -> Bool,
+        parent_types -> Text,
+        child_types -> Text,
+        uniqueness_scope -> Text,
     }
 }
 
@@ -21,6 +36,19 @@ table! {
         name -> Text,
         description -> Nullable<Text>,
         subgroup_id -> Integer,
+        version -> Integer,
+    }
+}
+
+table! {
+    pruned_changes (up_to) {
+        up_to -> Integer,
+    }
+}
+
+table! {
+    seed_versions (version) {
+        version -> Integer,
     }
 }
 
@@ -36,4 +64,12 @@ joinable!(nodes -> node_types (type_id));
 joinable!(nodes -> subgroups (subgroup_id));
 joinable!(subgroups -> groups (group_id));
 
-allow_tables_to_appear_in_same_query!(groups, node_types, nodes, subgroups,);
+allow_tables_to_appear_in_same_query!(
+    changes,
+    groups,
+    node_types,
+    nodes,
+    pruned_changes,
+    seed_versions,
+    subgroups,
+);
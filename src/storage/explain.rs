@@ -0,0 +1,56 @@
+use diesel::backend::Backend;
+use diesel::query_builder::{AstPass, Query, QueryBuilder, QueryFragment, QueryId};
+use diesel::query_dsl::RunQueryDsl;
+use diesel::result::QueryResult;
+use std::marker::PhantomData;
+
+/// The query with the explain prefix of the backend, the values of the query are bound like
+/// when it's run. The rows of the plan are loaded as `ST`.
+pub(super) struct Explain<Q, ST> {
+    prefix: &'static str,
+    query: Q,
+    rows: PhantomData<ST>,
+}
+
+impl<Q, ST> Explain<Q, ST> {
+    pub(super) fn new(prefix: &'static str, query: Q) -> Self {
+        Explain {
+            prefix,
+            query,
+            rows: PhantomData,
+        }
+    }
+}
+
+impl<Q, ST> Query for Explain<Q, ST> {
+    type SqlType = ST;
+}
+
+// The prefix is not a part of the type, so the statement is not cached
+impl<Q, ST> QueryId for Explain<Q, ST> {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<Q, ST, DB> QueryFragment<DB> for Explain<Q, ST>
+where
+    DB: Backend,
+    Q: QueryFragment<DB>,
+{
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        out.push_sql(self.prefix);
+        self.query.walk_ast(out.reborrow())
+    }
+}
+
+impl<Q, ST, Conn> RunQueryDsl<Conn> for Explain<Q, ST> {}
+
+/// The SQL of the query, with the placeholders of the backend instead of the values
+pub(super) fn sql<DB, Q>(query: &Q, mut builder: DB::QueryBuilder) -> QueryResult<String>
+where
+    DB: Backend,
+    Q: QueryFragment<DB>,
+{
+    query.to_sql(&mut builder)?;
+    Ok(builder.finish())
+}
@@ -0,0 +1,637 @@
+use super::{NewNode, QueryPlan, Storage};
+use crate::database_setup::add_seeds;
+use crate::models::{ChangeElement, GroupElement, NodeElement, NodeTypeElement, SubGroupElement};
+use diesel::result::{DatabaseErrorKind, Error};
+use parking_lot::ReentrantMutex;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+
+// The columns changed by the write, the checks of the database triggers depend on them
+#[derive(Clone, Copy, PartialEq)]
+enum NodeWrite {
+    Insert,
+    Content,
+    Link,
+    SubGroup,
+}
+
+const GROUPS: usize = 0;
+const SUBGROUPS: usize = 1;
+const NODE_TYPES: usize = 2;
+const NODES: usize = 3;
+const CHANGES: usize = 4;
+
+#[derive(Clone, Default)]
+struct Tables {
+    groups: BTreeMap<i32, GroupElement>,
+    subgroups: BTreeMap<i32, SubGroupElement>,
+    node_types: BTreeMap<i32, NodeTypeElement>,
+    nodes: BTreeMap<i32, NodeElement>,
+    seed_versions: BTreeSet<i32>,
+    // Logged like by the triggers, so that the models can be refreshed the same way
+    changes: Vec<ChangeElement>,
+    // The latest pruned change
+    pruned_change: i32,
+    // The ids are never reused, like with the autoincrement of SQLite
+    last_ids: [i32; 5],
+}
+
+impl Tables {
+    fn next_id(&mut self, table: usize) -> i32 {
+        self.last_ids[table] += 1;
+        self.last_ids[table]
+    }
+
+    fn log_change(&mut self, table_name: &str, row_id: i32, parent_id: Option<i32>) {
+        let id = self.next_id(CHANGES);
+        self.changes.push(ChangeElement {
+            id,
+            table_name: table_name.to_string(),
+            row_id,
+            parent_id,
+        });
+    }
+
+    // The old parent, and the new one if the row was moved
+    fn log_update(&mut self, table_name: &str, row_id: i32, old_parent: i32, new_parent: i32) {
+        self.log_change(table_name, row_id, Some(old_parent));
+        if new_parent != old_parent {
+            self.log_change(table_name, row_id, Some(new_parent));
+        }
+    }
+
+    fn type_value(&self, type_id: i32) -> Option<&str> {
+        self.node_types.get(&type_id).map(|t| &t.value[..])
+    }
+
+    fn group_of(&self, subgroup_id: i32) -> Option<i32> {
+        self.subgroups.get(&subgroup_id).map(|s| s.group_id)
+    }
+
+    fn is_divisor(&self, node: &NodeElement) -> bool {
+        self.type_value(node.type_id) == Some("divisor")
+    }
+
+    // Divisors are transparent, so the namespace belongs to the nearest owner which is not one
+    fn namespace_owner(&self, linked_to_id: Option<i32>) -> Option<i32> {
+        let mut owner_id = linked_to_id;
+        for _ in 0..self.nodes.len() {
+            match owner_id.and_then(|id| self.nodes.get(&id)) {
+                Some(owner) if self.is_divisor(owner) => owner_id = owner.linked_to_id,
+                _ => break,
+            }
+        }
+        owner_id
+    }
+
+    // The children of the owner (the roots of the subgroup without one) and of the divisors
+    // among them
+    fn namespace(&self, owner_id: Option<i32>, subgroup_id: i32) -> Vec<&NodeElement> {
+        let mut members = self
+            .nodes
+            .values()
+            .filter(|n| {
+                n.linked_to_id == owner_id && (owner_id.is_some() || n.subgroup_id == subgroup_id)
+            })
+            .collect::<Vec<&NodeElement>>();
+        let mut position = 0;
+        while let Some(member) = members.get(position) {
+            if self.is_divisor(member) {
+                let divisor_id = member.id;
+                let children = self
+                    .nodes
+                    .values()
+                    .filter(|n| n.linked_to_id == Some(divisor_id))
+                    .filter(|n| !members.iter().any(|m| m.id == n.id))
+                    .collect::<Vec<&NodeElement>>();
+                members.extend(children);
+            }
+            position += 1;
+        }
+        members
+    }
+
+    // The same rules as the triggers of the nodes table, checked before the row is written
+    fn check_node(&self, node: &NodeElement, write: NodeWrite) -> Result<(), Error> {
+        let others = || self.nodes.values().filter(move |n| n.id != node.id);
+        let type_value = self.type_value(node.type_id);
+        if type_value == Some("regular") && write != NodeWrite::Link {
+            let group_id = self.group_of(node.subgroup_id);
+            if others().any(|n| {
+                n.type_id == node.type_id
+                    && n.name == node.name
+                    && group_id.is_some()
+                    && self.group_of(n.subgroup_id) == group_id
+            }) {
+                return Err(rejected("DuplicateRegularNode"));
+            }
+        }
+        if let Some(value @ "sticky_notes") | Some(value @ "inherited") = type_value {
+            if write != NodeWrite::SubGroup
+                && self
+                    .namespace(self.namespace_owner(node.linked_to_id), node.subgroup_id)
+                    .iter()
+                    .any(|n| n.id != node.id && n.type_id == node.type_id && n.name == node.name)
+            {
+                return Err(rejected(if value == "sticky_notes" {
+                    "DuplicateStickyNote"
+                } else {
+                    "DuplicateInheritedNode"
+                }));
+            }
+        }
+        // The moved divisor takes its namespace children into the namespace of its new owner
+        if type_value == Some("divisor") && write == NodeWrite::Link {
+            let moved = self.namespace(Some(node.id), node.subgroup_id);
+            let namespace = self
+                .namespace(self.namespace_owner(node.linked_to_id), node.subgroup_id)
+                .into_iter()
+                .filter(|n| n.id != node.id && !moved.iter().any(|m| m.id == n.id))
+                .collect::<Vec<&NodeElement>>();
+            let duplicate = moved.iter().find(|m| {
+                matches!(
+                    self.type_value(m.type_id),
+                    Some("sticky_notes") | Some("inherited")
+                ) && namespace
+                    .iter()
+                    .any(|n| n.type_id == m.type_id && n.name == m.name)
+            });
+            if let Some(duplicate) = duplicate {
+                return Err(rejected(
+                    if self.type_value(duplicate.type_id) == Some("sticky_notes") {
+                        "DuplicateStickyNote"
+                    } else {
+                        "DuplicateInheritedNode"
+                    },
+                ));
+            }
+        }
+        if write != NodeWrite::Content {
+            let source_subgroup_id = node
+                .linked_to_id
+                .and_then(|id| self.nodes.get(&id))
+                .map(|source| source.subgroup_id);
+            if type_value == Some("symlinks") && source_subgroup_id == Some(node.subgroup_id) {
+                return Err(rejected("SymLinkToSameSubgroup"));
+            }
+            // The source can't be moved into the subgroup of its symlinks either
+            if write != NodeWrite::Insert
+                && others().any(|n| {
+                    self.type_value(n.type_id) == Some("symlinks")
+                        && n.linked_to_id == Some(node.id)
+                        && n.subgroup_id == node.subgroup_id
+                })
+            {
+                return Err(rejected("SymLinkToSameSubgroup"));
+            }
+        }
+        let link_write = write == NodeWrite::Insert || write == NodeWrite::Link;
+        if node.linked_to_id == Some(node.id) && link_write {
+            return Err(rejected("LinkedToItself"));
+        }
+        if write == NodeWrite::Link {
+            let mut ancestor_id = node.linked_to_id;
+            for _ in 0..self.nodes.len() {
+                match ancestor_id {
+                    Some(id) if id == node.id => return Err(rejected("CyclicLink")),
+                    Some(id) => ancestor_id = self.nodes.get(&id).and_then(|n| n.linked_to_id),
+                    None => break,
+                }
+            }
+        }
+        // The foreign keys are checked after the triggers, like by the database
+        let missing_link = link_write
+            && node
+                .linked_to_id
+                .is_some_and(|id| !self.nodes.contains_key(&id));
+        let missing_subgroup = (write == NodeWrite::Insert || write == NodeWrite::SubGroup)
+            && !self.subgroups.contains_key(&node.subgroup_id);
+        let missing_type = write == NodeWrite::Insert && type_value.is_none();
+        if missing_link || missing_subgroup || missing_type {
+            return Err(foreign_key_violation());
+        }
+        Ok(())
+    }
+
+    // Deleted together, the nodes that are kept can't be linked to them
+    fn delete_nodes(&mut self, node_ids: &[i32]) -> Result<(), Error> {
+        if self.nodes.values().any(|n| {
+            !node_ids.contains(&n.id) && n.linked_to_id.is_some_and(|id| node_ids.contains(&id))
+        }) {
+            return Err(foreign_key_violation());
+        }
+        for node_id in node_ids {
+            if let Some(node) = self.nodes.remove(node_id) {
+                self.log_change("nodes", node.id, Some(node.subgroup_id));
+            }
+        }
+        Ok(())
+    }
+
+    // The nodes of the subgroups are deleted with them, like by the cascades of the database
+    fn delete_subgroups(&mut self, subgroup_ids: &[i32]) -> Result<(), Error> {
+        let node_ids = self
+            .nodes
+            .values()
+            .filter(|n| subgroup_ids.contains(&n.subgroup_id))
+            .map(|n| n.id)
+            .collect::<Vec<i32>>();
+        self.delete_nodes(&node_ids)?;
+        for subgroup_id in subgroup_ids {
+            if let Some(subgroup) = self.subgroups.remove(subgroup_id) {
+                self.log_change("subgroups", subgroup.id, Some(subgroup.group_id));
+            }
+        }
+        Ok(())
+    }
+
+    // Row by row like the triggers, the written rows are restored if one of them is rejected
+    fn update_nodes(
+        &mut self,
+        node_ids: &[i32],
+        write: NodeWrite,
+        update: impl Fn(&mut NodeElement),
+    ) -> Result<(), Error> {
+        let mut written: Vec<NodeElement> = vec![];
+        let logged = self.changes.len();
+        for node_id in node_ids {
+            let mut node = match self.nodes.get(node_id) {
+                Some(node) => node.clone(),
+                None => continue,
+            };
+            update(&mut node);
+            node.version += 1;
+            if let Err(e) = self.check_node(&node, write) {
+                for old in written {
+                    self.nodes.insert(old.id, old);
+                }
+                self.changes.truncate(logged);
+                return Err(e);
+            }
+            self.log_update(
+                "nodes",
+                node.id,
+                self.nodes[node_id].subgroup_id,
+                node.subgroup_id,
+            );
+            written.push(self.nodes.insert(node.id, node).unwrap());
+        }
+        Ok(())
+    }
+}
+
+fn rejected(message: &str) -> Error {
+    Error::DatabaseError(DatabaseErrorKind::__Unknown, Box::new(message.to_string()))
+}
+
+fn foreign_key_violation() -> Error {
+    Error::DatabaseError(
+        DatabaseErrorKind::ForeignKeyViolation,
+        Box::new("FOREIGN KEY constraint failed".to_string()),
+    )
+}
+
+fn unique_violation(columns: &str) -> Error {
+    Error::DatabaseError(
+        DatabaseErrorKind::UniqueViolation,
+        Box::new(format!("UNIQUE constraint failed: {}", columns)),
+    )
+}
+
+/// The storage that keeps everything in memory, for the tests and for embedding. The seed data
+/// is added on creation and the writes are checked like by the database triggers.
+pub struct MemoryStorage {
+    // Locked by the thread running a transaction until it's finished, like the connections
+    tables: ReentrantMutex<RefCell<Tables>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        let storage = MemoryStorage {
+            tables: ReentrantMutex::new(RefCell::new(Tables::default())),
+        };
+        add_seeds(&storage).expect("Seed data is valid");
+        storage
+    }
+
+    // The tables are only borrowed for one query, so the nested calls of a transaction can lock
+    // them again
+    fn with_tables<T>(&self, f: impl FnOnce(&mut Tables) -> T) -> T {
+        let guard = self.tables.lock();
+        let mut tables = guard.borrow_mut();
+        f(&mut tables)
+    }
+}
+
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        MemoryStorage::new()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn run_in_transaction(&self, f: &mut dyn FnMut() -> Result<(), Error>) -> Result<(), Error> {
+        let guard = self.tables.lock();
+        let snapshot = guard.borrow().clone();
+        f().inspect_err(|_| *guard.borrow_mut() = snapshot)
+    }
+
+    fn load_groups(&self) -> Result<Vec<GroupElement>, Error> {
+        Ok(self.with_tables(|tables| tables.groups.values().cloned().collect()))
+    }
+
+    fn get_group(&self, group_id: i32) -> Result<Option<GroupElement>, Error> {
+        Ok(self.with_tables(|tables| tables.groups.get(&group_id).cloned()))
+    }
+
+    fn create_group(&self, name: &str) -> Result<GroupElement, Error> {
+        self.with_tables(|tables| {
+            if tables.groups.values().any(|g| g.name == name) {
+                return Err(unique_violation("groups.name"));
+            }
+            let group = GroupElement {
+                id: tables.next_id(GROUPS),
+                name: name.to_string(),
+            };
+            tables.groups.insert(group.id, group.clone());
+            tables.log_change("groups", group.id, None);
+            Ok(group)
+        })
+    }
+
+    fn update_group(&self, group: &GroupElement) -> Result<(), Error> {
+        self.with_tables(|tables| {
+            if tables
+                .groups
+                .values()
+                .any(|g| g.id != group.id && g.name == group.name)
+            {
+                return Err(unique_violation("groups.name"));
+            }
+            if let Some(existing) = tables.groups.get_mut(&group.id) {
+                existing.name = group.name.clone();
+                tables.log_change("groups", group.id, None);
+            }
+            Ok(())
+        })
+    }
+
+    fn delete_group(&self, group_id: i32) -> Result<(), Error> {
+        self.with_tables(|tables| {
+            let subgroup_ids = tables
+                .subgroups
+                .values()
+                .filter(|s| s.group_id == group_id)
+                .map(|s| s.id)
+                .collect::<Vec<i32>>();
+            tables.delete_subgroups(&subgroup_ids)?;
+            if tables.groups.remove(&group_id).is_some() {
+                tables.log_change("groups", group_id, None);
+            }
+            Ok(())
+        })
+    }
+
+    fn load_subgroups(&self, group_id: Option<i32>) -> Result<Vec<SubGroupElement>, Error> {
+        Ok(self.with_tables(|tables| {
+            tables
+                .subgroups
+                .values()
+                .filter(|s| group_id.is_none_or(|group_id| s.group_id == group_id))
+                .cloned()
+                .collect()
+        }))
+    }
+
+    fn get_subgroup(&self, subgroup_id: i32) -> Result<Option<SubGroupElement>, Error> {
+        Ok(self.with_tables(|tables| tables.subgroups.get(&subgroup_id).cloned()))
+    }
+
+    fn create_subgroup(&self, group_id: i32, name: &str) -> Result<SubGroupElement, Error> {
+        self.with_tables(|tables| {
+            if tables
+                .subgroups
+                .values()
+                .any(|s| s.group_id == group_id && s.name == name)
+            {
+                return Err(unique_violation("subgroups.group_id, subgroups.name"));
+            }
+            let subgroup = SubGroupElement {
+                id: tables.next_id(SUBGROUPS),
+                group_id,
+                name: name.to_string(),
+            };
+            tables.subgroups.insert(subgroup.id, subgroup.clone());
+            tables.log_change("subgroups", subgroup.id, Some(group_id));
+            Ok(subgroup)
+        })
+    }
+
+    fn update_subgroup(&self, subgroup: &SubGroupElement) -> Result<(), Error> {
+        self.with_tables(|tables| {
+            if tables.subgroups.values().any(|s| {
+                s.id != subgroup.id && s.group_id == subgroup.group_id && s.name == subgroup.name
+            }) {
+                return Err(unique_violation("subgroups.group_id, subgroups.name"));
+            }
+            if let Some(existing) = tables.subgroups.get_mut(&subgroup.id) {
+                let old_group_id = existing.group_id;
+                *existing = subgroup.clone();
+                tables.log_update("subgroups", subgroup.id, old_group_id, subgroup.group_id);
+            }
+            Ok(())
+        })
+    }
+
+    fn delete_subgroup(&self, subgroup_id: i32) -> Result<(), Error> {
+        self.with_tables(|tables| tables.delete_subgroups(&[subgroup_id]))
+    }
+
+    fn load_node_types(&self) -> Result<Vec<NodeTypeElement>, Error> {
+        Ok(self.with_tables(|tables| tables.node_types.values().cloned().collect()))
+    }
+
+    fn save_node_type(&self, node_type: &NodeTypeElement) -> Result<i32, Error> {
+        self.with_tables(|tables| {
+            let id = match tables
+                .node_types
+                .values()
+                .find(|t| t.value == node_type.value)
+            {
+                Some(existing) => existing.id,
+                None => tables.next_id(NODE_TYPES),
+            };
+            tables.node_types.insert(
+                id,
+                NodeTypeElement {
+                    id,
+                    ..node_type.clone()
+                },
+            );
+            Ok(id)
+        })
+    }
+
+    fn seed_version(&self) -> Result<i32, Error> {
+        Ok(self.with_tables(|tables| tables.seed_versions.iter().last().copied().unwrap_or(0)))
+    }
+
+    fn add_seed_version(&self, version: i32) -> Result<(), Error> {
+        self.with_tables(|tables| {
+            if tables.seed_versions.insert(version) {
+                Ok(())
+            } else {
+                Err(unique_violation("seed_versions.version"))
+            }
+        })
+    }
+
+    fn load_nodes(&self, subgroup_id: Option<i32>) -> Result<Vec<NodeElement>, Error> {
+        Ok(self.with_tables(|tables| {
+            tables
+                .nodes
+                .values()
+                .filter(|n| subgroup_id.is_none_or(|subgroup_id| n.subgroup_id == subgroup_id))
+                .cloned()
+                .collect()
+        }))
+    }
+
+    fn get_node(&self, node_id: i32) -> Result<Option<NodeElement>, Error> {
+        Ok(self.with_tables(|tables| tables.nodes.get(&node_id).cloned()))
+    }
+
+    fn get_nodes(&self, node_ids: &[i32]) -> Result<Vec<NodeElement>, Error> {
+        Ok(self.with_tables(|tables| {
+            node_ids
+                .iter()
+                .filter_map(|id| tables.nodes.get(id))
+                .cloned()
+                .collect()
+        }))
+    }
+
+    fn lock_nodes(&self, node_ids: &[i32]) -> Result<Vec<NodeElement>, Error> {
+        // The tables are locked by the transaction already
+        self.get_nodes(node_ids)
+    }
+
+    fn create_node(&self, node: &NewNode) -> Result<NodeElement, Error> {
+        self.with_tables(|tables| {
+            let node = NodeElement {
+                id: tables.last_ids[NODES] + 1,
+                linked_to_id: node.linked_to_id,
+                type_id: node.type_id,
+                name: node.name.to_string(),
+                description: node.description.map(String::from),
+                subgroup_id: node.subgroup_id,
+                version: 1,
+            };
+            tables.check_node(&node, NodeWrite::Insert)?;
+            tables.next_id(NODES);
+            tables.nodes.insert(node.id, node.clone());
+            tables.log_change("nodes", node.id, Some(node.subgroup_id));
+            Ok(node)
+        })
+    }
+
+    fn update_node_content(
+        &self,
+        node_id: i32,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<(), Error> {
+        self.with_tables(|tables| {
+            tables.update_nodes(&[node_id], NodeWrite::Content, |node| {
+                node.name = name.to_string();
+                node.description = description.map(String::from);
+            })
+        })
+    }
+
+    fn set_linked_to_id(&self, node_ids: &[i32], linked_to_id: Option<i32>) -> Result<(), Error> {
+        self.with_tables(|tables| {
+            tables.update_nodes(node_ids, NodeWrite::Link, |node| {
+                node.linked_to_id = linked_to_id
+            })
+        })
+    }
+
+    fn set_subgroup_id(&self, node_ids: &[i32], subgroup_id: i32) -> Result<(), Error> {
+        self.with_tables(|tables| {
+            tables.update_nodes(node_ids, NodeWrite::SubGroup, |node| {
+                node.subgroup_id = subgroup_id
+            })
+        })
+    }
+
+    fn delete_nodes(&self, node_ids: &[i32]) -> Result<(), Error> {
+        self.with_tables(|tables| tables.delete_nodes(node_ids))
+    }
+
+    fn find_nodes_in_group(
+        &self,
+        group_id: i32,
+        names: &[&str],
+    ) -> Result<Vec<NodeElement>, Error> {
+        Ok(self.with_tables(|tables| {
+            tables
+                .nodes
+                .values()
+                .filter(|n| {
+                    tables.group_of(n.subgroup_id) == Some(group_id) && names.contains(&&n.name[..])
+                })
+                .cloned()
+                .collect()
+        }))
+    }
+
+    fn find_linked_nodes(&self, node_ids: &[i32]) -> Result<Vec<NodeElement>, Error> {
+        Ok(self.with_tables(|tables| {
+            tables
+                .nodes
+                .values()
+                .filter(|n| n.linked_to_id.is_some_and(|id| node_ids.contains(&id)))
+                .cloned()
+                .collect()
+        }))
+    }
+
+    fn last_change(&self) -> Result<i32, Error> {
+        Ok(self.with_tables(|tables| {
+            tables
+                .changes
+                .last()
+                .map_or(tables.pruned_change, |change| change.id)
+        }))
+    }
+
+    fn load_changes(&self, after: i32) -> Result<Vec<ChangeElement>, Error> {
+        Ok(self.with_tables(|tables| {
+            tables
+                .changes
+                .iter()
+                .filter(|change| change.id > after)
+                .cloned()
+                .collect()
+        }))
+    }
+
+    fn prune_changes(&self, up_to: i32) -> Result<(), Error> {
+        self.with_tables(|tables| {
+            if up_to > tables.pruned_change {
+                tables.changes.retain(|change| change.id > up_to);
+                tables.pruned_change = up_to;
+            }
+        });
+        Ok(())
+    }
+
+    fn pruned_change(&self) -> Result<i32, Error> {
+        Ok(self.with_tables(|tables| tables.pruned_change))
+    }
+
+    fn explain_queries(&self) -> Result<Vec<QueryPlan>, Error> {
+        Ok(Vec::new())
+    }
+}
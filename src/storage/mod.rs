@@ -0,0 +1,137 @@
+use crate::models::{ChangeElement, GroupElement, NodeElement, NodeTypeElement, SubGroupElement};
+use diesel::result::Error;
+use std::sync::Arc;
+
+mod explain;
+mod memory;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod sqlite;
+
+pub use memory::MemoryStorage;
+#[cfg(feature = "postgres")]
+pub use postgres::PgStorage;
+pub(crate) use sqlite::set_foreign_keys;
+pub use sqlite::SqliteStorage;
+
+/// The values of the node to insert, the id is assigned by the storage
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewNode<'n> {
+    pub name: &'n str,
+    pub description: Option<&'n str>,
+    pub type_id: i32,
+    pub linked_to_id: Option<i32>,
+    pub subgroup_id: i32,
+}
+
+/// The plan chosen by the database for one of the loading or validation queries
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryPlan {
+    /// The storage method running the query
+    pub query: &'static str,
+    pub sql: String,
+    /// The lines of the plan, in the format of the backend
+    pub plan: Vec<String>,
+}
+
+/// The queries of the groups, subgroups and nodes, so that the models don't depend on the
+/// database. The errors are the diesel errors for every backend, the rejected writes are
+/// reported as `Error::DatabaseError`. The storages are shared between threads, the calls of
+/// other threads wait while a transaction is running.
+pub trait Storage: Send + Sync {
+    /// Runs the function in a transaction, which is rolled back if the function fails.
+    /// Transactions can be nested, the nested ones are savepoints.
+    fn run_in_transaction(&self, f: &mut dyn FnMut() -> Result<(), Error>) -> Result<(), Error>;
+
+    fn load_groups(&self) -> Result<Vec<GroupElement>, Error>;
+    fn get_group(&self, group_id: i32) -> Result<Option<GroupElement>, Error>;
+    fn create_group(&self, name: &str) -> Result<GroupElement, Error>;
+    fn update_group(&self, group: &GroupElement) -> Result<(), Error>;
+    fn delete_group(&self, group_id: i32) -> Result<(), Error>;
+
+    // All subgroups without the group id
+    fn load_subgroups(&self, group_id: Option<i32>) -> Result<Vec<SubGroupElement>, Error>;
+    fn get_subgroup(&self, subgroup_id: i32) -> Result<Option<SubGroupElement>, Error>;
+    fn create_subgroup(&self, group_id: i32, name: &str) -> Result<SubGroupElement, Error>;
+    fn update_subgroup(&self, subgroup: &SubGroupElement) -> Result<(), Error>;
+    fn delete_subgroup(&self, subgroup_id: i32) -> Result<(), Error>;
+
+    fn load_node_types(&self) -> Result<Vec<NodeTypeElement>, Error>;
+    /// Adds the node type or updates the existing one with the same value, the id of the element
+    /// is ignored
+    fn save_node_type(&self, node_type: &NodeTypeElement) -> Result<i32, Error>;
+
+    /// The latest version of the seed data added to the storage, 0 if none was added yet
+    fn seed_version(&self) -> Result<i32, Error>;
+    fn add_seed_version(&self, version: i32) -> Result<(), Error>;
+
+    // All nodes without the subgroup id
+    fn load_nodes(&self, subgroup_id: Option<i32>) -> Result<Vec<NodeElement>, Error>;
+    fn get_node(&self, node_id: i32) -> Result<Option<NodeElement>, Error>;
+    /// The existing nodes with the ids, in one query
+    fn get_nodes(&self, node_ids: &[i32]) -> Result<Vec<NodeElement>, Error>;
+    /// Like `get_nodes`, but the rows can't be written by the other transactions until the
+    /// current one is finished, so that their versions can be checked before the writes
+    fn lock_nodes(&self, node_ids: &[i32]) -> Result<Vec<NodeElement>, Error>;
+    fn create_node(&self, node: &NewNode) -> Result<NodeElement, Error>;
+    fn update_node_content(
+        &self,
+        node_id: i32,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<(), Error>;
+    fn set_linked_to_id(&self, node_ids: &[i32], linked_to_id: Option<i32>) -> Result<(), Error>;
+    fn set_subgroup_id(&self, node_ids: &[i32], subgroup_id: i32) -> Result<(), Error>;
+    fn delete_nodes(&self, node_ids: &[i32]) -> Result<(), Error>;
+    /// Nodes with one of the names in any subgroup of the group
+    fn find_nodes_in_group(&self, group_id: i32, names: &[&str])
+        -> Result<Vec<NodeElement>, Error>;
+    /// Nodes linked to one of the given nodes
+    fn find_linked_nodes(&self, node_ids: &[i32]) -> Result<Vec<NodeElement>, Error>;
+
+    /// The id of the latest logged change, 0 if nothing was written yet. The pruned changes
+    /// count too.
+    fn last_change(&self) -> Result<i32, Error>;
+    /// The changes logged after the given one, in the order they were made
+    fn load_changes(&self, after: i32) -> Result<Vec<ChangeElement>, Error>;
+    /// Removes the changes logged up to the given one, the models synced before it are loaded
+    /// again by their refresh
+    fn prune_changes(&self, up_to: i32) -> Result<(), Error>;
+    /// The latest change removed by `prune_changes`, 0 if nothing was pruned yet
+    fn pruned_change(&self) -> Result<i32, Error>;
+
+    /// The plans of the queries used to load the trees and to validate the changes, to check
+    /// that they use the indexes. Empty for the storages without a query planner.
+    fn explain_queries(&self) -> Result<Vec<QueryPlan>, Error>;
+}
+
+/// The storage owned by the models, one handle is cloned into every group, subgroup and tree
+pub type SharedStorage = Arc<dyn Storage>;
+
+impl<'s> dyn Storage + 's {
+    /// Runs the function in a transaction, which is rolled back if the function fails
+    pub fn transaction<T, E, F>(&self, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+        E: From<Error>,
+    {
+        let mut f = Some(f);
+        let mut result = None;
+        let outcome = self.run_in_transaction(&mut || {
+            let value = (f.take().unwrap())();
+            let failed = value.is_err();
+            result = Some(value);
+            if failed {
+                Err(Error::RollbackTransaction)
+            } else {
+                Ok(())
+            }
+        });
+        match (outcome, result) {
+            (_, Some(Err(e))) => Err(e),
+            (Err(e), _) => Err(e.into()),
+            (Ok(()), Some(Ok(value))) => Ok(value),
+            (Ok(()), None) => unreachable!("The function of the transaction was not called"),
+        }
+    }
+}
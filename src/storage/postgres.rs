@@ -0,0 +1,316 @@
+use super::explain::{self, Explain};
+use super::{NewNode, QueryPlan, Storage};
+use crate::models::{ChangeElement, GroupElement, NodeElement, NodeTypeElement, SubGroupElement};
+use crate::schema::{
+    changes, groups, node_types, nodes, pruned_changes, seed_versions, subgroups,
+};
+use diesel::pg::{Pg, PgConnection, PgQueryBuilder};
+use diesel::prelude::*;
+use diesel::query_builder::QueryFragment;
+use diesel::result::Error;
+use diesel::sql_types::Text;
+use parking_lot::{ReentrantMutex, ReentrantMutexGuard};
+
+/// The storage of the shared PostgreSQL database, the foreign keys are enforced here
+pub struct PgStorage {
+    // Locked by the thread running a transaction until it's finished
+    conn: ReentrantMutex<PgConnection>,
+}
+
+impl PgStorage {
+    pub fn new(conn: PgConnection) -> Self {
+        PgStorage {
+            conn: ReentrantMutex::new(conn),
+        }
+    }
+
+    // For the migrations and the queries that are not covered by the storage
+    pub fn connection(&self) -> ReentrantMutexGuard<'_, PgConnection> {
+        self.conn.lock()
+    }
+
+    // Every row of the plan is one line of the text
+    fn explain(
+        &self,
+        query: &'static str,
+        statement: impl QueryFragment<Pg>,
+    ) -> Result<QueryPlan, Error> {
+        let sql = explain::sql(&statement, PgQueryBuilder::new())?;
+        let plan =
+            Explain::<_, Text>::new("EXPLAIN ", statement).load::<String>(&*self.connection())?;
+        Ok(QueryPlan { query, sql, plan })
+    }
+}
+
+impl Storage for PgStorage {
+    fn run_in_transaction(&self, f: &mut dyn FnMut() -> Result<(), Error>) -> Result<(), Error> {
+        self.connection().transaction(f)
+    }
+
+    fn load_groups(&self) -> Result<Vec<GroupElement>, Error> {
+        groups::table.load::<GroupElement>(&*self.connection())
+    }
+
+    fn get_group(&self, group_id: i32) -> Result<Option<GroupElement>, Error> {
+        groups::table
+            .filter(groups::id.eq(group_id))
+            .first::<GroupElement>(&*self.connection())
+            .optional()
+    }
+
+    fn create_group(&self, name: &str) -> Result<GroupElement, Error> {
+        diesel::insert_into(groups::table)
+            .values(groups::name.eq(name))
+            .get_result::<GroupElement>(&*self.connection())
+    }
+
+    fn update_group(&self, group: &GroupElement) -> Result<(), Error> {
+        diesel::update(groups::table.filter(groups::id.eq(group.id)))
+            .set(groups::name.eq(&group.name))
+            .execute(&*self.connection())?;
+        Ok(())
+    }
+
+    fn delete_group(&self, group_id: i32) -> Result<(), Error> {
+        diesel::delete(groups::table.filter(groups::id.eq(group_id)))
+            .execute(&*self.connection())?;
+        Ok(())
+    }
+
+    fn load_subgroups(&self, group_id: Option<i32>) -> Result<Vec<SubGroupElement>, Error> {
+        let query = match group_id {
+            Some(group_id) => subgroups_in_group(group_id),
+            None => subgroups::table.into_boxed(),
+        };
+        query.load::<SubGroupElement>(&*self.connection())
+    }
+
+    fn get_subgroup(&self, subgroup_id: i32) -> Result<Option<SubGroupElement>, Error> {
+        subgroups::table
+            .filter(subgroups::id.eq(subgroup_id))
+            .first::<SubGroupElement>(&*self.connection())
+            .optional()
+    }
+
+    fn create_subgroup(&self, group_id: i32, name: &str) -> Result<SubGroupElement, Error> {
+        diesel::insert_into(subgroups::table)
+            .values((subgroups::group_id.eq(group_id), subgroups::name.eq(name)))
+            .get_result::<SubGroupElement>(&*self.connection())
+    }
+
+    fn update_subgroup(&self, subgroup: &SubGroupElement) -> Result<(), Error> {
+        diesel::update(subgroups::table.filter(subgroups::id.eq(subgroup.id)))
+            .set((
+                subgroups::group_id.eq(subgroup.group_id),
+                subgroups::name.eq(&subgroup.name),
+            ))
+            .execute(&*self.connection())?;
+        Ok(())
+    }
+
+    fn delete_subgroup(&self, subgroup_id: i32) -> Result<(), Error> {
+        diesel::delete(subgroups::table.filter(subgroups::id.eq(subgroup_id)))
+            .execute(&*self.connection())?;
+        Ok(())
+    }
+
+    fn load_node_types(&self) -> Result<Vec<NodeTypeElement>, Error> {
+        node_types::table.load::<NodeTypeElement>(&*self.connection())
+    }
+
+    fn save_node_type(&self, node_type: &NodeTypeElement) -> Result<i32, Error> {
+        let values = (
+            node_types::name.eq(&node_type.name),
+            node_types::value.eq(&node_type.value),
+            node_types::name_policy.eq(&node_type.name_policy),
+            node_types::allows_description.eq(node_type.allows_description),
+            node_types::allows_root.eq(node_type.allows_root),
+            node_types::parent_types.eq(&node_type.parent_types),
+            node_types::child_types.eq(&node_type.child_types),
+            node_types::uniqueness_scope.eq(&node_type.uniqueness_scope),
+        );
+        diesel::insert_into(node_types::table)
+            .values(values)
+            .on_conflict(node_types::value)
+            .do_update()
+            .set(values)
+            .returning(node_types::id)
+            .get_result::<i32>(&*self.connection())
+    }
+
+    fn seed_version(&self) -> Result<i32, Error> {
+        let version = seed_versions::table
+            .select(diesel::dsl::max(seed_versions::version))
+            .first::<Option<i32>>(&*self.connection())?;
+        Ok(version.unwrap_or(0))
+    }
+
+    fn add_seed_version(&self, version: i32) -> Result<(), Error> {
+        diesel::insert_into(seed_versions::table)
+            .values(seed_versions::version.eq(version))
+            .execute(&*self.connection())?;
+        Ok(())
+    }
+
+    fn load_nodes(&self, subgroup_id: Option<i32>) -> Result<Vec<NodeElement>, Error> {
+        let query = match subgroup_id {
+            Some(subgroup_id) => nodes_in_subgroup(subgroup_id),
+            None => nodes::table.into_boxed(),
+        };
+        query.load::<NodeElement>(&*self.connection())
+    }
+
+    fn get_node(&self, node_id: i32) -> Result<Option<NodeElement>, Error> {
+        nodes::table
+            .filter(nodes::id.eq(node_id))
+            .first::<NodeElement>(&*self.connection())
+            .optional()
+    }
+
+    fn get_nodes(&self, node_ids: &[i32]) -> Result<Vec<NodeElement>, Error> {
+        nodes_with_ids(node_ids).load::<NodeElement>(&*self.connection())
+    }
+
+    fn lock_nodes(&self, node_ids: &[i32]) -> Result<Vec<NodeElement>, Error> {
+        nodes::table
+            .filter(nodes::id.eq_any(node_ids))
+            .for_update()
+            .load::<NodeElement>(&*self.connection())
+    }
+
+    fn create_node(&self, node: &NewNode) -> Result<NodeElement, Error> {
+        diesel::insert_into(nodes::table)
+            .values((
+                nodes::name.eq(node.name),
+                nodes::description.eq(node.description),
+                nodes::type_id.eq(node.type_id),
+                nodes::linked_to_id.eq(node.linked_to_id),
+                nodes::subgroup_id.eq(node.subgroup_id),
+            ))
+            .get_result::<NodeElement>(&*self.connection())
+    }
+
+    fn update_node_content(
+        &self,
+        node_id: i32,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<(), Error> {
+        diesel::update(nodes::table.filter(nodes::id.eq(node_id)))
+            .set((nodes::name.eq(name), nodes::description.eq(description)))
+            .execute(&*self.connection())?;
+        Ok(())
+    }
+
+    fn set_linked_to_id(&self, node_ids: &[i32], linked_to_id: Option<i32>) -> Result<(), Error> {
+        diesel::update(nodes::table.filter(nodes::id.eq_any(node_ids)))
+            .set(nodes::linked_to_id.eq(linked_to_id))
+            .execute(&*self.connection())?;
+        Ok(())
+    }
+
+    fn set_subgroup_id(&self, node_ids: &[i32], subgroup_id: i32) -> Result<(), Error> {
+        diesel::update(nodes::table.filter(nodes::id.eq_any(node_ids)))
+            .set(nodes::subgroup_id.eq(subgroup_id))
+            .execute(&*self.connection())?;
+        Ok(())
+    }
+
+    fn delete_nodes(&self, node_ids: &[i32]) -> Result<(), Error> {
+        diesel::delete(nodes::table.filter(nodes::id.eq_any(node_ids)))
+            .execute(&*self.connection())?;
+        Ok(())
+    }
+
+    fn find_nodes_in_group(
+        &self,
+        group_id: i32,
+        names: &[&str],
+    ) -> Result<Vec<NodeElement>, Error> {
+        nodes_in_group(group_id, names).load::<NodeElement>(&*self.connection())
+    }
+
+    fn find_linked_nodes(&self, node_ids: &[i32]) -> Result<Vec<NodeElement>, Error> {
+        nodes_linked_to(node_ids).load::<NodeElement>(&*self.connection())
+    }
+
+    fn last_change(&self) -> Result<i32, Error> {
+        let id = changes::table
+            .select(diesel::dsl::max(changes::id))
+            .first::<Option<i32>>(&*self.connection())?;
+        Ok(id.unwrap_or(0).max(self.pruned_change()?))
+    }
+
+    fn load_changes(&self, after: i32) -> Result<Vec<ChangeElement>, Error> {
+        changes::table
+            .filter(changes::id.gt(after))
+            .order(changes::id)
+            .load::<ChangeElement>(&*self.connection())
+    }
+
+    fn prune_changes(&self, up_to: i32) -> Result<(), Error> {
+        let conn = &*self.connection();
+        conn.transaction(|| {
+            if up_to <= self.pruned_change()? {
+                return Ok(());
+            }
+            diesel::delete(changes::table.filter(changes::id.le(up_to))).execute(conn)?;
+            diesel::insert_into(pruned_changes::table)
+                .values(pruned_changes::up_to.eq(up_to))
+                .execute(conn)?;
+            Ok(())
+        })
+    }
+
+    fn pruned_change(&self) -> Result<i32, Error> {
+        let up_to = pruned_changes::table
+            .select(diesel::dsl::max(pruned_changes::up_to))
+            .first::<Option<i32>>(&*self.connection())?;
+        Ok(up_to.unwrap_or(0))
+    }
+
+    fn explain_queries(&self) -> Result<Vec<QueryPlan>, Error> {
+        // The plans don't depend on the values
+        let (id, ids, names) = (0, &[0][..], &[""][..]);
+        Ok(vec![
+            self.explain("load_subgroups", subgroups_in_group(id))?,
+            self.explain("load_nodes", nodes_in_subgroup(id))?,
+            self.explain("get_nodes", nodes_with_ids(ids))?,
+            self.explain("find_nodes_in_group", nodes_in_group(id, names))?,
+            self.explain("find_linked_nodes", nodes_linked_to(ids))?,
+        ])
+    }
+}
+
+// The loading and the validation queries, shared with their explained plans
+fn subgroups_in_group(group_id: i32) -> subgroups::BoxedQuery<'static, Pg> {
+    subgroups::table
+        .filter(subgroups::group_id.eq(group_id))
+        .into_boxed()
+}
+
+fn nodes_in_subgroup(subgroup_id: i32) -> nodes::BoxedQuery<'static, Pg> {
+    nodes::table
+        .filter(nodes::subgroup_id.eq(subgroup_id))
+        .into_boxed()
+}
+
+fn nodes_with_ids(node_ids: &[i32]) -> nodes::BoxedQuery<'_, Pg> {
+    nodes::table.filter(nodes::id.eq_any(node_ids)).into_boxed()
+}
+
+fn nodes_in_group<'a>(group_id: i32, names: &'a [&'a str]) -> nodes::BoxedQuery<'a, Pg> {
+    let subgroup_ids = subgroups::table
+        .filter(subgroups::group_id.eq(group_id))
+        .select(subgroups::id);
+    nodes::table
+        .filter(nodes::subgroup_id.eq_any(subgroup_ids))
+        .filter(nodes::name.eq_any(names))
+        .into_boxed()
+}
+
+fn nodes_linked_to(node_ids: &[i32]) -> nodes::BoxedQuery<'_, Pg> {
+    nodes::table
+        .filter(nodes::linked_to_id.eq_any(node_ids))
+        .into_boxed()
+}
@@ -0,0 +1,151 @@
+use crate::abstracts::Loadable;
+use crate::database_setup::setup_database;
+use crate::errors::RelanotesError;
+use crate::events::Event;
+use crate::groups_mod::subgroups_mod::nodes_mod::node_types::NodeTypeDefinition;
+use crate::groups_mod::subgroups_mod::nodes_mod::undo::next_transaction_id;
+use crate::groups_mod::subgroups_mod::nodes_mod::RefreshReport;
+use crate::groups_mod::{GroupAbstraction, Groups};
+use crate::storage::{MemoryStorage, SharedStorage, SqliteStorage};
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+
+/// The opened notebook with the whole model, the single entry point of the front-ends. The
+/// groups, subgroups and node trees are loaded on the first access.
+#[derive(Clone)]
+pub struct Workspace {
+    // None for the workspaces that are not opened from a file
+    path: Option<PathBuf>,
+    storage: SharedStorage,
+    groups: Groups,
+}
+
+impl Workspace {
+    /// Opens the existing notebook file, the pending migrations and seed data are applied
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, RelanotesError> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Err(RelanotesError::NotebookNotFound(path.to_path_buf()));
+        }
+        Workspace::connect(path)
+    }
+
+    /// Creates the notebook file, the path must not exist
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, RelanotesError> {
+        let path = path.as_ref();
+        if path.exists() {
+            return Err(RelanotesError::NotebookExists(path.to_path_buf()));
+        }
+        Workspace::connect(path)
+    }
+
+    fn connect(path: &Path) -> Result<Self, RelanotesError> {
+        let conn = SqliteConnection::establish(&path.to_string_lossy())
+            .map_err(RelanotesError::ConnectionError)?;
+        let storage = SqliteStorage::new(conn)?;
+        setup_database(&storage)?;
+        let mut workspace = Workspace::from_storage(Arc::new(storage));
+        workspace.path = Some(path.to_path_buf());
+        Ok(workspace)
+    }
+
+    /// The workspace that is not saved anywhere
+    pub fn in_memory() -> Self {
+        Workspace::from_storage(Arc::new(MemoryStorage::new()))
+    }
+
+    /// The storage has to be set up already, like with `setup_postgres_database`
+    pub fn from_storage(storage: SharedStorage) -> Self {
+        Workspace {
+            path: None,
+            groups: Groups::new(storage.clone()),
+            storage,
+        }
+    }
+
+    /// The file of the notebook, if it was opened from one
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn storage(&self) -> &SharedStorage {
+        &self.storage
+    }
+
+    /// The changes of the groups, subgroups and nodes from now on, for the views to follow
+    pub fn subscribe(&self) -> Receiver<Event> {
+        self.groups.events().subscribe()
+    }
+
+    pub fn groups(&mut self) -> Result<&mut Groups, RelanotesError> {
+        if !self.groups.loaded {
+            self.groups.load()?;
+        }
+        Ok(&mut self.groups)
+    }
+
+    pub fn group(&mut self, name: &str) -> Result<&mut GroupAbstraction, RelanotesError> {
+        self.groups()?
+            .groups_map
+            .values_mut()
+            .find(|g| g.group.name == name)
+            .ok_or_else(|| RelanotesError::GroupNameNotFound(name.to_string()))
+    }
+
+    pub fn create_group(&mut self, name: &str) -> Result<&mut GroupAbstraction, RelanotesError> {
+        let groups = self.groups()?;
+        let group_id = groups.create(name.to_string())?.group.id;
+        Ok(groups.groups_map.get_mut(&group_id).unwrap())
+    }
+
+    /// Applies the edits of the other processes on the same database to the loaded model. The
+    /// edits of the nodes that were changed meanwhile fail with the conflicts until then.
+    pub fn refresh(&mut self) -> Result<RefreshReport, RelanotesError> {
+        Ok(self.groups.refresh()?)
+    }
+
+    /// Removes the logged changes that the loaded model already applied, except for the latest
+    /// `retained` ones kept for the other processes refreshing from the same database. Their
+    /// models synced before the removed changes are loaded again by the refresh.
+    pub fn prune_changes(&mut self, retained: i32) -> Result<(), RelanotesError> {
+        let mut up_to = self.storage.last_change()? - retained;
+        if let Some(synced_change) = self.groups.synced_change() {
+            up_to = up_to.min(synced_change);
+        }
+        if up_to > 0 {
+            self.storage.prune_changes(up_to)?;
+        }
+        Ok(())
+    }
+
+    /// Saves the node type and makes it available in all trees
+    pub fn save_node_type(
+        &mut self,
+        definition: &NodeTypeDefinition,
+    ) -> Result<i32, RelanotesError> {
+        Ok(self.groups.save_node_type(definition)?)
+    }
+
+    /// Runs the mutations of several subgroups or groups in one transaction. If anything fails,
+    /// neither the database nor the model are changed, and no events are published. Only the
+    /// groups and subgroups are copied up front, the nodes are copied before their first change.
+    pub fn transaction<T, E, F>(&mut self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut Workspace) -> Result<T, E>,
+        E: From<diesel::result::Error>,
+    {
+        let transaction_id = next_transaction_id();
+        self.groups.begin_transaction(transaction_id);
+        let storage = self.storage.clone();
+        let events = self.groups.events().clone();
+        let result = events.transaction(|| storage.transaction(|| f(self)));
+        match result {
+            Ok(_) => self.groups.commit_transaction(transaction_id),
+            Err(_) => self.groups.rollback_transaction(transaction_id),
+        }
+        result
+    }
+}
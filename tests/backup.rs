@@ -0,0 +1,175 @@
+mod common;
+
+use common::TempDir;
+use diesel::prelude::*;
+use diesel::SqliteConnection;
+use relanotes_rs::backup::{restore, snapshot, Backups, RetentionPolicy};
+use relanotes_rs::errors::RelanotesError;
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::NodeType;
+use relanotes_rs::workspace::Workspace;
+use std::path::Path;
+use std::time::Duration;
+
+fn create_notebook(path: &Path) -> Workspace {
+    let mut workspace = Workspace::create(path).unwrap();
+    workspace
+        .create_group("Medicine")
+        .unwrap()
+        .create_subgroup("Anatomy")
+        .unwrap()
+        .tree()
+        .unwrap()
+        .create_node("Heart", None, None, NodeType::Regular)
+        .unwrap();
+    workspace
+}
+
+fn node_names(path: &Path) -> Vec<String> {
+    let mut workspace = Workspace::open(path).unwrap();
+    let tree = workspace
+        .group("Medicine")
+        .unwrap()
+        .subgroup("Anatomy")
+        .unwrap()
+        .tree()
+        .unwrap();
+    let mut names = tree
+        .nodes_map
+        .values()
+        .map(|graph_node| graph_node.node.get_name().to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+#[test]
+fn snapshot_has_the_committed_state_of_the_open_notebook() {
+    let directory = TempDir::new("snapshot");
+    let notebook = directory.path.join("anatomy.db");
+    let mut workspace = create_notebook(&notebook);
+    let backups = Backups::new(directory.path.join("backups"), RetentionPolicy::default());
+
+    let backup = workspace
+        .transaction(|workspace| {
+            workspace
+                .group("Medicine")?
+                .subgroup("Anatomy")?
+                .tree()?
+                .create_node("Lungs", None, None, NodeType::Regular)?;
+            backups.back_up(&notebook)
+        })
+        .unwrap();
+    assert_eq!(node_names(&backup.path), ["Heart"]);
+    assert_eq!(node_names(&notebook), ["Heart", "Lungs"]);
+    match snapshot(&notebook, &backup.path) {
+        Err(RelanotesError::NotebookExists(path)) => assert_eq!(path, backup.path),
+        other => panic!("Unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn old_backups_are_removed() {
+    let directory = TempDir::new("rotation");
+    let notebook = directory.path.join("anatomy.db");
+    create_notebook(&notebook);
+    // The backups of other notebooks are not touched
+    let other_notebook = directory.path.join("anatomy-2.db");
+    create_notebook(&other_notebook);
+    let backups_directory = directory.path.join("backups");
+    let backups = Backups::new(
+        &backups_directory,
+        RetentionPolicy {
+            keep_last: 2,
+            max_age: None,
+        },
+    );
+    let other = backups.back_up(&other_notebook).unwrap();
+    let written = (0..4)
+        .map(|_| backups.back_up(&notebook).unwrap())
+        .collect::<Vec<_>>();
+    assert!(written.windows(2).all(|w| w[0].timestamp < w[1].timestamp));
+    assert_eq!(
+        backups.list(&notebook).unwrap(),
+        [written[3].clone(), written[2].clone()]
+    );
+    assert_eq!(backups.list(&other_notebook).unwrap(), [other]);
+
+    let expiring = Backups::new(
+        &backups_directory,
+        RetentionPolicy {
+            keep_last: 10,
+            max_age: Some(Duration::from_secs(0)),
+        },
+    );
+    let latest = expiring.back_up(&notebook).unwrap();
+    assert_eq!(expiring.list(&notebook).unwrap(), [latest]);
+}
+
+#[test]
+fn failed_backups_leave_no_files() {
+    let directory = TempDir::new("failed");
+    let notebook = directory.path.join("anatomy.db");
+    std::fs::write(&notebook, vec![7; 4096]).unwrap();
+    let backups_directory = directory.path.join("backups");
+    let backups = Backups::new(&backups_directory, RetentionPolicy::default());
+
+    assert!(matches!(
+        backups.back_up(&notebook),
+        Err(RelanotesError::BackupFailed(_))
+    ));
+    assert_eq!(backups.list(&notebook).unwrap(), []);
+    assert_eq!(std::fs::read_dir(&backups_directory).unwrap().count(), 0);
+}
+
+#[test]
+fn restore_replaces_the_notebook_with_the_snapshot() {
+    let directory = TempDir::new("restore");
+    let notebook = directory.path.join("anatomy.db");
+    let mut workspace = create_notebook(&notebook);
+    let backups = Backups::new(directory.path.join("backups"), RetentionPolicy::default());
+    let backup = backups.back_up(&notebook).unwrap();
+    workspace
+        .group("Medicine")
+        .unwrap()
+        .subgroup("Anatomy")
+        .unwrap()
+        .tree()
+        .unwrap()
+        .create_node("Lungs", None, None, NodeType::Regular)
+        .unwrap();
+
+    restore(&backup.path, &notebook).unwrap();
+    assert_eq!(node_names(&notebook), ["Heart"]);
+    let copy = directory.path.join("copy.db");
+    restore(&backup.path, &copy).unwrap();
+    assert_eq!(node_names(&copy), ["Heart"]);
+    assert!(!directory.path.join("copy.db.restoring").exists());
+}
+
+#[test]
+fn broken_snapshots_are_not_restored() {
+    let directory = TempDir::new("broken");
+    let notebook = directory.path.join("anatomy.db");
+    drop(create_notebook(&notebook));
+    let content = std::fs::read(&notebook).unwrap();
+
+    let garbage = directory.path.join("garbage.backup");
+    std::fs::write(&garbage, vec![7; 4096]).unwrap();
+    // A SQLite database without the tables of the notebooks
+    let empty = directory.path.join("empty.backup");
+    SqliteConnection::establish(&empty.to_string_lossy())
+        .unwrap()
+        .execute("create table notes (id integer primary key)")
+        .unwrap();
+    for snapshot in &[garbage, empty] {
+        match restore(snapshot, &notebook) {
+            Err(RelanotesError::CorruptedSnapshot { path, problems }) => {
+                assert_eq!(&path, snapshot);
+                assert!(!problems.is_empty());
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+        assert_eq!(std::fs::read(&notebook).unwrap(), content);
+    }
+    assert_eq!(node_names(&notebook), ["Heart"]);
+}
@@ -0,0 +1,376 @@
+#![allow(dead_code)]
+
+use diesel::prelude::*;
+use diesel::SqliteConnection;
+use relanotes_rs::abstracts::Loadable;
+use relanotes_rs::database_setup::setup_database;
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::node_types::NodeTypesRegistry;
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::{NodeType, NodesTree};
+use relanotes_rs::groups_mod::subgroups_mod::SubGroups;
+use relanotes_rs::groups_mod::Groups;
+use relanotes_rs::storage::{MemoryStorage, NewNode, SqliteStorage, Storage};
+use std::sync::Arc;
+
+pub struct Fixture<S = SqliteStorage> {
+    pub storage: Arc<S>,
+    pub group_id: i32,
+    pub subgroup_id: i32,
+    pub other_subgroup_id: i32,
+}
+
+pub fn setup() -> Fixture {
+    let storage = SqliteStorage::new(SqliteConnection::establish(":memory:").unwrap()).unwrap();
+    setup_database(&storage).unwrap();
+    add_subgroups(storage)
+}
+
+// The database file of the test, removed when dropped
+pub struct TempFile {
+    pub path: std::path::PathBuf,
+}
+
+impl TempFile {
+    pub fn new(name: &str) -> Self {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static FILES: AtomicUsize = AtomicUsize::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "relanotes_test_{}_{}_{}",
+            std::process::id(),
+            FILES.fetch_add(1, Ordering::SeqCst),
+            name
+        ));
+        let _ = std::fs::remove_file(&path);
+        TempFile { path }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+// The directory of the test, removed with its files when dropped
+pub struct TempDir {
+    pub path: std::path::PathBuf,
+}
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = TempFile::new(name).path.clone();
+        std::fs::create_dir(&path).unwrap();
+        TempDir { path }
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+// Removes the throwaway database of the test when dropped
+#[cfg(feature = "postgres")]
+pub struct TestDatabase {
+    server_url: String,
+    name: String,
+}
+
+#[cfg(feature = "postgres")]
+impl Drop for TestDatabase {
+    fn drop(&mut self) {
+        if let Ok(conn) = diesel::pg::PgConnection::establish(&self.server_url) {
+            let _ = diesel::sql_query(format!(
+                "drop database if exists \"{}\" with (force)",
+                self.name
+            ))
+            .execute(&conn);
+        }
+    }
+}
+
+// Each test gets its own database on the local server
+#[cfg(feature = "postgres")]
+pub fn setup_postgres() -> (Fixture<relanotes_rs::storage::PgStorage>, TestDatabase) {
+    use diesel::pg::PgConnection;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static DATABASES: AtomicUsize = AtomicUsize::new(0);
+
+    let server_url = std::env::var("POSTGRES_TEST_URL").expect(
+        "POSTGRES_TEST_URL must be set - the url of a local server, like postgres://postgres@localhost/postgres",
+    );
+    let name = format!(
+        "relanotes_test_{}_{}",
+        std::process::id(),
+        DATABASES.fetch_add(1, Ordering::SeqCst)
+    );
+    let server = PgConnection::establish(&server_url).unwrap();
+    diesel::sql_query(format!("create database \"{}\"", name))
+        .execute(&server)
+        .unwrap();
+    let database = TestDatabase {
+        server_url: server_url.clone(),
+        name,
+    };
+    let (server_base_url, _) = server_url.rsplit_once('/').unwrap();
+    let conn = PgConnection::establish(&format!("{}/{}", server_base_url, database.name)).unwrap();
+    let storage = relanotes_rs::storage::PgStorage::new(conn);
+    relanotes_rs::database_setup::setup_postgres_database(&storage).unwrap();
+    (add_subgroups(storage), database)
+}
+
+pub fn setup_in_memory() -> Fixture<MemoryStorage> {
+    add_subgroups(MemoryStorage::new())
+}
+
+fn add_subgroups<S: Storage + 'static>(storage: S) -> Fixture<S> {
+    let storage = Arc::new(storage);
+    let group_id = Groups::new(storage.clone())
+        .create("Medicine".into())
+        .unwrap()
+        .group
+        .id;
+    let subgroup_id = add_subgroup(&storage, group_id, "Anatomy");
+    let other_subgroup_id = add_subgroup(&storage, group_id, "Physiology");
+    Fixture {
+        storage,
+        group_id,
+        subgroup_id,
+        other_subgroup_id,
+    }
+}
+
+pub fn add_subgroup<S: Storage + 'static>(storage: &Arc<S>, group_id: i32, name: &str) -> i32 {
+    SubGroups::new(storage.clone(), group_id)
+        .create(name.into())
+        .unwrap()
+        .subgroup
+        .id
+}
+
+pub fn load_tree<S: Storage + 'static>(storage: &Arc<S>, subgroup_id: i32) -> NodesTree {
+    let mut tree = NodesTree::new(storage.clone(), subgroup_id);
+    tree.load().unwrap();
+    tree
+}
+
+// The groups with the subgroups of the fixture group and their trees loaded
+pub fn load_groups(fixture: &Fixture) -> Groups {
+    let mut groups = Groups::new(fixture.storage.clone());
+    groups.load().unwrap();
+    let group = groups.groups_map.get_mut(&fixture.group_id).unwrap();
+    group.subgroups.load().unwrap();
+    for subgroup in group.subgroups.subgroups_map.values_mut() {
+        subgroup.nodes.load().unwrap();
+    }
+    groups
+}
+
+pub fn add_node(
+    tree: &mut NodesTree,
+    name: &str,
+    description: Option<&str>,
+    linked_to_id: Option<i32>,
+    node_type: NodeType,
+) -> i32 {
+    tree.create_node(name, description, linked_to_id, node_type)
+        .unwrap()
+        .get_node_id()
+}
+
+// Written without the validation and the foreign keys, the type id is 999 for unknown type
+// values
+pub fn insert_raw_node(
+    fixture: &Fixture,
+    id: i32,
+    name: &str,
+    linked_to_id: Option<i32>,
+    subgroup_id: i32,
+    type_value: &str,
+) {
+    let conn = &*fixture.storage.connection();
+    diesel::sql_query("pragma foreign_keys = off")
+        .execute(conn)
+        .unwrap();
+    diesel::sql_query(format!(
+        "insert into nodes (id, name, linked_to_id, subgroup_id, type_id) \
+         values ({}, '{}', {}, {}, coalesce((select id from node_types where value = '{}'), 999))",
+        id,
+        name,
+        linked_to_id.map_or("null".to_string(), |id| id.to_string()),
+        subgroup_id,
+        type_value,
+    ))
+    .execute(conn)
+    .unwrap();
+    diesel::sql_query("pragma foreign_keys = on")
+        .execute(conn)
+        .unwrap();
+}
+
+fn violation<T>(result: Result<T, diesel::result::Error>) -> String {
+    match result {
+        Err(diesel::result::Error::DatabaseError(_, info)) => info.message().to_string(),
+        Err(e) => panic!("Expected a constraint violation, got {:?}", e),
+        Ok(_) => panic!("Expected a constraint violation"),
+    }
+}
+
+// Written with the storage directly, so that only the database rules are checked
+pub fn assert_divisors_are_transparent_for_owners<S: Storage>(fixture: &Fixture<S>) {
+    let storage = &*fixture.storage;
+    let node_types = NodeTypesRegistry::load(storage).unwrap();
+    let create = |name: &str, linked_to_id: Option<i32>, subgroup_id: i32, node_type| {
+        storage.create_node(&NewNode {
+            name,
+            description: None,
+            type_id: node_types.get_type_id(&node_type).unwrap(),
+            linked_to_id,
+            subgroup_id,
+        })
+    };
+    let subgroup_id = fixture.subgroup_id;
+    let heart = create("Heart", None, subgroup_id, NodeType::Regular)
+        .unwrap()
+        .id;
+    let parts = create("Parts", Some(heart), subgroup_id, NodeType::Divisor)
+        .unwrap()
+        .id;
+    let chambers = create("Chambers", Some(parts), subgroup_id, NodeType::Divisor)
+        .unwrap()
+        .id;
+    create(
+        "Ventricle",
+        Some(chambers),
+        subgroup_id,
+        NodeType::Inherited,
+    )
+    .unwrap();
+
+    for owner_id in [heart, parts] {
+        let result = create(
+            "Ventricle",
+            Some(owner_id),
+            subgroup_id,
+            NodeType::Inherited,
+        );
+        assert_eq!(violation(result), "DuplicateInheritedNode");
+    }
+    let atrium = create("Atrium", Some(parts), subgroup_id, NodeType::Inherited)
+        .unwrap()
+        .id;
+    let result = storage.update_node_content(atrium, "Ventricle", None);
+    assert_eq!(violation(result), "DuplicateInheritedNode");
+    // Another owner has its own namespace
+    let lobe = create("Ventricle", Some(atrium), subgroup_id, NodeType::Inherited)
+        .unwrap()
+        .id;
+    let result = storage.set_linked_to_id(&[lobe], Some(heart));
+    assert_eq!(violation(result), "DuplicateInheritedNode");
+
+    // Divisors without an owner share the namespace of the roots of the subgroup
+    let first = create("First", None, subgroup_id, NodeType::Divisor)
+        .unwrap()
+        .id;
+    let second = create("Second", None, subgroup_id, NodeType::Divisor)
+        .unwrap()
+        .id;
+    create("Valve", Some(first), subgroup_id, NodeType::Inherited).unwrap();
+    let result = create("Valve", Some(second), subgroup_id, NodeType::Inherited);
+    assert_eq!(violation(result), "DuplicateInheritedNode");
+    let other = create("Other", None, fixture.other_subgroup_id, NodeType::Divisor)
+        .unwrap()
+        .id;
+    create(
+        "Valve",
+        Some(other),
+        fixture.other_subgroup_id,
+        NodeType::Inherited,
+    )
+    .unwrap();
+
+    // The moved divisor takes its children into the namespace of its new owner
+    let lungs = create("Lungs", None, subgroup_id, NodeType::Regular)
+        .unwrap()
+        .id;
+    let lobes = create("Lobes", Some(lungs), subgroup_id, NodeType::Divisor)
+        .unwrap()
+        .id;
+    create("Atrium", Some(lobes), subgroup_id, NodeType::Inherited).unwrap();
+    let result = storage.set_linked_to_id(&[lobes], Some(heart));
+    assert_eq!(violation(result), "DuplicateInheritedNode");
+    let result = storage.set_linked_to_id(&[lobes], Some(chambers));
+    assert_eq!(violation(result), "DuplicateInheritedNode");
+    create("Ventricle", Some(lungs), subgroup_id, NodeType::Inherited).unwrap();
+    let result = storage.set_linked_to_id(&[parts], Some(lungs));
+    assert_eq!(violation(result), "DuplicateInheritedNode");
+    // Moved inside the namespace, the children don't collide with themselves
+    storage.set_linked_to_id(&[chambers], Some(heart)).unwrap();
+    storage.set_linked_to_id(&[chambers], Some(parts)).unwrap();
+}
+
+fn assert_foreign_key_violation<T>(result: Result<T, diesel::result::Error>) {
+    use diesel::result::{DatabaseErrorKind, Error};
+    match result {
+        Err(Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _)) => {}
+        Err(e) => panic!("Expected a foreign key violation, got {:?}", e),
+        Ok(_) => panic!("Expected a foreign key violation"),
+    }
+}
+
+pub fn assert_foreign_keys_are_enforced<S: Storage>(fixture: &Fixture<S>) {
+    let storage = &*fixture.storage;
+    let node_types = NodeTypesRegistry::load(storage).unwrap();
+    let create = |name: &str, linked_to_id: Option<i32>, subgroup_id: i32, node_type| {
+        storage.create_node(&NewNode {
+            name,
+            description: None,
+            type_id: node_types.get_type_id(&node_type).unwrap(),
+            linked_to_id,
+            subgroup_id,
+        })
+    };
+    let heart = create("Heart", None, fixture.subgroup_id, NodeType::Regular)
+        .unwrap()
+        .id;
+    let symlink = create(
+        "",
+        Some(heart),
+        fixture.other_subgroup_id,
+        NodeType::SymLink,
+    )
+    .unwrap()
+    .id;
+
+    let result = create(
+        "Ventricle",
+        Some(999),
+        fixture.subgroup_id,
+        NodeType::Inherited,
+    );
+    assert_foreign_key_violation(result);
+    let result = create("Lungs", None, 999, NodeType::Regular);
+    assert_foreign_key_violation(result);
+    let result = storage.delete_nodes(&[heart]);
+    assert_foreign_key_violation(result);
+    // The nodes linked to each other can be deleted together
+    storage.delete_nodes(&[heart, symlink]).unwrap();
+
+    let heart = create("Heart", None, fixture.subgroup_id, NodeType::Regular)
+        .unwrap()
+        .id;
+    create(
+        "Ventricle",
+        Some(heart),
+        fixture.subgroup_id,
+        NodeType::Inherited,
+    )
+    .unwrap();
+    storage.delete_subgroup(fixture.subgroup_id).unwrap();
+    assert!(storage.load_nodes(None).unwrap().is_empty());
+    create("Lungs", None, fixture.other_subgroup_id, NodeType::Regular).unwrap();
+    storage.delete_group(fixture.group_id).unwrap();
+    assert!(storage.load_subgroups(None).unwrap().is_empty());
+    assert!(storage.load_nodes(None).unwrap().is_empty());
+}
@@ -0,0 +1,277 @@
+mod common;
+
+use common::{add_node, add_subgroup, load_tree, setup};
+use diesel::prelude::*;
+use diesel::result::Error;
+use diesel::SqliteConnection;
+use relanotes_rs::abstracts::Loadable;
+use relanotes_rs::errors::RelanotesError;
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::{NodeType, RelanotesValidationRejection};
+use relanotes_rs::groups_mod::Groups;
+use relanotes_rs::schema::{node_types, nodes};
+
+fn type_id(conn: &SqliteConnection, value: &str) -> i32 {
+    node_types::table
+        .filter(node_types::value.eq(value))
+        .select(node_types::id)
+        .first(conn)
+        .unwrap()
+}
+
+fn insert_node(
+    conn: &SqliteConnection,
+    name: &str,
+    linked_to_id: Option<i32>,
+    subgroup_id: i32,
+    type_value: &str,
+) -> QueryResult<usize> {
+    diesel::insert_into(nodes::table)
+        .values((
+            nodes::name.eq(name),
+            nodes::linked_to_id.eq(linked_to_id),
+            nodes::subgroup_id.eq(subgroup_id),
+            nodes::type_id.eq(type_id(conn, type_value)),
+        ))
+        .execute(conn)
+}
+
+fn violation(result: QueryResult<usize>) -> String {
+    match result {
+        Err(Error::DatabaseError(_, info)) => info.message().to_string(),
+        result => panic!("Expected a constraint violation, got {:?}", result),
+    }
+}
+
+#[test]
+fn regular_names_are_unique_in_the_group() {
+    let fixture = setup();
+    let conn = &*fixture.storage.connection();
+    insert_node(conn, "Heart", None, fixture.subgroup_id, "regular").unwrap();
+    insert_node(conn, "Lungs", None, fixture.subgroup_id, "regular").unwrap();
+
+    let result = insert_node(conn, "Heart", None, fixture.other_subgroup_id, "regular");
+    assert_eq!(violation(result), "DuplicateRegularNode");
+    let result = diesel::update(nodes::table.filter(nodes::name.eq("Lungs")))
+        .set(nodes::name.eq("Heart"))
+        .execute(conn);
+    assert_eq!(violation(result), "DuplicateRegularNode");
+
+    let other_group_id = Groups::new(fixture.storage.clone())
+        .create("Biology".into())
+        .unwrap()
+        .group
+        .id;
+    let other_group_subgroup_id = add_subgroup(&fixture.storage, other_group_id, "Cells");
+    assert!(insert_node(conn, "Heart", None, other_group_subgroup_id, "regular").is_ok());
+}
+
+#[test]
+fn sticky_notes_and_inherited_nodes_are_unique_in_the_owner() {
+    let fixture = setup();
+    let conn = &*fixture.storage.connection();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let lungs = add_node(&mut tree, "Lungs", None, None, NodeType::Regular);
+    add_node(
+        &mut tree,
+        "Mnemonic",
+        None,
+        Some(heart),
+        NodeType::StickyNotes,
+    );
+    add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+
+    let result = insert_node(
+        conn,
+        "Mnemonic",
+        Some(heart),
+        fixture.subgroup_id,
+        "sticky_notes",
+    );
+    assert_eq!(violation(result), "DuplicateStickyNote");
+    let result = insert_node(
+        conn,
+        "Ventricle",
+        Some(heart),
+        fixture.subgroup_id,
+        "inherited",
+    );
+    assert_eq!(violation(result), "DuplicateInheritedNode");
+
+    let lobe = add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(lungs),
+        NodeType::Inherited,
+    );
+    let result = diesel::update(nodes::table.filter(nodes::id.eq(lobe)))
+        .set(nodes::linked_to_id.eq(heart))
+        .execute(conn);
+    assert_eq!(violation(result), "DuplicateInheritedNode");
+}
+
+#[test]
+fn symlinks_cant_target_their_own_subgroup() {
+    let fixture = setup();
+    let conn = &*fixture.storage.connection();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let mut other_tree = load_tree(&fixture.storage, fixture.other_subgroup_id);
+    add_node(&mut other_tree, "", None, Some(heart), NodeType::SymLink);
+
+    let result = insert_node(conn, "", Some(heart), fixture.subgroup_id, "symlinks");
+    assert_eq!(violation(result), "SymLinkToSameSubgroup");
+    // The source can't be moved next to its symlink either
+    let result = diesel::update(nodes::table.filter(nodes::id.eq(heart)))
+        .set(nodes::subgroup_id.eq(fixture.other_subgroup_id))
+        .execute(conn);
+    assert_eq!(violation(result), "SymLinkToSameSubgroup");
+}
+
+#[test]
+fn links_never_form_a_cycle() {
+    let fixture = setup();
+    let conn = &*fixture.storage.connection();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let ventricle = add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+    let wall = add_node(
+        &mut tree,
+        "Wall",
+        None,
+        Some(ventricle),
+        NodeType::Inherited,
+    );
+
+    let result = diesel::update(nodes::table.filter(nodes::id.eq(heart)))
+        .set(nodes::linked_to_id.eq(heart))
+        .execute(conn);
+    assert_eq!(violation(result), "LinkedToItself");
+    let result = diesel::update(nodes::table.filter(nodes::id.eq(heart)))
+        .set(nodes::linked_to_id.eq(wall))
+        .execute(conn);
+    assert_eq!(violation(result), "CyclicLink");
+}
+
+#[test]
+fn violations_are_mapped_to_validation_rejections() {
+    let fixture = setup();
+    let conn = &*fixture.storage.connection();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    // Written by another tool after the tree was loaded
+    insert_node(
+        conn,
+        "Mnemonic",
+        Some(heart),
+        fixture.subgroup_id,
+        "sticky_notes",
+    )
+    .unwrap();
+
+    let result = tree.create_node("Mnemonic", None, Some(heart), NodeType::StickyNotes);
+    assert!(matches!(
+        result,
+        Err(RelanotesError::ValidationRejection(
+            RelanotesValidationRejection::DuplicateStickyNote(ref name)
+        )) if name == "Mnemonic"
+    ));
+}
+
+#[test]
+fn moving_subtree_next_to_its_symlinks_merges_them() {
+    let fixture = setup();
+    let conn = &*fixture.storage.connection();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let mut other_tree = load_tree(&fixture.storage, fixture.other_subgroup_id);
+    let symlink = add_node(&mut other_tree, "", None, Some(heart), NodeType::SymLink);
+    let note = add_node(
+        &mut other_tree,
+        "Rhythm",
+        None,
+        Some(symlink),
+        NodeType::StickyNotes,
+    );
+
+    let mut groups = Groups::new(fixture.storage.clone());
+    groups.load().unwrap();
+    let group = groups.groups_map.get_mut(&fixture.group_id).unwrap();
+    group.subgroups.load().unwrap();
+    for subgroup in group.subgroups.subgroups_map.values_mut() {
+        subgroup.nodes.load().unwrap();
+    }
+    groups
+        .move_subtree(heart, fixture.other_subgroup_id, None)
+        .unwrap();
+
+    let symlinks = nodes::table
+        .filter(nodes::id.eq(symlink))
+        .count()
+        .get_result::<i64>(conn)
+        .unwrap();
+    assert_eq!(symlinks, 0);
+    let note_owner = nodes::table
+        .filter(nodes::id.eq(note))
+        .select(nodes::linked_to_id)
+        .first::<Option<i32>>(conn)
+        .unwrap();
+    assert_eq!(note_owner, Some(heart));
+}
+
+#[test]
+fn merged_symlink_children_are_unique_in_the_namespace_of_the_source() {
+    let fixture = setup();
+    let conn = &*fixture.storage.connection();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    // The divisor is transparent, Ventricle is in the namespace of Heart
+    let chambers = add_node(&mut tree, "Chambers", None, Some(heart), NodeType::Divisor);
+    add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(chambers),
+        NodeType::Inherited,
+    );
+    let mut other_tree = load_tree(&fixture.storage, fixture.other_subgroup_id);
+    let symlink = add_node(&mut other_tree, "", None, Some(heart), NodeType::SymLink);
+    let ventricle = add_node(
+        &mut other_tree,
+        "Ventricle",
+        None,
+        Some(symlink),
+        NodeType::Inherited,
+    );
+
+    let mut groups = Groups::new(fixture.storage.clone());
+    groups.load().unwrap();
+    let group = groups.groups_map.get_mut(&fixture.group_id).unwrap();
+    group.subgroups.load().unwrap();
+    for subgroup in group.subgroups.subgroups_map.values_mut() {
+        subgroup.nodes.load().unwrap();
+    }
+    match groups.move_subtree(heart, fixture.other_subgroup_id, None) {
+        Err(RelanotesError::ChildCannotBeLifted(child_id)) => assert_eq!(child_id, ventricle),
+        other => panic!("Unexpected result {:?}", other),
+    }
+    let subgroup_id = nodes::table
+        .filter(nodes::id.eq(heart))
+        .select(nodes::subgroup_id)
+        .first::<i32>(conn)
+        .unwrap();
+    assert_eq!(subgroup_id, fixture.subgroup_id);
+}
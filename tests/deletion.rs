@@ -0,0 +1,220 @@
+mod common;
+
+use common::{add_node, insert_raw_node, load_tree, setup};
+use relanotes_rs::errors::RelanotesError;
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::{DeletionStrategy, NodeType};
+use relanotes_rs::storage::Storage;
+
+#[test]
+fn node_with_children_is_refused_unless_asked() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let ventricle = add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+    match tree.delete_node(heart, DeletionStrategy::RefuseIfHasChildren) {
+        Err(RelanotesError::NodeHasChildren(node_id)) => assert_eq!(node_id, heart),
+        other => panic!("Unexpected result {:?}", other),
+    }
+    assert_eq!(tree.nodes_map.len(), 2);
+
+    assert_eq!(
+        tree.delete_node(ventricle, DeletionStrategy::RefuseIfHasChildren)
+            .unwrap(),
+        [ventricle]
+    );
+    assert!(tree.get_node_loaded_children(&heart).unwrap().is_empty());
+}
+
+#[test]
+fn cascade_deletes_the_descendants_first() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let ventricle = add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+    let wall = add_node(
+        &mut tree,
+        "Wall",
+        None,
+        Some(ventricle),
+        NodeType::Inherited,
+    );
+    let lungs = add_node(&mut tree, "Lungs", None, None, NodeType::Regular);
+
+    let deleted = tree.delete_node(heart, DeletionStrategy::Cascade).unwrap();
+    assert_eq!(deleted, [wall, ventricle, heart]);
+    assert_eq!(tree.get_roots(), [lungs]);
+    let remaining = fixture
+        .storage
+        .load_nodes(Some(fixture.subgroup_id))
+        .unwrap();
+    assert_eq!(remaining.iter().map(|n| n.id).collect::<Vec<_>>(), [lungs]);
+}
+
+#[test]
+fn lifted_children_take_the_place_of_the_deleted_node() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let body = add_node(&mut tree, "Body", None, None, NodeType::Regular);
+    let heart = add_node(&mut tree, "Heart", None, Some(body), NodeType::Inherited);
+    let lungs = add_node(&mut tree, "Lungs", None, Some(body), NodeType::Inherited);
+    let brain = add_node(&mut tree, "Brain", None, Some(body), NodeType::Inherited);
+    let alveoli = add_node(&mut tree, "Alveoli", None, Some(lungs), NodeType::Inherited);
+    let bronchi = add_node(&mut tree, "Bronchi", None, Some(lungs), NodeType::Inherited);
+
+    let deleted = tree
+        .delete_node(lungs, DeletionStrategy::LiftChildrenToParent)
+        .unwrap();
+    assert_eq!(deleted, [lungs]);
+    assert_eq!(
+        tree.get_node_loaded_children(&body).unwrap(),
+        [heart, alveoli, bronchi, brain]
+    );
+    assert_eq!(tree.nodes_map[&alveoli].parent_node_id, Some(body));
+    assert_eq!(tree.nodes_map[&bronchi].unique_path, [body, bronchi]);
+    // The lifted children have the versions of the database, so they can be written again
+    assert_eq!(
+        tree.nodes_map[&bronchi].version,
+        fixture.storage.get_node(bronchi).unwrap().unwrap().version
+    );
+    tree.move_node(bronchi, Some(heart)).unwrap();
+
+    let tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    assert_eq!(tree.nodes_map[&alveoli].parent_node_id, Some(body));
+    assert_eq!(tree.nodes_map[&bronchi].parent_node_id, Some(heart));
+}
+
+#[test]
+fn children_are_not_lifted_to_a_quarantined_parent() {
+    let fixture = setup();
+    let subgroup_id = fixture.subgroup_id;
+    insert_raw_node(&fixture, 100, "Unknown", None, subgroup_id, "missing");
+    insert_raw_node(&fixture, 101, "Heart", Some(100), subgroup_id, "regular");
+    insert_raw_node(
+        &fixture,
+        102,
+        "Ventricle",
+        Some(101),
+        subgroup_id,
+        "inherited",
+    );
+    let mut tree = load_tree(&fixture.storage, subgroup_id);
+
+    match tree.delete_node(101, DeletionStrategy::LiftChildrenToParent) {
+        Err(RelanotesError::ParentNotLoaded { node_id, parent_id }) => {
+            assert_eq!((node_id, parent_id), (101, 100))
+        }
+        other => panic!("Unexpected result {:?}", other),
+    }
+    assert_eq!(tree.get_node_loaded_children(&101).unwrap(), [102]);
+    let ventricle = fixture.storage.get_node(102).unwrap().unwrap();
+    assert_eq!(ventricle.linked_to_id, Some(101));
+}
+
+#[test]
+fn children_that_cant_be_lifted_keep_the_node() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let body = add_node(&mut tree, "Body", None, None, NodeType::Regular);
+    let heart = add_node(&mut tree, "Heart", None, Some(body), NodeType::Inherited);
+    let chambers = add_node(&mut tree, "Chambers", None, Some(body), NodeType::Inherited);
+    // Would become a second Heart in the children of Body
+    let inner_heart = add_node(
+        &mut tree,
+        "Heart",
+        None,
+        Some(chambers),
+        NodeType::Inherited,
+    );
+    match tree.delete_node(chambers, DeletionStrategy::LiftChildrenToParent) {
+        Err(RelanotesError::ChildCannotBeLifted(child_id)) => assert_eq!(child_id, inner_heart),
+        other => panic!("Unexpected result {:?}", other),
+    }
+
+    // Sticky notes can't become roots
+    let lungs = add_node(&mut tree, "Lungs", None, None, NodeType::Regular);
+    let mnemonic = add_node(
+        &mut tree,
+        "Mnemonic",
+        None,
+        Some(lungs),
+        NodeType::StickyNotes,
+    );
+    match tree.delete_node(lungs, DeletionStrategy::LiftChildrenToParent) {
+        Err(RelanotesError::ChildCannotBeLifted(child_id)) => assert_eq!(child_id, mnemonic),
+        other => panic!("Unexpected result {:?}", other),
+    }
+    assert_eq!(
+        tree.get_node_loaded_children(&body).unwrap(),
+        [heart, chambers]
+    );
+    assert_eq!(tree.get_node_loaded_children(&lungs).unwrap(), [mnemonic]);
+    assert_eq!(fixture.storage.load_nodes(None).unwrap().len(), 6);
+}
+
+#[test]
+fn nodes_with_symlinks_in_other_subgroups_are_not_deleted() {
+    let fixture = setup();
+    let mut anatomy = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut anatomy, "Heart", None, None, NodeType::Regular);
+    let ventricle = add_node(
+        &mut anatomy,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+    let mut physiology = load_tree(&fixture.storage, fixture.other_subgroup_id);
+    let symlink = add_node(
+        &mut physiology,
+        "",
+        None,
+        Some(ventricle),
+        NodeType::SymLink,
+    );
+
+    match anatomy.delete_node(heart, DeletionStrategy::Cascade) {
+        Err(RelanotesError::NodeIsReferenced {
+            node_id,
+            referenced_by,
+        }) => assert_eq!((node_id, referenced_by), (heart, vec![symlink])),
+        other => panic!("Unexpected result {:?}", other),
+    }
+    assert_eq!(anatomy.nodes_map.len(), 2);
+
+    physiology
+        .delete_node(symlink, DeletionStrategy::RefuseIfHasChildren)
+        .unwrap();
+    anatomy
+        .delete_node(heart, DeletionStrategy::Cascade)
+        .unwrap();
+    assert!(fixture.storage.load_nodes(None).unwrap().is_empty());
+}
+
+#[test]
+fn lifted_child_can_take_the_name_of_the_deleted_node() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let body = add_node(&mut tree, "Body", None, None, NodeType::Regular);
+    let lungs = add_node(&mut tree, "Lungs", None, Some(body), NodeType::Inherited);
+    let inner_lungs = add_node(&mut tree, "Lungs", None, Some(lungs), NodeType::Inherited);
+
+    let deleted = tree
+        .delete_node(lungs, DeletionStrategy::LiftChildrenToParent)
+        .unwrap();
+    assert_eq!(deleted, [lungs]);
+    assert_eq!(tree.get_node_loaded_children(&body).unwrap(), [inner_lungs]);
+    let tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    assert_eq!(tree.nodes_map[&inner_lungs].parent_node_id, Some(body));
+}
@@ -0,0 +1,362 @@
+mod common;
+
+use common::{add_node, setup_in_memory};
+use relanotes_rs::abstracts::{Loadable, Saveable};
+use relanotes_rs::errors::RelanotesError;
+use relanotes_rs::events::Event;
+use relanotes_rs::events::Events;
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::node_types::NodeTypesCache;
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::{DeletionStrategy, NodeType, NodesTree};
+use relanotes_rs::workspace::Workspace;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+fn received(receiver: &Receiver<Event>) -> Vec<Event> {
+    receiver.try_iter().collect()
+}
+
+// The names of the events, as the front-ends get them
+fn names(events: &[Event]) -> Vec<String> {
+    events
+        .iter()
+        .map(|event| serde_json::to_value(event).unwrap()["event"].to_string())
+        .map(|name| name.trim_matches('"').to_string())
+        .collect()
+}
+
+fn node_ids(event: &Event) -> Vec<i32> {
+    match event {
+        Event::SubGroupLoaded { nodes, .. } | Event::NodeMoved { nodes, .. } => nodes
+            .iter()
+            .map(|graph_node| graph_node.node.get_node_id())
+            .collect(),
+        other => panic!("Unexpected event {:?}", other),
+    }
+}
+
+#[test]
+fn changes_of_the_model_are_published() {
+    let mut workspace = Workspace::in_memory();
+    let events = workspace.subscribe();
+    let medicine = workspace.create_group("Medicine").unwrap();
+    let group_id = medicine.group.id;
+    let anatomy = medicine.create_subgroup("Anatomy").unwrap();
+    let subgroup_id = anatomy.subgroup.id;
+    let tree = anatomy.tree().unwrap();
+    let heart = tree
+        .create_node("Heart", None, None, NodeType::Regular)
+        .unwrap()
+        .get_node_id();
+    let ventricle = tree
+        .create_node("Ventricle", None, None, NodeType::Regular)
+        .unwrap()
+        .get_node_id();
+    let created = received(&events);
+    assert_eq!(
+        names(&created),
+        [
+            "GroupCreated",
+            "SubGroupCreated",
+            "SubGroupLoaded",
+            "NodeCreated",
+            "NodeCreated"
+        ]
+    );
+    if let Event::SubGroupCreated { subgroup } = &created[1] {
+        assert_eq!((subgroup.id, subgroup.group_id), (subgroup_id, group_id));
+    }
+    assert!(node_ids(&created[2]).is_empty());
+    if let Event::NodeCreated { node, .. } = &created[3] {
+        assert_eq!(node.node.get_name(), "Heart");
+    }
+
+    tree.update_name_and_description(heart, "Heart".into(), Some("Pumps the blood".into()))
+        .unwrap();
+    tree.move_node(ventricle, Some(heart)).unwrap();
+    tree.delete_node(heart, DeletionStrategy::LiftChildrenToParent)
+        .unwrap();
+    let changed = received(&events);
+    assert_eq!(
+        names(&changed),
+        ["NodeUpdated", "NodeMoved", "NodeDeleted", "NodeMoved"]
+    );
+    if let Event::NodeUpdated { node, .. } = &changed[0] {
+        assert_eq!(node.node.get_description(), Some("Pumps the blood"));
+    }
+    if let Event::NodeMoved { nodes, .. } = &changed[1] {
+        assert_eq!(nodes[0].parent_node_id, Some(heart));
+    }
+    if let Event::NodeDeleted { node_ids, .. } = &changed[2] {
+        assert_eq!(node_ids, &[heart]);
+    }
+    // The lifted child
+    assert_eq!(node_ids(&changed[3]), [ventricle]);
+
+    tree.unload();
+    let anatomy = workspace
+        .group("Medicine")
+        .unwrap()
+        .subgroup("Anatomy")
+        .unwrap();
+    anatomy.subgroup.name = "Human anatomy".into();
+    anatomy.save().unwrap();
+    anatomy.tree().unwrap();
+    let reloaded = received(&events);
+    assert_eq!(
+        names(&reloaded),
+        ["SubGroupUnloaded", "SubGroupUpdated", "SubGroupLoaded"]
+    );
+    if let Event::SubGroupUpdated { subgroup } = &reloaded[1] {
+        assert_eq!(subgroup.name, "Human anatomy");
+    }
+    assert_eq!(node_ids(&reloaded[2]), [ventricle]);
+
+    let medicine = workspace.group("Medicine").unwrap();
+    medicine.group.name = "Biology".into();
+    medicine.save().unwrap();
+    medicine.subgroups.delete(subgroup_id).unwrap();
+    workspace.groups().unwrap().delete(group_id).unwrap();
+    let deleted = received(&events);
+    assert_eq!(
+        names(&deleted),
+        ["GroupUpdated", "SubGroupDeleted", "GroupDeleted"]
+    );
+    if let Event::GroupUpdated { group } = &deleted[0] {
+        assert_eq!(group.name, "Biology");
+    }
+}
+
+#[test]
+fn events_of_transactions_are_published_on_commit() {
+    let mut workspace = Workspace::in_memory();
+    let events = workspace.subscribe();
+    let result = workspace.transaction(|workspace| -> Result<(), RelanotesError> {
+        workspace.create_group("Medicine")?;
+        assert!(received(&events).is_empty());
+        workspace.create_group("Medicine")?;
+        Ok(())
+    });
+    assert!(result.is_err());
+    assert!(received(&events).is_empty());
+
+    workspace
+        .transaction(|workspace| -> Result<(), RelanotesError> {
+            let tree = workspace
+                .create_group("Medicine")?
+                .create_subgroup("Anatomy")?
+                .tree()?;
+            tree.create_node("Heart", None, None, NodeType::Regular)?;
+            // The failed nested transaction drops only its own events
+            let nested = tree.transaction(|tree| -> Result<(), RelanotesError> {
+                tree.create_node("Lungs", None, None, NodeType::Regular)?;
+                tree.create_node("Lungs", None, None, NodeType::Regular)?;
+                Ok(())
+            });
+            assert!(nested.is_err());
+            assert!(received(&events).is_empty());
+            Ok(())
+        })
+        .unwrap();
+    let committed = received(&events);
+    assert_eq!(
+        names(&committed),
+        [
+            "GroupCreated",
+            "SubGroupCreated",
+            "SubGroupLoaded",
+            "NodeCreated"
+        ]
+    );
+    if let Event::NodeCreated { node, .. } = &committed[3] {
+        assert_eq!(node.node.get_name(), "Heart");
+    }
+
+    // The dropped receivers are unsubscribed
+    drop(events);
+    workspace.create_group("Physics").unwrap();
+}
+
+#[test]
+fn events_are_published_after_a_panicking_transaction() {
+    let mut workspace = Workspace::in_memory();
+    let events = workspace.subscribe();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        workspace.transaction(|workspace| -> Result<(), RelanotesError> {
+            workspace.create_group("Medicine")?;
+            panic!("Failed in the transaction");
+        })
+    }));
+    assert!(result.is_err());
+    assert!(received(&events).is_empty());
+
+    workspace.create_group("Physics").unwrap();
+    assert_eq!(names(&received(&events)), ["GroupCreated"]);
+}
+
+#[test]
+fn moves_between_subgroups_are_published_with_the_merged_symlinks() {
+    let mut workspace = Workspace::in_memory();
+    let medicine = workspace.create_group("Medicine").unwrap();
+    let anatomy = medicine.create_subgroup("Anatomy").unwrap();
+    let anatomy_id = anatomy.subgroup.id;
+    let heart = anatomy
+        .tree()
+        .unwrap()
+        .create_node("Heart", None, None, NodeType::Regular)
+        .unwrap()
+        .get_node_id();
+    let physiology = medicine.create_subgroup("Physiology").unwrap();
+    let physiology_id = physiology.subgroup.id;
+    let tree = physiology.tree().unwrap();
+    let circulation = tree
+        .create_node("Circulation", None, None, NodeType::Regular)
+        .unwrap()
+        .get_node_id();
+    let symlink = tree
+        .create_node("", None, Some(heart), NodeType::SymLink)
+        .unwrap()
+        .get_node_id();
+    let pulse = tree
+        .create_node("Pulse", None, Some(symlink), NodeType::StickyNotes)
+        .unwrap()
+        .get_node_id();
+
+    let events = workspace.subscribe();
+    workspace
+        .groups()
+        .unwrap()
+        .move_subtree(heart, physiology_id, Some(circulation))
+        .unwrap();
+    let moved = received(&events);
+    assert_eq!(names(&moved), ["NodeDeleted", "NodeMoved", "NodeMoved"]);
+    if let Event::NodeDeleted {
+        subgroup_id,
+        node_ids,
+    } = &moved[0]
+    {
+        assert_eq!(
+            (*subgroup_id, &node_ids[..]),
+            (physiology_id, &[symlink][..])
+        );
+    }
+    if let Event::NodeMoved {
+        from_subgroup_id,
+        to_subgroup_id,
+        ..
+    } = &moved[1]
+    {
+        assert_eq!(
+            (*from_subgroup_id, *to_subgroup_id),
+            (anatomy_id, physiology_id)
+        );
+    }
+    assert_eq!(node_ids(&moved[1]), [heart, pulse]);
+    // The child of the merged symlink is linked to the moved node
+    assert_eq!(node_ids(&moved[2]), [pulse]);
+}
+
+#[test]
+fn transactions_hold_back_only_the_events_of_their_thread() {
+    // Two notebooks sharing the events, so that the transaction doesn't block the other writes
+    let events = Events::default();
+    let receiver = events.subscribe();
+    let trees = [setup_in_memory(), setup_in_memory()].map(|fixture| {
+        let mut tree = NodesTree::in_workspace(
+            fixture.storage.clone(),
+            fixture.subgroup_id,
+            NodeTypesCache::default(),
+            events.clone(),
+        );
+        tree.load().unwrap();
+        tree
+    });
+    let [mut failing, mut other] = trees;
+    received(&receiver);
+
+    let barrier = Arc::new(Barrier::new(2));
+    let transaction = {
+        let barrier = barrier.clone();
+        thread::spawn(move || {
+            let result = failing.transaction(|tree| -> Result<(), RelanotesError> {
+                add_node(tree, "Heart", None, None, NodeType::Regular);
+                barrier.wait();
+                barrier.wait();
+                Err(RelanotesError::NodeNotFound(0))
+            });
+            assert!(result.is_err());
+        })
+    };
+    barrier.wait();
+    let lungs = add_node(&mut other, "Lungs", None, None, NodeType::Regular);
+    let published = received(&receiver);
+    barrier.wait();
+    transaction.join().unwrap();
+
+    assert_eq!(names(&published), ["NodeCreated"]);
+    match &published[0] {
+        Event::NodeCreated { node, .. } => assert_eq!(node.node.get_node_id(), lungs),
+        other => panic!("Unexpected event {:?}", other),
+    }
+    assert!(received(&receiver).is_empty());
+}
+
+#[test]
+fn symlink_moved_next_to_its_source_is_published_as_deleted() {
+    let mut workspace = Workspace::in_memory();
+    let medicine = workspace.create_group("Medicine").unwrap();
+    let anatomy = medicine.create_subgroup("Anatomy").unwrap();
+    let anatomy_id = anatomy.subgroup.id;
+    let heart = anatomy
+        .tree()
+        .unwrap()
+        .create_node("Heart", None, None, NodeType::Regular)
+        .unwrap()
+        .get_node_id();
+    let physiology = medicine.create_subgroup("Physiology").unwrap();
+    let physiology_id = physiology.subgroup.id;
+    let tree = physiology.tree().unwrap();
+    let symlink = tree
+        .create_node("", None, Some(heart), NodeType::SymLink)
+        .unwrap()
+        .get_node_id();
+    let pulse = tree
+        .create_node("Pulse", None, Some(symlink), NodeType::StickyNotes)
+        .unwrap()
+        .get_node_id();
+
+    let events = workspace.subscribe();
+    workspace
+        .groups()
+        .unwrap()
+        .move_subtree(symlink, anatomy_id, None)
+        .unwrap();
+    let moved = received(&events);
+    assert_eq!(names(&moved), ["NodeDeleted", "NodeMoved"]);
+    match &moved[0] {
+        Event::NodeDeleted {
+            subgroup_id,
+            node_ids,
+        } => assert_eq!(
+            (*subgroup_id, &node_ids[..]),
+            (physiology_id, &[symlink][..])
+        ),
+        other => panic!("Unexpected event {:?}", other),
+    }
+    match &moved[1] {
+        Event::NodeMoved {
+            node_id,
+            from_subgroup_id,
+            to_subgroup_id,
+            nodes,
+        } => {
+            assert_eq!(
+                (*node_id, *from_subgroup_id, *to_subgroup_id),
+                (pulse, physiology_id, anatomy_id)
+            );
+            assert_eq!(nodes[0].parent_node_id, Some(heart));
+        }
+        other => panic!("Unexpected event {:?}", other),
+    }
+}
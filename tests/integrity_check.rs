@@ -0,0 +1,261 @@
+mod common;
+
+use common::{add_node, insert_raw_node, load_tree, setup, Fixture};
+use diesel::prelude::*;
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::NodeType;
+use relanotes_rs::integrity_check::{
+    check_database, repair_database, IntegrityProblem, RepairAction,
+};
+use relanotes_rs::schema::nodes;
+use relanotes_rs::storage::Storage;
+
+// Heart (regular) > Parts (divisor) > Ventricle (inherited) and a broken node of each kind
+fn setup_broken_database() -> (Fixture, i32) {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let parts = add_node(&mut tree, "Parts", None, Some(heart), NodeType::Divisor);
+    add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(parts),
+        NodeType::Inherited,
+    );
+
+    let (subgroup_id, other_subgroup_id) = (fixture.subgroup_id, fixture.other_subgroup_id);
+    insert_raw_node(
+        &fixture,
+        100,
+        "Mnemonic",
+        Some(999),
+        subgroup_id,
+        "sticky_notes",
+    );
+    insert_raw_node(&fixture, 101, "Atrium", None, subgroup_id, "inherited");
+    insert_raw_node(&fixture, 102, "Unknown", None, other_subgroup_id, "missing");
+    insert_raw_node(&fixture, 103, "", Some(998), subgroup_id, "symlinks");
+    // The symlink is inserted before its source, so the triggers don't see it
+    insert_raw_node(&fixture, 104, "", Some(105), subgroup_id, "symlinks");
+    insert_raw_node(&fixture, 105, "Lungs", None, subgroup_id, "regular");
+    insert_raw_node(&fixture, 106, "Wall", Some(107), subgroup_id, "inherited");
+    insert_raw_node(&fixture, 107, "Layer", Some(106), subgroup_id, "inherited");
+    insert_raw_node(&fixture, 108, "Brain", None, 999, "regular");
+    // The duplicate is inserted before the divisor linking it to Heart, so the triggers don't
+    // see it
+    insert_raw_node(
+        &fixture,
+        109,
+        "Ventricle",
+        Some(110),
+        subgroup_id,
+        "inherited",
+    );
+    insert_raw_node(
+        &fixture,
+        110,
+        "Chambers",
+        Some(heart),
+        subgroup_id,
+        "divisor",
+    );
+    // Content that has to survive the repairs
+    insert_raw_node(
+        &fixture,
+        111,
+        "Rhythm",
+        Some(103),
+        subgroup_id,
+        "sticky_notes",
+    );
+    for query in [
+        "pragma foreign_keys = off",
+        "insert into subgroups (id, group_id, name) values (50, 999, 'Lost')",
+        "pragma foreign_keys = on",
+    ] {
+        diesel::sql_query(query)
+            .execute(&*fixture.storage.connection())
+            .unwrap();
+    }
+    insert_raw_node(&fixture, 112, "Kidney", None, 50, "regular");
+    diesel::sql_query("update nodes set description = 'Filters the blood' where id = 112")
+        .execute(&*fixture.storage.connection())
+        .unwrap();
+    (fixture, heart)
+}
+
+#[test]
+fn clean_database_has_no_problems() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    add_node(
+        &mut tree,
+        "Mnemonic",
+        None,
+        Some(heart),
+        NodeType::StickyNotes,
+    );
+    assert_eq!(check_database(&*fixture.storage).unwrap(), vec![]);
+}
+
+#[test]
+fn finds_every_problem() {
+    let (fixture, heart) = setup_broken_database();
+    let problems = check_database(&*fixture.storage).unwrap();
+    let expected = vec![
+        IntegrityProblem::SubGroupWithoutGroup {
+            subgroup_id: 50,
+            group_id: 999,
+        },
+        IntegrityProblem::OrphanedLink {
+            node_id: 100,
+            linked_to_id: 999,
+        },
+        IntegrityProblem::NodeWithoutOwner { node_id: 101 },
+        IntegrityProblem::TypelessNode {
+            node_id: 102,
+            type_id: 999,
+        },
+        IntegrityProblem::DanglingSymLink {
+            node_id: 103,
+            source_node_id: Some(998),
+        },
+        IntegrityProblem::SymLinkToSameSubGroup {
+            node_id: 104,
+            source_node_id: 105,
+        },
+        IntegrityProblem::NodeWithoutSubGroup {
+            node_id: 108,
+            subgroup_id: 999,
+        },
+        IntegrityProblem::Cycle {
+            node_ids: vec![106, 107],
+        },
+        IntegrityProblem::DuplicateName {
+            node_ids: vec![heart + 2, 109],
+            name: "Ventricle".into(),
+        },
+    ];
+    assert_eq!(problems, expected);
+}
+
+#[test]
+fn repairs_what_can_be_repaired() {
+    let (fixture, _) = setup_broken_database();
+    let repairs = repair_database(&*fixture.storage).unwrap();
+    let lost_and_found_id = nodes::table
+        .filter(nodes::name.eq("lost+found (Anatomy)"))
+        .select(nodes::id)
+        .first::<i32>(&*fixture.storage.connection())
+        .unwrap();
+    let moved = RepairAction::MovedToLostAndFound { lost_and_found_id };
+    let action_of = |node_id: i32| {
+        repairs
+            .iter()
+            .find(|repair| match &repair.problem {
+                IntegrityProblem::SubGroupWithoutGroup { subgroup_id, .. } => {
+                    *subgroup_id == node_id
+                }
+                IntegrityProblem::OrphanedLink { node_id: id, .. }
+                | IntegrityProblem::NodeWithoutOwner { node_id: id }
+                | IntegrityProblem::TypelessNode { node_id: id, .. }
+                | IntegrityProblem::DanglingSymLink { node_id: id, .. }
+                | IntegrityProblem::SymLinkToSameSubGroup { node_id: id, .. }
+                | IntegrityProblem::NodeWithoutSubGroup { node_id: id, .. } => *id == node_id,
+                IntegrityProblem::Cycle { node_ids } => node_ids.contains(&node_id),
+                _ => false,
+            })
+            .map(|repair| repair.action.clone())
+    };
+    assert_eq!(action_of(100), Some(moved.clone()));
+    assert_eq!(action_of(101), Some(moved.clone()));
+    assert_eq!(action_of(102), Some(RepairAction::NotRepaired));
+    assert_eq!(action_of(103), Some(RepairAction::Deleted));
+    assert_eq!(action_of(104), Some(RepairAction::Deleted));
+    assert_eq!(action_of(106), Some(moved));
+    let groups = fixture.storage.load_groups().unwrap();
+    let lost_and_found_group = groups.iter().find(|g| g.name == "lost+found").unwrap();
+    assert_eq!(
+        action_of(50),
+        Some(RepairAction::MovedToLostAndFoundGroup {
+            group_id: lost_and_found_group.id
+        })
+    );
+    let subgroups = fixture
+        .storage
+        .load_subgroups(Some(lost_and_found_group.id))
+        .unwrap();
+    let lost_and_found_subgroup = subgroups
+        .iter()
+        .find(|s| s.name == "lost+found (999)")
+        .unwrap();
+    assert_eq!(
+        action_of(108),
+        Some(RepairAction::MovedToLostAndFoundSubGroup {
+            subgroup_id: lost_and_found_subgroup.id
+        })
+    );
+    // Nothing with content was deleted
+    let kidney = fixture.storage.get_node(112).unwrap().unwrap();
+    assert_eq!(
+        (kidney.subgroup_id, kidney.description.as_deref()),
+        (50, Some("Filters the blood"))
+    );
+    assert!(subgroups.iter().any(|s| s.id == 50));
+    let rhythm = fixture.storage.get_node(111).unwrap().unwrap();
+    assert_eq!(rhythm.linked_to_id, Some(lost_and_found_id));
+
+    // Only the problems that need the user are left
+    let problems = check_database(&*fixture.storage).unwrap();
+    assert_eq!(problems.len(), 2);
+    assert!(matches!(
+        problems[0],
+        IntegrityProblem::TypelessNode { node_id: 102, .. }
+    ));
+    assert!(matches!(
+        problems[1],
+        IntegrityProblem::DuplicateName { .. }
+    ));
+    let tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    assert_eq!(
+        tree.get_node_loaded_children(&lost_and_found_id)
+            .unwrap()
+            .len(),
+        4
+    );
+}
+
+#[test]
+fn cycle_of_a_missing_subgroup_is_repaired_in_the_lost_and_found_subgroup() {
+    let fixture = setup();
+    insert_raw_node(&fixture, 200, "Rhythm", Some(201), 999, "sticky_notes");
+    insert_raw_node(&fixture, 201, "Pulse", Some(200), 999, "sticky_notes");
+
+    let repairs = repair_database(&*fixture.storage).unwrap();
+    let rhythm = fixture.storage.get_node(200).unwrap().unwrap();
+    let pulse = fixture.storage.get_node(201).unwrap().unwrap();
+    assert_eq!(rhythm.subgroup_id, pulse.subgroup_id);
+    let lost_and_found = fixture
+        .storage
+        .get_node(rhythm.linked_to_id.unwrap())
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        (lost_and_found.name.as_str(), lost_and_found.subgroup_id),
+        ("lost+found (lost+found (999))", rhythm.subgroup_id)
+    );
+    assert_eq!(pulse.linked_to_id, Some(200));
+    assert!(repairs.iter().any(|repair| repair.problem
+        == IntegrityProblem::Cycle {
+            node_ids: vec![200, 201]
+        }
+        && repair.action
+            == RepairAction::MovedToLostAndFound {
+                lost_and_found_id: lost_and_found.id
+            }));
+    assert!(repairs
+        .iter()
+        .all(|repair| repair.action != RepairAction::NotRepaired));
+    assert_eq!(check_database(&*fixture.storage).unwrap(), []);
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use common::{add_node, insert_raw_node, load_tree, setup};
+use relanotes_rs::abstracts::Loadable;
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::{NodeType, NodesTree, QuarantineReason};
+
+#[test]
+fn broken_nodes_are_quarantined() {
+    let fixture = setup();
+    let subgroup_id = fixture.subgroup_id;
+    let mut tree = load_tree(&fixture.storage, subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let ventricle = add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+    insert_raw_node(&fixture, 100, "Unknown", None, subgroup_id, "missing");
+    insert_raw_node(&fixture, 101, "Mnemonic", None, subgroup_id, "sticky_notes");
+    insert_raw_node(&fixture, 102, "", None, subgroup_id, "symlinks");
+    insert_raw_node(&fixture, 103, "", Some(999), subgroup_id, "symlinks");
+    insert_raw_node(&fixture, 104, "Wall", Some(105), subgroup_id, "inherited");
+    insert_raw_node(&fixture, 105, "Layer", Some(104), subgroup_id, "inherited");
+    // The child of the cycle stays usable
+    insert_raw_node(
+        &fixture,
+        106,
+        "Note",
+        Some(104),
+        subgroup_id,
+        "sticky_notes",
+    );
+
+    let mut tree = NodesTree::new(fixture.storage.clone(), subgroup_id);
+    let report = tree.load_with_report().unwrap();
+    assert_eq!(report.loaded, vec![heart, ventricle, 106]);
+    let reasons = report
+        .quarantined
+        .iter()
+        .map(|q| (q.node.id, q.reason.clone()))
+        .collect::<Vec<(i32, QuarantineReason)>>();
+    assert_eq!(
+        reasons,
+        vec![
+            (100, QuarantineReason::UnknownNodeType(999)),
+            (101, QuarantineReason::MissingOwner),
+            (102, QuarantineReason::MissingSymLinkSource(None)),
+            (103, QuarantineReason::MissingSymLinkSource(Some(999))),
+            (104, QuarantineReason::Cycle),
+            (105, QuarantineReason::Cycle),
+        ]
+    );
+    assert!(!report.is_clean());
+    assert_eq!(
+        tree.unique_path(ventricle).unwrap().to_string(),
+        "Heart>Ventricle"
+    );
+
+    // The report is kept by the tree when it's loaded as any other Loadable
+    let mut tree = NodesTree::new(fixture.storage.clone(), subgroup_id);
+    tree.load().unwrap();
+    assert_eq!(tree.get_load_report().quarantined.len(), 6);
+}
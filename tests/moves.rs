@@ -0,0 +1,212 @@
+mod common;
+
+use common::{add_node, insert_raw_node, load_groups, load_tree, setup};
+use relanotes_rs::errors::RelanotesError;
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::{NodeType, RelanotesValidationRejection};
+
+#[test]
+fn moved_node_changes_the_children_of_both_parents() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let lungs = add_node(&mut tree, "Lungs", None, None, NodeType::Regular);
+    let ventricle = add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+    let wall = add_node(
+        &mut tree,
+        "Wall",
+        None,
+        Some(ventricle),
+        NodeType::Inherited,
+    );
+
+    tree.move_node(ventricle, Some(lungs)).unwrap();
+    assert!(tree.get_node_loaded_children(&heart).unwrap().is_empty());
+    assert_eq!(tree.get_node_loaded_children(&lungs).unwrap(), [ventricle]);
+    // The inherited descendants follow to the new unique path
+    assert_eq!(tree.nodes_map[&wall].unique_path, [lungs, ventricle, wall]);
+
+    let tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    assert_eq!(tree.nodes_map[&ventricle].parent_node_id, Some(lungs));
+    assert_eq!(tree.nodes_map[&wall].unique_path, [lungs, ventricle, wall]);
+}
+
+#[test]
+fn node_cant_be_moved_into_its_own_subtree() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let ventricle = add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+    let wall = add_node(
+        &mut tree,
+        "Wall",
+        None,
+        Some(ventricle),
+        NodeType::Inherited,
+    );
+
+    for new_parent in [heart, wall] {
+        match tree.move_node(heart, Some(new_parent)) {
+            Err(RelanotesError::CyclicMove {
+                node_id,
+                new_parent_id,
+            }) => assert_eq!((node_id, new_parent_id), (heart, new_parent)),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+    assert_eq!(tree.get_roots(), [heart]);
+    assert_eq!(tree.nodes_map[&wall].parent_node_id, Some(ventricle));
+}
+
+#[test]
+fn moves_are_validated_against_the_new_owner() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let lungs = add_node(&mut tree, "Lungs", None, None, NodeType::Regular);
+    add_node(&mut tree, "Wall", None, Some(heart), NodeType::Inherited);
+    let wall = add_node(&mut tree, "Wall", None, Some(lungs), NodeType::Inherited);
+    let mnemonic = add_node(
+        &mut tree,
+        "Mnemonic",
+        None,
+        Some(lungs),
+        NodeType::StickyNotes,
+    );
+
+    assert!(matches!(
+        tree.move_node(wall, Some(heart)),
+        Err(RelanotesError::ValidationRejection(_))
+    ));
+    assert!(matches!(
+        tree.move_node(mnemonic, None),
+        Err(RelanotesError::ValidationRejection(_))
+    ));
+    assert_eq!(
+        tree.get_node_loaded_children(&lungs).unwrap(),
+        [wall, mnemonic]
+    );
+    tree.move_node(mnemonic, Some(heart)).unwrap();
+    assert_eq!(tree.nodes_map[&mnemonic].parent_node_id, Some(heart));
+}
+
+#[test]
+fn moved_divisor_takes_its_children_into_the_new_namespace() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+    let lungs = add_node(&mut tree, "Lungs", None, None, NodeType::Regular);
+    let parts = add_node(&mut tree, "Parts", None, Some(lungs), NodeType::Divisor);
+    let ventricle = add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(parts),
+        NodeType::Inherited,
+    );
+
+    match tree.move_node(parts, Some(heart)) {
+        Err(RelanotesError::ValidationRejection(
+            RelanotesValidationRejection::DuplicateInheritedNode(name),
+        )) => assert_eq!(name, "Ventricle"),
+        other => panic!("Unexpected result {:?}", other),
+    }
+    // The inherited node can't be a root either
+    assert!(matches!(
+        tree.move_node(parts, None),
+        Err(RelanotesError::ValidationRejection(_))
+    ));
+    assert_eq!(tree.get_node_loaded_children(&lungs).unwrap(), [parts]);
+
+    let tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    assert_eq!(tree.nodes_map[&parts].parent_node_id, Some(lungs));
+    assert_eq!(tree.nodes_map[&ventricle].unique_path, [lungs, ventricle]);
+}
+
+#[test]
+fn divisor_moved_to_another_subgroup_is_validated_with_its_children() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let lungs = add_node(&mut tree, "Lungs", None, None, NodeType::Regular);
+    let parts = add_node(&mut tree, "Parts", None, Some(lungs), NodeType::Divisor);
+    add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(parts),
+        NodeType::Inherited,
+    );
+    let mut other_tree = load_tree(&fixture.storage, fixture.other_subgroup_id);
+    let heart = add_node(&mut other_tree, "Heart", None, None, NodeType::Regular);
+    add_node(
+        &mut other_tree,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+
+    let mut groups = load_groups(&fixture);
+    match groups.move_subtree(parts, fixture.other_subgroup_id, Some(heart)) {
+        Err(RelanotesError::ValidationRejection(
+            RelanotesValidationRejection::DuplicateInheritedNode(name),
+        )) => assert_eq!(name, "Ventricle"),
+        other => panic!("Unexpected result {:?}", other),
+    }
+    let tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    assert_eq!(tree.nodes_map[&parts].parent_node_id, Some(lungs));
+}
+
+#[test]
+fn subtree_with_unloaded_children_is_not_split() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let ventricle = add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+    insert_raw_node(
+        &fixture,
+        100,
+        "Unknown",
+        Some(ventricle),
+        fixture.subgroup_id,
+        "missing",
+    );
+
+    let mut groups = load_groups(&fixture);
+    match groups.move_subtree(heart, fixture.other_subgroup_id, None) {
+        Err(RelanotesError::NodeIsReferenced {
+            node_id,
+            referenced_by,
+        }) => assert_eq!((node_id, referenced_by), (heart, vec![100])),
+        other => panic!("Unexpected result {:?}", other),
+    }
+    let tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    assert_eq!(tree.get_roots(), [heart]);
+    assert_eq!(tree.get_load_report().quarantined[0].node.id, 100);
+    let other_tree = load_tree(&fixture.storage, fixture.other_subgroup_id);
+    assert!(other_tree.nodes_map.is_empty());
+}
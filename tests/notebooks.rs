@@ -0,0 +1,107 @@
+mod common;
+
+use common::TempDir;
+use relanotes_rs::errors::RelanotesError;
+use relanotes_rs::notebooks::{list_notebooks, Notebooks, MAX_RECENT_NOTEBOOKS};
+use std::path::PathBuf;
+
+fn recent_paths(notebooks: &Notebooks) -> Vec<PathBuf> {
+    notebooks
+        .recent()
+        .iter()
+        .map(|notebook| notebook.path.clone())
+        .collect()
+}
+
+#[test]
+fn notebooks_are_used_side_by_side() {
+    let directory = TempDir::new("courses");
+    let mut notebooks = Notebooks::load(directory.path.join("recent.json")).unwrap();
+    let anatomy_path = directory.path.join("anatomy.db");
+    let physiology_path = directory.path.join("physiology.db");
+    let mut anatomy = notebooks.create(&anatomy_path).unwrap();
+    let mut physiology = notebooks.create(&physiology_path).unwrap();
+    anatomy
+        .create_group("Medicine")
+        .unwrap()
+        .create_subgroup("Heart")
+        .unwrap();
+    physiology
+        .create_group("Medicine")
+        .unwrap()
+        .create_subgroup("Circulation")
+        .unwrap();
+
+    let heart = anatomy
+        .group("Medicine")
+        .unwrap()
+        .subgroup("Heart")
+        .unwrap();
+    let circulation = physiology
+        .group("Medicine")
+        .unwrap()
+        .subgroup("Circulation")
+        .unwrap();
+    // Separate databases, so the first subgroups of both have the same id
+    assert_eq!(heart.subgroup.id, circulation.subgroup.id);
+    assert!(anatomy
+        .group("Medicine")
+        .unwrap()
+        .subgroup("Circulation")
+        .is_err());
+    assert_eq!(anatomy.path(), Some(anatomy_path.as_path()));
+
+    // The registry is not a notebook
+    assert_eq!(
+        list_notebooks(&directory.path).unwrap(),
+        [anatomy_path, physiology_path]
+    );
+}
+
+#[test]
+fn recent_notebooks_are_remembered() {
+    let directory = TempDir::new("recent");
+    let registry_path = directory.path.join("recent.json");
+    let mut notebooks = Notebooks::load(&registry_path).unwrap();
+    let paths = (0..=MAX_RECENT_NOTEBOOKS)
+        .map(|i| directory.path.join(format!("course {}.db", i)))
+        .collect::<Vec<_>>();
+    for path in &paths {
+        notebooks.create(path).unwrap();
+    }
+    let first = paths[0].canonicalize().unwrap();
+    let last = paths[MAX_RECENT_NOTEBOOKS].canonicalize().unwrap();
+    assert_eq!(notebooks.recent().len(), MAX_RECENT_NOTEBOOKS);
+    assert_eq!(notebooks.recent()[0].path, last);
+    assert!(!recent_paths(&notebooks).contains(&first));
+
+    // Other paths of the same file are the same notebook
+    notebooks
+        .open(directory.path.join(".").join("course 0.db"))
+        .unwrap();
+    let reloaded = Notebooks::load(&registry_path).unwrap();
+    assert_eq!(reloaded.recent(), notebooks.recent());
+    assert_eq!(recent_paths(&reloaded)[..2], [first.clone(), last]);
+
+    notebooks.forget(&paths[0]).unwrap();
+    assert!(!recent_paths(&Notebooks::load(&registry_path).unwrap()).contains(&first));
+    assert!(paths[0].exists());
+}
+
+#[test]
+fn missing_and_existing_notebooks_are_reported() {
+    let directory = TempDir::new("errors");
+    let mut notebooks = Notebooks::load(directory.path.join("recent.json")).unwrap();
+    let path = directory.path.join("anatomy.db");
+    match notebooks.open(&path) {
+        Err(RelanotesError::NotebookNotFound(missing)) => assert_eq!(missing, path),
+        other => panic!("Unexpected result {:?}", other.map(|_| ())),
+    }
+    assert!(!path.exists());
+    notebooks.create(&path).unwrap();
+    match notebooks.create(&path) {
+        Err(RelanotesError::NotebookExists(existing)) => assert_eq!(existing, path),
+        other => panic!("Unexpected result {:?}", other.map(|_| ())),
+    }
+    assert_eq!(notebooks.recent().len(), 1);
+}
@@ -0,0 +1,248 @@
+mod common;
+
+use common::{add_node, load_groups, load_tree, setup};
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::{
+    NodeType, ParsedPath, ParsedSegment, ParsedSegmentKind, PathResolutionError,
+};
+
+#[test]
+fn full_path_is_the_unique_path_with_the_relative_one() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let ventricle = add_node(
+        &mut tree,
+        "Left ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+    let mnemonic = add_node(
+        &mut tree,
+        "Mnemonic",
+        None,
+        Some(ventricle),
+        NodeType::StickyNotes,
+    );
+    let rhyme = add_node(
+        &mut tree,
+        "Rhyme",
+        None,
+        Some(mnemonic),
+        NodeType::StickyNotes,
+    );
+
+    assert_eq!(
+        tree.unique_path(rhyme).unwrap().to_string(),
+        "Heart>Left ventricle"
+    );
+    assert_eq!(
+        tree.relative_path(rhyme).unwrap().to_string(),
+        "::Mnemonic::Rhyme"
+    );
+    assert_eq!(
+        tree.full_path(rhyme).unwrap().to_string(),
+        "Heart>Left ventricle::Mnemonic::Rhyme"
+    );
+    assert_eq!(
+        tree.full_path(rhyme).unwrap().node_ids(),
+        [heart, ventricle, mnemonic, rhyme]
+    );
+    // Only sticky notes have relative paths
+    assert!(tree.relative_path(ventricle).unwrap().is_empty());
+    assert_eq!(
+        tree.full_path(ventricle).unwrap().to_string(),
+        "Heart>Left ventricle"
+    );
+    assert!(tree.full_path(rhyme + 1).is_none());
+}
+
+#[test]
+fn separators_in_names_are_escaped() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let pressure = add_node(
+        &mut tree,
+        "Systolic/diastolic",
+        None,
+        None,
+        NodeType::Regular,
+    );
+    let rule = add_node(
+        &mut tree,
+        "Normal > 90",
+        None,
+        Some(pressure),
+        NodeType::Inherited,
+    );
+    let note = add_node(
+        &mut tree,
+        "Note: C:\\",
+        None,
+        Some(rule),
+        NodeType::StickyNotes,
+    );
+
+    assert_eq!(
+        tree.full_path(note).unwrap().to_string(),
+        "Systolic\\/diastolic>Normal \\> 90::Note\\: C\\:\\\\"
+    );
+}
+
+#[test]
+fn formatted_paths_are_parsed_back() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let pressure = add_node(
+        &mut tree,
+        "Systolic/diastolic",
+        None,
+        None,
+        NodeType::Regular,
+    );
+    let rule = add_node(
+        &mut tree,
+        "Normal > 90",
+        None,
+        Some(pressure),
+        NodeType::Inherited,
+    );
+    let note = add_node(
+        &mut tree,
+        "Note: C:\\",
+        None,
+        Some(rule),
+        NodeType::StickyNotes,
+    );
+
+    let formatted = tree.full_path(note).unwrap().to_string();
+    let parsed = ParsedPath::parse(&format!("Anatomy/{}", formatted)).unwrap();
+    assert_eq!(parsed.subgroup_name.as_deref(), Some("Anatomy"));
+    assert_eq!(
+        parsed.segments,
+        [
+            ParsedSegment {
+                kind: ParsedSegmentKind::Start,
+                name: "Systolic/diastolic".into()
+            },
+            ParsedSegment {
+                kind: ParsedSegmentKind::Inherited,
+                name: "Normal > 90".into()
+            },
+            ParsedSegment {
+                kind: ParsedSegmentKind::StickyNote,
+                name: "Note: C:\\".into()
+            },
+        ]
+    );
+    assert_eq!(tree.resolve_path(&formatted).unwrap(), note);
+}
+
+#[test]
+fn malformed_paths_are_rejected() {
+    for path in [
+        "/Heart",
+        " /Heart",
+        "Heart>",
+        "Heart>>Ventricle",
+        "Heart\\",
+        "A/B/Heart",
+    ] {
+        assert!(
+            matches!(
+                ParsedPath::parse(path),
+                Err(PathResolutionError::InvalidPath(_))
+            ),
+            "{}",
+            path
+        );
+    }
+}
+
+#[test]
+fn missing_segments_are_reported_with_their_position() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+
+    match tree.resolve_path("Heart>Ventricle>Wall") {
+        Err(PathResolutionError::SegmentNotFound { segment, name }) => {
+            assert_eq!((segment, &name[..]), (2, "Wall"))
+        }
+        other => panic!("Unexpected result {:?}", other),
+    }
+    // Inherited nodes don't start the path
+    assert!(matches!(
+        tree.resolve_path("Ventricle"),
+        Err(PathResolutionError::SegmentNotFound { segment: 0, .. })
+    ));
+}
+
+#[test]
+fn paths_continue_through_symlinks_to_their_sources() {
+    let fixture = setup();
+    let mut anatomy = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut anatomy, "Heart", None, None, NodeType::Regular);
+    let ventricle = add_node(
+        &mut anatomy,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+    let rhythm = add_node(
+        &mut anatomy,
+        "Rhythm",
+        None,
+        Some(heart),
+        NodeType::StickyNotes,
+    );
+    let mut physiology = load_tree(&fixture.storage, fixture.other_subgroup_id);
+    let symlink = add_node(&mut physiology, "", None, Some(heart), NodeType::SymLink);
+    let tempo = add_node(
+        &mut physiology,
+        "Tempo",
+        None,
+        Some(symlink),
+        NodeType::StickyNotes,
+    );
+    let other_rhythm = add_node(
+        &mut physiology,
+        "Rhythm",
+        None,
+        Some(symlink),
+        NodeType::StickyNotes,
+    );
+
+    let groups = load_groups(&fixture);
+    let group = &groups.groups_map[&fixture.group_id];
+    assert_eq!(
+        group.resolve_path("Physiology/Heart>Ventricle").unwrap(),
+        ventricle
+    );
+    assert_eq!(
+        group.resolve_path("Physiology/Heart::Tempo").unwrap(),
+        tempo
+    );
+    // The symlink found together with its source is the same node
+    assert_eq!(group.resolve_path("Heart").unwrap(), heart);
+    assert_eq!(group.resolve_path("Physiology/Heart").unwrap(), symlink);
+    match group.resolve_path("Heart::Rhythm") {
+        Err(PathResolutionError::AmbiguousSegment {
+            segment,
+            name,
+            candidates,
+        }) => {
+            assert_eq!((segment, &name[..]), (1, "Rhythm"));
+            assert_eq!(candidates, [rhythm, other_rhythm]);
+        }
+        other => panic!("Unexpected result {:?}", other),
+    }
+}
@@ -0,0 +1,237 @@
+#![cfg(feature = "postgres")]
+// Run with `POSTGRES_TEST_URL=postgres://... cargo test --features postgres`
+
+mod common;
+
+use common::{
+    add_node, assert_divisors_are_transparent_for_owners, assert_foreign_keys_are_enforced,
+    load_tree, setup_postgres,
+};
+use diesel::prelude::*;
+use diesel::result::Error;
+use relanotes_rs::abstracts::Loadable;
+use relanotes_rs::database_setup::{plan_postgres_database_setup, setup_postgres_database};
+use relanotes_rs::errors::RelanotesError;
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::node_types::NodeTypesRegistry;
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::{
+    DeletionStrategy, NodeType, RelanotesValidationRejection,
+};
+use relanotes_rs::groups_mod::Groups;
+use relanotes_rs::schema::nodes;
+use relanotes_rs::storage::Storage;
+
+#[test]
+fn nodes_are_created_moved_and_deleted() {
+    let (fixture, _database) = setup_postgres();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", Some("Pumps"), None, NodeType::Regular);
+    let lungs = add_node(&mut tree, "Lungs", None, None, NodeType::Regular);
+    let ventricle = add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+    add_node(
+        &mut tree,
+        "Wall",
+        None,
+        Some(ventricle),
+        NodeType::Inherited,
+    );
+    tree.move_node(ventricle, Some(lungs)).unwrap();
+
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    assert_eq!(tree.get_roots(), vec![heart, lungs]);
+    assert_eq!(
+        tree.get_node_loaded_children(&lungs).unwrap(),
+        vec![ventricle]
+    );
+    assert_eq!(tree.nodes_map[&heart].node.get_description(), Some("Pumps"));
+    let deleted = tree.delete_node(lungs, DeletionStrategy::Cascade).unwrap();
+    assert_eq!(deleted.len(), 3);
+    assert_eq!(fixture.storage.load_nodes(None).unwrap().len(), 1);
+}
+
+#[test]
+fn violations_are_mapped_to_validation_rejections() {
+    let (fixture, _database) = setup_postgres();
+    let storage = &fixture.storage;
+    let mut tree = load_tree(storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let ventricle = add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+    // Written by another client after the tree was loaded
+    let sticky_notes = NodeTypesRegistry::load(&**storage)
+        .unwrap()
+        .get_type_id(&NodeType::StickyNotes)
+        .unwrap();
+    diesel::insert_into(nodes::table)
+        .values((
+            nodes::name.eq("Mnemonic"),
+            nodes::linked_to_id.eq(heart),
+            nodes::subgroup_id.eq(fixture.subgroup_id),
+            nodes::type_id.eq(sticky_notes),
+        ))
+        .execute(&*storage.connection())
+        .unwrap();
+
+    let result = tree.create_node("Mnemonic", None, Some(heart), NodeType::StickyNotes);
+    assert!(matches!(
+        result,
+        Err(RelanotesError::ValidationRejection(
+            RelanotesValidationRejection::DuplicateStickyNote(ref name)
+        )) if name == "Mnemonic"
+    ));
+    match storage.set_linked_to_id(&[heart], Some(ventricle)) {
+        Err(Error::DatabaseError(_, info)) => assert_eq!(info.message(), "CyclicLink"),
+        result => panic!("Expected a constraint violation, got {:?}", result),
+    }
+}
+
+#[test]
+fn divisors_are_transparent_for_the_uniqueness_in_the_owner() {
+    let (fixture, _database) = setup_postgres();
+    assert_divisors_are_transparent_for_owners(&fixture);
+}
+
+#[test]
+fn foreign_keys_are_enforced() {
+    let (fixture, _database) = setup_postgres();
+    assert_foreign_keys_are_enforced(&fixture);
+}
+
+#[test]
+fn moving_subtree_next_to_its_symlinks_merges_them() {
+    let (fixture, _database) = setup_postgres();
+    let storage = &fixture.storage;
+    let mut tree = load_tree(storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let mut other_tree = load_tree(storage, fixture.other_subgroup_id);
+    let symlink = add_node(&mut other_tree, "", None, Some(heart), NodeType::SymLink);
+    let note = add_node(
+        &mut other_tree,
+        "Rhythm",
+        None,
+        Some(symlink),
+        NodeType::StickyNotes,
+    );
+
+    let mut groups = Groups::new(storage.clone());
+    groups.load().unwrap();
+    let group = groups.groups_map.get_mut(&fixture.group_id).unwrap();
+    group.subgroups.load().unwrap();
+    for subgroup in group.subgroups.subgroups_map.values_mut() {
+        subgroup.nodes.load().unwrap();
+    }
+    groups
+        .move_subtree(heart, fixture.other_subgroup_id, None)
+        .unwrap();
+
+    assert!(storage.get_node(symlink).unwrap().is_none());
+    let note = storage.get_node(note).unwrap().unwrap();
+    assert_eq!(note.linked_to_id, Some(heart));
+    assert_eq!(note.subgroup_id, fixture.other_subgroup_id);
+}
+
+#[test]
+fn setup_keeps_the_node_type_ids() {
+    let (fixture, _database) = setup_postgres();
+    let type_ids = |storage: &dyn Storage| {
+        let mut type_ids = storage
+            .load_node_types()
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.id, t.value))
+            .collect::<Vec<(i32, String)>>();
+        type_ids.sort();
+        type_ids
+    };
+    let before = type_ids(&*fixture.storage);
+    setup_postgres_database(&fixture.storage).unwrap();
+    assert_eq!(type_ids(&*fixture.storage), before);
+    assert_eq!(before.len(), 5);
+}
+
+#[test]
+fn loading_and_validation_queries_use_the_indexes() {
+    let (fixture, _database) = setup_postgres();
+    // The tables are too small for the planner to prefer the indexes otherwise
+    diesel::sql_query("set enable_seqscan = off")
+        .execute(&*fixture.storage.connection())
+        .unwrap();
+    let plans = fixture.storage.explain_queries().unwrap();
+    assert_eq!(plans.len(), 5);
+    for plan in &plans {
+        assert!(!plan.plan.is_empty(), "{:?}", plan);
+        assert!(
+            plan.plan.iter().all(|line| !line.contains("Seq Scan")),
+            "{:?}",
+            plan
+        );
+    }
+}
+
+#[test]
+fn setup_of_an_up_to_date_database_changes_nothing() {
+    let (fixture, _database) = setup_postgres();
+    let changes = plan_postgres_database_setup(&fixture.storage).unwrap();
+    assert!(changes.is_empty(), "{:?}", changes);
+    assert!(changes.schema_version.is_some());
+    assert_eq!(changes.seed_version, 2);
+}
+
+#[test]
+fn changes_are_logged_for_the_refresh_and_stale_edits_conflict() {
+    let (fixture, _database) = setup_postgres();
+    let mut first = load_tree(&fixture.storage, fixture.subgroup_id);
+    let mut second = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut first, "Heart", None, None, NodeType::Regular);
+    let lungs = add_node(&mut first, "Lungs", None, None, NodeType::Regular);
+    first.move_node(lungs, Some(heart)).unwrap();
+    assert_eq!(second.refresh().unwrap().nodes, [heart, lungs]);
+    assert_eq!(second.nodes_map[&lungs].parent_node_id, Some(heart));
+
+    first
+        .update_name_and_description(heart, "Heart".into(), Some("Pumps".into()))
+        .unwrap();
+    match second.update_name_and_description(heart, "Heart".into(), Some("Muscle".into())) {
+        Err(RelanotesError::EditConflicts(conflicts)) => {
+            assert_eq!(
+                (conflicts[0].node_id, conflicts[0].loaded_version),
+                (heart, 1)
+            );
+        }
+        other => panic!("Unexpected result {:?}", other),
+    }
+    assert_eq!(second.refresh().unwrap().nodes, [heart]);
+    second
+        .update_name_and_description(heart, "Heart".into(), Some("Muscle".into()))
+        .unwrap();
+    assert!(second.refresh().unwrap().is_empty());
+}
+
+#[test]
+fn pruned_changes_are_reloaded_by_the_refresh() {
+    let (fixture, _database) = setup_postgres();
+    let mut first = load_tree(&fixture.storage, fixture.subgroup_id);
+    let mut second = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut first, "Heart", None, None, NodeType::Regular);
+    let last_change = fixture.storage.last_change().unwrap();
+    fixture.storage.prune_changes(last_change).unwrap();
+    // Pruning again up to the older change keeps the mark
+    fixture.storage.prune_changes(last_change - 1).unwrap();
+    assert_eq!(fixture.storage.pruned_change().unwrap(), last_change);
+    assert_eq!(fixture.storage.last_change().unwrap(), last_change);
+    assert!(fixture.storage.load_changes(0).unwrap().is_empty());
+
+    let report = second.refresh().unwrap();
+    assert!(report.reloaded);
+    assert_eq!(second.get_roots(), [heart]);
+}
@@ -0,0 +1,316 @@
+mod common;
+
+use common::{add_node, load_tree, setup};
+use diesel::result::Error;
+use parking_lot::Mutex;
+use relanotes_rs::abstracts::Loadable;
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::{DeletionStrategy, NodeType};
+use relanotes_rs::groups_mod::Groups;
+use relanotes_rs::models::{
+    ChangeElement, GroupElement, NodeElement, NodeTypeElement, SubGroupElement,
+};
+use relanotes_rs::storage::{NewNode, QueryPlan, SqliteStorage, Storage};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// Counts the calls of every query, the calls are passed to the SQLite storage
+struct CountingStorage {
+    inner: Arc<SqliteStorage>,
+    counts: Mutex<HashMap<&'static str, usize>>,
+    // The depth of the open transactions
+    transactions: AtomicUsize,
+}
+
+impl CountingStorage {
+    fn new(inner: Arc<SqliteStorage>) -> Self {
+        CountingStorage {
+            inner,
+            counts: Mutex::new(HashMap::new()),
+            transactions: AtomicUsize::new(0),
+        }
+    }
+
+    fn count(&self, query: &'static str) {
+        *self.counts.lock().entry(query).or_default() += 1;
+    }
+
+    // The validation reads are counted apart when they are made outside of a transaction
+    fn count_validation(&self, query: &'static str) {
+        self.count(query);
+        if self.transactions.load(Ordering::SeqCst) == 0 {
+            self.count("validation outside of a transaction");
+        }
+    }
+
+    fn take_counts(&self) -> HashMap<&'static str, usize> {
+        std::mem::take(&mut *self.counts.lock())
+    }
+}
+
+impl Storage for CountingStorage {
+    fn run_in_transaction(&self, f: &mut dyn FnMut() -> Result<(), Error>) -> Result<(), Error> {
+        self.transactions.fetch_add(1, Ordering::SeqCst);
+        let result = self.inner.run_in_transaction(f);
+        self.transactions.fetch_sub(1, Ordering::SeqCst);
+        result
+    }
+    fn load_groups(&self) -> Result<Vec<GroupElement>, Error> {
+        self.count("load_groups");
+        self.inner.load_groups()
+    }
+    fn get_group(&self, group_id: i32) -> Result<Option<GroupElement>, Error> {
+        self.count("get_group");
+        self.inner.get_group(group_id)
+    }
+    fn create_group(&self, name: &str) -> Result<GroupElement, Error> {
+        self.count("create_group");
+        self.inner.create_group(name)
+    }
+    fn update_group(&self, group: &GroupElement) -> Result<(), Error> {
+        self.count("update_group");
+        self.inner.update_group(group)
+    }
+    fn delete_group(&self, group_id: i32) -> Result<(), Error> {
+        self.count("delete_group");
+        self.inner.delete_group(group_id)
+    }
+    fn load_subgroups(&self, group_id: Option<i32>) -> Result<Vec<SubGroupElement>, Error> {
+        self.count("load_subgroups");
+        self.inner.load_subgroups(group_id)
+    }
+    fn get_subgroup(&self, subgroup_id: i32) -> Result<Option<SubGroupElement>, Error> {
+        self.count("get_subgroup");
+        self.inner.get_subgroup(subgroup_id)
+    }
+    fn create_subgroup(&self, group_id: i32, name: &str) -> Result<SubGroupElement, Error> {
+        self.count("create_subgroup");
+        self.inner.create_subgroup(group_id, name)
+    }
+    fn update_subgroup(&self, subgroup: &SubGroupElement) -> Result<(), Error> {
+        self.count("update_subgroup");
+        self.inner.update_subgroup(subgroup)
+    }
+    fn delete_subgroup(&self, subgroup_id: i32) -> Result<(), Error> {
+        self.count("delete_subgroup");
+        self.inner.delete_subgroup(subgroup_id)
+    }
+    fn load_node_types(&self) -> Result<Vec<NodeTypeElement>, Error> {
+        self.count("load_node_types");
+        self.inner.load_node_types()
+    }
+    fn save_node_type(&self, node_type: &NodeTypeElement) -> Result<i32, Error> {
+        self.count("save_node_type");
+        self.inner.save_node_type(node_type)
+    }
+    fn seed_version(&self) -> Result<i32, Error> {
+        self.count("seed_version");
+        self.inner.seed_version()
+    }
+
+    fn add_seed_version(&self, version: i32) -> Result<(), Error> {
+        self.count("add_seed_version");
+        self.inner.add_seed_version(version)
+    }
+
+    fn load_nodes(&self, subgroup_id: Option<i32>) -> Result<Vec<NodeElement>, Error> {
+        self.count("load_nodes");
+        self.inner.load_nodes(subgroup_id)
+    }
+    fn get_node(&self, node_id: i32) -> Result<Option<NodeElement>, Error> {
+        self.count("get_node");
+        self.inner.get_node(node_id)
+    }
+    fn get_nodes(&self, node_ids: &[i32]) -> Result<Vec<NodeElement>, Error> {
+        self.count("get_nodes");
+        self.inner.get_nodes(node_ids)
+    }
+    fn lock_nodes(&self, node_ids: &[i32]) -> Result<Vec<NodeElement>, Error> {
+        self.count("lock_nodes");
+        self.inner.lock_nodes(node_ids)
+    }
+    fn create_node(&self, node: &NewNode) -> Result<NodeElement, Error> {
+        self.count("create_node");
+        self.inner.create_node(node)
+    }
+    fn update_node_content(
+        &self,
+        node_id: i32,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<(), Error> {
+        self.count("update_node_content");
+        self.inner.update_node_content(node_id, name, description)
+    }
+    fn set_linked_to_id(&self, node_ids: &[i32], linked_to_id: Option<i32>) -> Result<(), Error> {
+        self.count("set_linked_to_id");
+        self.inner.set_linked_to_id(node_ids, linked_to_id)
+    }
+    fn set_subgroup_id(&self, node_ids: &[i32], subgroup_id: i32) -> Result<(), Error> {
+        self.count("set_subgroup_id");
+        self.inner.set_subgroup_id(node_ids, subgroup_id)
+    }
+    fn delete_nodes(&self, node_ids: &[i32]) -> Result<(), Error> {
+        self.count("delete_nodes");
+        self.inner.delete_nodes(node_ids)
+    }
+    fn find_nodes_in_group(
+        &self,
+        group_id: i32,
+        names: &[&str],
+    ) -> Result<Vec<NodeElement>, Error> {
+        self.count_validation("find_nodes_in_group");
+        self.inner.find_nodes_in_group(group_id, names)
+    }
+    fn find_linked_nodes(&self, node_ids: &[i32]) -> Result<Vec<NodeElement>, Error> {
+        self.count_validation("find_linked_nodes");
+        self.inner.find_linked_nodes(node_ids)
+    }
+    fn last_change(&self) -> Result<i32, Error> {
+        self.count("last_change");
+        self.inner.last_change()
+    }
+    fn load_changes(&self, after: i32) -> Result<Vec<ChangeElement>, Error> {
+        self.count("load_changes");
+        self.inner.load_changes(after)
+    }
+    fn prune_changes(&self, up_to: i32) -> Result<(), Error> {
+        self.count("prune_changes");
+        self.inner.prune_changes(up_to)
+    }
+    fn pruned_change(&self) -> Result<i32, Error> {
+        self.count("pruned_change");
+        self.inner.pruned_change()
+    }
+
+    fn explain_queries(&self) -> Result<Vec<QueryPlan>, Error> {
+        self.inner.explain_queries()
+    }
+}
+
+#[test]
+fn loading_takes_the_same_queries_for_any_number_of_symlinks() {
+    const SYMLINKS: usize = 50;
+    let fixture = setup();
+    let mut physiology = load_tree(&fixture.storage, fixture.other_subgroup_id);
+    let mut anatomy = load_tree(&fixture.storage, fixture.subgroup_id);
+    for i in 0..SYMLINKS {
+        let source = add_node(
+            &mut physiology,
+            &format!("Process {}", i),
+            None,
+            None,
+            NodeType::Regular,
+        );
+        add_node(&mut anatomy, "", None, Some(source), NodeType::SymLink);
+    }
+
+    let storage = Arc::new(CountingStorage::new(fixture.storage.clone()));
+    let mut groups = Groups::new(storage.clone());
+    groups.load().unwrap();
+    let subgroups = &mut groups
+        .groups_map
+        .get_mut(&fixture.group_id)
+        .unwrap()
+        .subgroups;
+    subgroups.load().unwrap();
+    for subgroup in subgroups.subgroups_map.values_mut() {
+        subgroup.nodes.load().unwrap();
+    }
+
+    let counts = storage.take_counts();
+    let expected = [
+        ("load_groups", 1),
+        ("load_subgroups", 1),
+        // Shared by both trees
+        ("load_node_types", 1),
+        ("load_nodes", 2),
+        // Only the subgroup with the symlinks looks up the sources
+        ("get_nodes", 1),
+        // The marks of the refresh, read once by the groups, the subgroups and each tree
+        ("last_change", 4),
+    ];
+    assert_eq!(counts, expected.iter().copied().collect());
+    let anatomy = &subgroups.subgroups_map[&fixture.subgroup_id].nodes;
+    assert_eq!(anatomy.nodes_map.len(), SYMLINKS);
+    assert!(anatomy
+        .nodes_map
+        .values()
+        .all(|graph_node| graph_node.node.get_name().starts_with("Process ")));
+}
+
+#[test]
+fn moves_and_deletions_validate_inside_their_transactions() {
+    let fixture = setup();
+    let storage = Arc::new(CountingStorage::new(fixture.storage.clone()));
+    let mut tree = load_tree(&storage, fixture.subgroup_id);
+    let body = add_node(&mut tree, "Body", None, None, NodeType::Regular);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let lungs = add_node(&mut tree, "Lungs", None, Some(body), NodeType::Regular);
+    add_node(&mut tree, "Alveoli", None, Some(lungs), NodeType::Regular);
+    storage.take_counts();
+
+    tree.move_node(heart, Some(body)).unwrap();
+    tree.delete_node(lungs, DeletionStrategy::LiftChildrenToParent)
+        .unwrap();
+    let counts = storage.take_counts();
+    assert!(counts["find_nodes_in_group"] > 0);
+    assert!(counts["find_linked_nodes"] > 0);
+    assert_eq!(counts.get("validation outside of a transaction"), None);
+}
+
+#[test]
+fn moves_between_groups_validate_inside_their_transactions() {
+    let fixture = setup();
+    let physics = fixture.storage.create_group("Physics").unwrap();
+    let mechanics = fixture
+        .storage
+        .create_subgroup(physics.id, "Mechanics")
+        .unwrap();
+    let mut anatomy = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut anatomy, "Heart", None, None, NodeType::Regular);
+
+    let storage = Arc::new(CountingStorage::new(fixture.storage.clone()));
+    let mut groups = Groups::new(storage.clone());
+    groups.load().unwrap();
+    for group in groups.groups_map.values_mut() {
+        group.subgroups.load().unwrap();
+        for subgroup in group.subgroups.subgroups_map.values_mut() {
+            subgroup.nodes.load().unwrap();
+        }
+    }
+    storage.take_counts();
+
+    groups.move_subtree(heart, mechanics.id, None).unwrap();
+    let counts = storage.take_counts();
+    assert!(counts["find_nodes_in_group"] > 0);
+    assert_eq!(counts.get("validation outside of a transaction"), None);
+}
+
+#[test]
+fn loading_and_validation_queries_use_the_indexes() {
+    let fixture = setup();
+    let plans = fixture.storage.explain_queries().unwrap();
+    let queries = plans.iter().map(|plan| plan.query).collect::<Vec<_>>();
+    assert_eq!(
+        queries,
+        [
+            "load_subgroups",
+            "load_nodes",
+            "get_nodes",
+            "find_nodes_in_group",
+            "find_linked_nodes"
+        ]
+    );
+    for plan in &plans {
+        assert!(!plan.plan.is_empty(), "{:?}", plan);
+        // Older SQLite versions write "SCAN TABLE"
+        assert!(
+            plan.plan
+                .iter()
+                .all(|line| !line.starts_with("SCAN nodes") && !line.starts_with("SCAN TABLE")),
+            "{:?}",
+            plan
+        );
+    }
+}
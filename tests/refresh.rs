@@ -0,0 +1,214 @@
+mod common;
+
+use common::TempFile;
+use relanotes_rs::abstracts::Saveable;
+use relanotes_rs::errors::RelanotesError;
+use relanotes_rs::events::Event;
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::{DeletionStrategy, NodeType, NodesTree};
+use relanotes_rs::storage::MemoryStorage;
+use relanotes_rs::workspace::Workspace;
+use std::sync::Arc;
+
+fn anatomy(workspace: &mut Workspace) -> &mut NodesTree {
+    workspace
+        .group("Medicine")
+        .unwrap()
+        .subgroup("Anatomy")
+        .unwrap()
+        .tree()
+        .unwrap()
+}
+
+fn node_id(tree: &NodesTree, name: &str) -> i32 {
+    tree.nodes_map
+        .values()
+        .find(|graph_node| graph_node.node.get_name() == name)
+        .map(|graph_node| graph_node.node.get_node_id())
+        .unwrap()
+}
+
+fn names(events: &[Event]) -> Vec<String> {
+    events
+        .iter()
+        .map(|event| serde_json::to_value(event).unwrap()["event"].to_string())
+        .map(|name| name.trim_matches('"').to_string())
+        .collect()
+}
+
+// The notebook with Heart and Lungs, opened by two processes with the tree loaded by both
+fn open_twice(path: &std::path::Path) -> (Workspace, Workspace) {
+    let mut first = Workspace::create(path).unwrap();
+    let tree = first
+        .create_group("Medicine")
+        .unwrap()
+        .create_subgroup("Anatomy")
+        .unwrap()
+        .tree()
+        .unwrap();
+    tree.create_node("Heart", None, None, NodeType::Regular)
+        .unwrap();
+    tree.create_node("Lungs", None, None, NodeType::Regular)
+        .unwrap();
+    let mut second = Workspace::open(path).unwrap();
+    anatomy(&mut second);
+    (first, second)
+}
+
+#[test]
+fn edits_of_another_process_are_applied_by_the_refresh() {
+    let file = TempFile::new("refresh.db");
+    let (mut first, mut second) = open_twice(&file.path);
+    let tree = anatomy(&mut first);
+    let heart = node_id(tree, "Heart");
+    let lungs = node_id(tree, "Lungs");
+    tree.update_name_and_description(heart, "Heart".into(), Some("Pumps the blood".into()))
+        .unwrap();
+    let ventricle = tree
+        .create_node("Ventricle", None, None, NodeType::Regular)
+        .unwrap()
+        .get_node_id();
+    tree.move_node(ventricle, Some(heart)).unwrap();
+    tree.delete_node(lungs, DeletionStrategy::RefuseIfHasChildren)
+        .unwrap();
+    // The own writes are already applied
+    assert!(first.refresh().unwrap().is_empty());
+
+    let events = second.subscribe();
+    let report = second.refresh().unwrap();
+    assert_eq!(report.nodes, [heart, lungs, ventricle]);
+    assert!(report.groups.is_empty() && report.subgroups.is_empty());
+    assert_eq!(
+        names(&events.try_iter().collect::<Vec<_>>()),
+        ["NodeDeleted", "NodeCreated", "NodeUpdated"]
+    );
+    let tree = anatomy(&mut second);
+    assert!(!tree.nodes_map.contains_key(&lungs));
+    assert_eq!(
+        tree.nodes_map[&heart].node.get_description(),
+        Some("Pumps the blood")
+    );
+    assert_eq!(tree.nodes_map[&ventricle].parent_node_id, Some(heart));
+    assert_eq!(tree.nodes_map[&heart].children, [ventricle]);
+    assert!(second.refresh().unwrap().is_empty());
+}
+
+#[test]
+fn stale_edits_are_refused_until_the_refresh() {
+    let file = TempFile::new("conflicts.db");
+    let (mut first, mut second) = open_twice(&file.path);
+    let tree = anatomy(&mut first);
+    let heart = node_id(tree, "Heart");
+    let lungs = node_id(tree, "Lungs");
+    tree.update_name_and_description(heart, "Heart".into(), Some("Pumps the blood".into()))
+        .unwrap();
+    tree.delete_node(lungs, DeletionStrategy::RefuseIfHasChildren)
+        .unwrap();
+
+    let tree = anatomy(&mut second);
+    match tree.update_name_and_description(heart, "Heart".into(), Some("Muscle".into())) {
+        Err(RelanotesError::EditConflicts(conflicts)) => {
+            assert_eq!(
+                (conflicts[0].node_id, conflicts[0].loaded_version),
+                (heart, 1)
+            );
+            let current = conflicts[0].current.as_ref().unwrap();
+            assert_eq!(current.description.as_deref(), Some("Pumps the blood"));
+        }
+        other => panic!("Unexpected result {:?}", other),
+    }
+    match tree.move_node(lungs, Some(heart)) {
+        Err(RelanotesError::EditConflicts(conflicts)) => {
+            let ids = conflicts.iter().map(|c| c.node_id).collect::<Vec<_>>();
+            assert_eq!(ids, [heart, lungs]);
+            assert!(conflicts[1].current.is_none());
+        }
+        other => panic!("Unexpected result {:?}", other),
+    }
+    // Neither the database nor the tree were changed
+    assert_eq!(tree.nodes_map[&heart].node.get_description(), None);
+
+    second.refresh().unwrap();
+    let tree = anatomy(&mut second);
+    tree.update_name_and_description(heart, "Heart".into(), Some("Muscle".into()))
+        .unwrap();
+    let report = first.refresh().unwrap();
+    assert_eq!(report.nodes, [heart]);
+    assert_eq!(
+        anatomy(&mut first).nodes_map[&heart].node.get_description(),
+        Some("Muscle")
+    );
+}
+
+#[test]
+fn groups_and_subgroups_of_another_process_are_refreshed() {
+    let storage = Arc::new(MemoryStorage::new());
+    let mut first = Workspace::from_storage(storage.clone());
+    let medicine = first.create_group("Medicine").unwrap();
+    let medicine_id = medicine.group.id;
+    let anatomy_id = medicine.create_subgroup("Anatomy").unwrap().subgroup.id;
+    let genetics_id = medicine.create_subgroup("Genetics").unwrap().subgroup.id;
+    let mut second = Workspace::from_storage(storage);
+    second
+        .group("Medicine")
+        .unwrap()
+        .subgroup("Anatomy")
+        .unwrap();
+
+    let medicine = first.group("Medicine").unwrap();
+    medicine.group.name = "Biology".into();
+    medicine.save().unwrap();
+    let anatomy = medicine.subgroup("Anatomy").unwrap();
+    anatomy.subgroup.name = "Human anatomy".into();
+    anatomy.save().unwrap();
+    medicine.subgroups.delete(genetics_id).unwrap();
+    let physics_id = first.create_group("Physics").unwrap().group.id;
+    assert!(first.refresh().unwrap().is_empty());
+
+    let report = second.refresh().unwrap();
+    assert_eq!(report.groups, [medicine_id, physics_id]);
+    assert_eq!(report.subgroups, [anatomy_id, genetics_id]);
+    let biology = second.group("Biology").unwrap();
+    assert!(biology.subgroup("Human anatomy").is_ok());
+    assert!(!biology.subgroups.subgroups_map.contains_key(&genetics_id));
+    assert!(second.group("Physics").is_ok());
+}
+
+#[test]
+fn pruned_changes_are_reloaded_by_the_refresh() {
+    let file = TempFile::new("pruned.db");
+    let (mut first, mut second) = open_twice(&file.path);
+    let tree = anatomy(&mut first);
+    let heart = node_id(tree, "Heart");
+    let lungs = node_id(tree, "Lungs");
+    tree.update_name_and_description(heart, "Heart".into(), Some("Pumps the blood".into()))
+        .unwrap();
+    tree.delete_node(lungs, DeletionStrategy::RefuseIfHasChildren)
+        .unwrap();
+    let physics_id = first.create_group("Physics").unwrap().group.id;
+    let last_change = first.storage().last_change().unwrap();
+    assert!(first.refresh().unwrap().is_empty());
+
+    // The changes not applied by the own model are kept
+    second.prune_changes(0).unwrap();
+    let pruned_change = second.storage().pruned_change().unwrap();
+    assert!(pruned_change > 0 && pruned_change < last_change);
+    assert!(!first.refresh().unwrap().reloaded);
+    // Unless they are older than the retained ones
+    first.prune_changes(1).unwrap();
+    assert_eq!(first.storage().pruned_change().unwrap(), last_change - 1);
+    assert_eq!(first.storage().last_change().unwrap(), last_change);
+    assert_eq!(first.storage().load_changes(0).unwrap().len(), 1);
+
+    let events = second.subscribe();
+    let report = second.refresh().unwrap();
+    assert!(report.reloaded);
+    assert_eq!(report.groups, [physics_id]);
+    assert!(names(&events.try_iter().collect::<Vec<_>>()).contains(&"GroupCreated".into()));
+    let tree = anatomy(&mut second);
+    assert_eq!(
+        tree.nodes_map[&heart].node.get_description(),
+        Some("Pumps the blood")
+    );
+    assert!(!tree.nodes_map.contains_key(&lungs));
+    assert!(!second.refresh().unwrap().reloaded);
+}
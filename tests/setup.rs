@@ -0,0 +1,106 @@
+use diesel::prelude::*;
+use diesel::SqliteConnection;
+use relanotes_rs::database_setup::{plan_database_setup, setup_database, SetupError};
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::node_types::{
+    NodeTypeDefinition, NodeTypesRegistry,
+};
+use relanotes_rs::storage::{SqliteStorage, Storage};
+
+fn new_storage() -> SqliteStorage {
+    SqliteStorage::new(SqliteConnection::establish(":memory:").unwrap()).unwrap()
+}
+
+fn migrations_count() -> usize {
+    std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/migrations"))
+        .unwrap()
+        .filter(|entry| entry.as_ref().unwrap().path().is_dir())
+        .count()
+}
+
+#[test]
+fn dry_run_reports_the_changes_without_making_them() {
+    let storage = new_storage();
+    let planned = plan_database_setup(&storage).unwrap();
+    assert_eq!(planned.schema_version, None);
+    assert_eq!(planned.migrations.len(), migrations_count());
+    assert_eq!(planned.seed_version, 0);
+    assert_eq!(planned.seeds, [1, 2]);
+    // Nothing was run, so the plan is the same
+    assert_eq!(plan_database_setup(&storage).unwrap(), planned);
+
+    assert_eq!(setup_database(&storage).unwrap(), planned);
+    let up_to_date = plan_database_setup(&storage).unwrap();
+    assert!(up_to_date.is_empty());
+    assert_eq!(
+        up_to_date.current_schema_version(),
+        planned.current_schema_version()
+    );
+    assert_eq!(up_to_date.seed_version, 2);
+}
+
+#[test]
+fn seed_data_is_added_once() {
+    let storage = new_storage();
+    setup_database(&storage).unwrap();
+    let mut regular = NodeTypeDefinition::built_in().remove(0);
+    regular.allows_description = !regular.allows_description;
+    regular.save(&storage).unwrap();
+
+    assert!(setup_database(&storage).unwrap().is_empty());
+    let registry = NodeTypesRegistry::load(&storage).unwrap();
+    let type_id = registry.get_type_id_by_value(&regular.value).unwrap();
+    assert_eq!(registry.get(type_id), Some(&regular));
+    assert_eq!(storage.seed_version().unwrap(), 2);
+}
+
+#[test]
+fn later_seeds_keep_the_other_rules_of_the_saved_types() {
+    let storage = new_storage();
+    setup_database(&storage).unwrap();
+    // The divisor of a database with only the first seed, with its rules changed by the user
+    let mut divisor = NodeTypeDefinition::built_in()
+        .into_iter()
+        .find(|definition| definition.value == "divisor")
+        .unwrap();
+    divisor.allows_description = true;
+    divisor.save(&storage).unwrap();
+    diesel::sql_query("delete from seed_versions where version = 2")
+        .execute(&*storage.connection())
+        .unwrap();
+
+    assert_eq!(setup_database(&storage).unwrap().seeds, [2]);
+    let registry = NodeTypesRegistry::load(&storage).unwrap();
+    let type_id = registry.get_type_id_by_value("divisor").unwrap();
+    let saved = registry.get(type_id).unwrap();
+    assert!(saved.allows_description);
+    assert!(saved.parent_types.contains("divisor"));
+    assert!(saved.child_types.contains("divisor"));
+    assert!(!saved.child_types.contains("sticky_notes"));
+}
+
+#[test]
+fn failed_migration_is_reported_and_rolled_back() {
+    let storage = new_storage();
+    // Created outside of the migrations, so the first one fails
+    diesel::sql_query("create table \"groups\" (\"id\" integer primary key)")
+        .execute(&*storage.connection())
+        .unwrap();
+
+    match plan_database_setup(&storage) {
+        Err(SetupError::Migration { version, .. }) => {
+            assert_eq!(version.as_deref(), Some("20191124100456"))
+        }
+        other => panic!("Unexpected result of the dry run {:?}", other),
+    }
+    match setup_database(&storage) {
+        Err(SetupError::Migration { version, .. }) => {
+            assert_eq!(version.as_deref(), Some("20191124100456"))
+        }
+        other => panic!("Unexpected result of the setup {:?}", other),
+    }
+    // Not even the table of the migrations is kept
+    diesel::sql_query("drop table \"groups\"")
+        .execute(&*storage.connection())
+        .unwrap();
+    assert_eq!(plan_database_setup(&storage).unwrap().schema_version, None);
+}
@@ -0,0 +1,240 @@
+mod common;
+
+use common::{
+    add_node, add_subgroup, assert_divisors_are_transparent_for_owners,
+    assert_foreign_keys_are_enforced, load_tree, setup, setup_in_memory,
+};
+use diesel::result::Error;
+use relanotes_rs::abstracts::Loadable;
+use relanotes_rs::errors::RelanotesError;
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::node_types::NodeTypesRegistry;
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::{NodeType, RelanotesValidationRejection};
+use relanotes_rs::groups_mod::Groups;
+use relanotes_rs::storage::{NewNode, Storage};
+use std::thread;
+
+fn new_node<'n>(
+    storage: &dyn Storage,
+    name: &'n str,
+    linked_to_id: Option<i32>,
+    subgroup_id: i32,
+    node_type: NodeType,
+) -> NewNode<'n> {
+    let type_id = NodeTypesRegistry::load(storage)
+        .unwrap()
+        .get_type_id(&node_type)
+        .unwrap();
+    NewNode {
+        name,
+        description: None,
+        type_id,
+        linked_to_id,
+        subgroup_id,
+    }
+}
+
+fn violation<T: std::fmt::Debug>(result: Result<T, Error>) -> String {
+    match result {
+        Err(Error::DatabaseError(_, info)) => info.message().to_string(),
+        result => panic!("Expected a constraint violation, got {:?}", result),
+    }
+}
+
+#[test]
+fn memory_storage_rejects_writes_like_the_triggers() {
+    let fixture = setup_in_memory();
+    let storage = &*fixture.storage;
+    let (subgroup_id, other_subgroup_id) = (fixture.subgroup_id, fixture.other_subgroup_id);
+    let heart = storage
+        .create_node(&new_node(
+            storage,
+            "Heart",
+            None,
+            subgroup_id,
+            NodeType::Regular,
+        ))
+        .unwrap()
+        .id;
+    let ventricle = storage
+        .create_node(&new_node(
+            storage,
+            "Ventricle",
+            Some(heart),
+            subgroup_id,
+            NodeType::Inherited,
+        ))
+        .unwrap()
+        .id;
+
+    let result = storage.create_node(&new_node(
+        storage,
+        "Heart",
+        None,
+        other_subgroup_id,
+        NodeType::Regular,
+    ));
+    assert_eq!(violation(result), "DuplicateRegularNode");
+    let result = storage.create_node(&new_node(
+        storage,
+        "Ventricle",
+        Some(heart),
+        subgroup_id,
+        NodeType::Inherited,
+    ));
+    assert_eq!(violation(result), "DuplicateInheritedNode");
+    let result = storage.create_node(&new_node(
+        storage,
+        "",
+        Some(heart),
+        subgroup_id,
+        NodeType::SymLink,
+    ));
+    assert_eq!(violation(result), "SymLinkToSameSubgroup");
+    let result = storage.set_linked_to_id(&[heart], Some(ventricle));
+    assert_eq!(violation(result), "CyclicLink");
+    assert_eq!(storage.get_node(heart).unwrap().unwrap().linked_to_id, None);
+}
+
+#[test]
+fn divisors_are_transparent_for_the_uniqueness_in_the_owner() {
+    assert_divisors_are_transparent_for_owners(&setup());
+    assert_divisors_are_transparent_for_owners(&setup_in_memory());
+}
+
+#[test]
+fn foreign_keys_are_enforced() {
+    assert_foreign_keys_are_enforced(&setup());
+    assert_foreign_keys_are_enforced(&setup_in_memory());
+}
+
+#[test]
+fn rejected_writes_are_mapped_to_validation_rejections_in_memory() {
+    let fixture = setup_in_memory();
+    let storage = &*fixture.storage;
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    // Written by another tree after this one was loaded
+    storage
+        .create_node(&new_node(
+            storage,
+            "Mnemonic",
+            Some(heart),
+            fixture.subgroup_id,
+            NodeType::StickyNotes,
+        ))
+        .unwrap();
+
+    let result = tree.create_node("Mnemonic", None, Some(heart), NodeType::StickyNotes);
+    assert!(matches!(
+        result,
+        Err(RelanotesError::ValidationRejection(
+            RelanotesValidationRejection::DuplicateStickyNote(ref name)
+        )) if name == "Mnemonic"
+    ));
+}
+
+#[test]
+fn failed_transactions_are_rolled_back() {
+    let sqlite = setup();
+    let memory = setup_in_memory();
+    let backends: [(&dyn Storage, i32); 2] = [
+        (&*sqlite.storage, sqlite.subgroup_id),
+        (&*memory.storage, memory.subgroup_id),
+    ];
+    for (storage, subgroup_id) in backends.iter().copied() {
+        let result = storage.transaction::<(), Error, _>(|| {
+            storage.create_node(&new_node(
+                storage,
+                "Heart",
+                None,
+                subgroup_id,
+                NodeType::Regular,
+            ))?;
+            // The nested transaction is committed, but the outer one is not
+            storage.transaction::<_, Error, _>(|| {
+                storage.create_node(&new_node(
+                    storage,
+                    "Lungs",
+                    None,
+                    subgroup_id,
+                    NodeType::Regular,
+                ))
+            })?;
+            Err(Error::RollbackTransaction)
+        });
+        assert_eq!(result, Err(Error::RollbackTransaction));
+        assert!(storage.load_nodes(None).unwrap().is_empty());
+    }
+}
+
+#[test]
+fn models_are_moved_to_other_threads() {
+    let fixture = setup();
+    let mut groups = Groups::new(fixture.storage.clone());
+    groups.load().unwrap();
+    let (group_id, subgroup_id) = (fixture.group_id, fixture.subgroup_id);
+    let other_tree = load_tree(&fixture.storage, fixture.other_subgroup_id);
+
+    let groups = thread::spawn(move || {
+        let subgroups = &mut groups.groups_map.get_mut(&group_id).unwrap().subgroups;
+        subgroups.load().unwrap();
+        let tree = &mut subgroups.subgroups_map.get_mut(&subgroup_id).unwrap().nodes;
+        tree.load().unwrap();
+        let heart = add_node(tree, "Heart", None, None, NodeType::Regular);
+        tree.update_name_and_description(heart, "Heart".into(), Some("Pumps the blood".into()))
+            .unwrap();
+        groups
+    })
+    .join()
+    .unwrap();
+    // The other tree shares the connection and sees the node of the other thread
+    let lungs = thread::spawn(move || {
+        let mut other_tree = other_tree;
+        let result = other_tree.create_node("Heart", None, None, NodeType::Regular);
+        assert!(matches!(
+            result,
+            Err(RelanotesError::ValidationRejection(
+                RelanotesValidationRejection::DuplicateRegularNode(_)
+            ))
+        ));
+        add_node(&mut other_tree, "Lungs", None, None, NodeType::Regular)
+    })
+    .join()
+    .unwrap();
+
+    let tree = &groups.get_subgroup_abstraction(subgroup_id).unwrap().nodes;
+    let heart = tree.resolve_path("Heart").unwrap();
+    assert_eq!(
+        tree.nodes_map[&heart].node.get_description(),
+        Some("Pumps the blood")
+    );
+    assert!(fixture.storage.get_node(lungs).unwrap().is_some());
+}
+
+#[test]
+fn rows_with_the_same_name_get_their_own_ids() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    // The divisors have no uniqueness scope
+    let first = add_node(&mut tree, "Chambers", None, Some(heart), NodeType::Divisor);
+    let second = add_node(&mut tree, "Chambers", None, Some(heart), NodeType::Divisor);
+    assert_ne!(first, second);
+    assert_eq!(
+        tree.get_node_loaded_children(&heart),
+        Some(vec![first, second])
+    );
+    assert_eq!(
+        load_tree(&fixture.storage, fixture.subgroup_id)
+            .nodes_map
+            .len(),
+        3
+    );
+
+    let mut groups = Groups::new(fixture.storage.clone());
+    let other_group_id = groups.create("Biology".into()).unwrap().group.id;
+    let subgroup_id = add_subgroup(&fixture.storage, other_group_id, "Anatomy");
+    assert_ne!(subgroup_id, fixture.subgroup_id);
+    let subgroup = fixture.storage.get_subgroup(subgroup_id).unwrap().unwrap();
+    assert_eq!(subgroup.group_id, other_group_id);
+}
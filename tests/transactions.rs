@@ -0,0 +1,216 @@
+mod common;
+
+use common::{add_node, load_tree, setup};
+use relanotes_rs::abstracts::Saveable;
+use relanotes_rs::errors::RelanotesError;
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::{
+    DeletionStrategy, NodeType, RelanotesValidationRejection,
+};
+use relanotes_rs::storage::Storage;
+use relanotes_rs::workspace::Workspace;
+
+#[test]
+fn committed_tree_transaction_updates_the_tree() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+
+    let heart = tree
+        .transaction::<_, RelanotesError, _>(|tree| {
+            let heart = add_node(tree, "Heart", None, None, NodeType::Regular);
+            add_node(tree, "Mnemonic", None, Some(heart), NodeType::StickyNotes);
+            Ok(heart)
+        })
+        .unwrap();
+
+    assert_eq!(tree.nodes_map.len(), 2);
+    assert_eq!(tree.get_node_loaded_children_count(&heart), Some(1));
+    assert_eq!(fixture.storage.load_nodes(None).unwrap().len(), 2);
+}
+
+#[test]
+fn failed_tree_transaction_changes_neither_the_tree_nor_the_database() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+
+    let result = tree.transaction(|tree| {
+        tree.update_name_and_description(heart, "Heart".into(), Some("Pumps the blood".into()))?;
+        let lungs = add_node(tree, "Lungs", None, None, NodeType::Regular);
+        add_node(tree, "Mnemonic", None, Some(lungs), NodeType::StickyNotes);
+        tree.create_node("Heart", None, None, NodeType::Regular)
+            .map(|_| ())
+    });
+
+    assert!(matches!(
+        result,
+        Err(RelanotesError::ValidationRejection(
+            RelanotesValidationRejection::DuplicateRegularNode(_)
+        ))
+    ));
+    assert_eq!(
+        tree.nodes_map.keys().copied().collect::<Vec<i32>>(),
+        vec![heart]
+    );
+    assert_eq!(tree.nodes_map[&heart].node.get_description(), None);
+    let nodes = fixture.storage.load_nodes(None).unwrap();
+    assert_eq!(nodes.len(), 1);
+    assert_eq!(nodes[0].description, None);
+}
+
+#[test]
+fn failed_workspace_transaction_keeps_both_subgroups() {
+    let mut workspace = Workspace::in_memory();
+    let medicine = workspace.create_group("Medicine").unwrap();
+    let anatomy = medicine.create_subgroup("Anatomy").unwrap();
+    let anatomy_id = anatomy.subgroup.id;
+    let heart = add_node(
+        anatomy.tree().unwrap(),
+        "Heart",
+        None,
+        None,
+        NodeType::Regular,
+    );
+    let physiology = medicine.create_subgroup("Physiology").unwrap();
+    let physiology_id = physiology.subgroup.id;
+    physiology.tree().unwrap();
+
+    let result = workspace.transaction(|workspace| {
+        workspace
+            .groups()?
+            .move_subtree(heart, physiology_id, None)?;
+        workspace
+            .group("Medicine")?
+            .subgroup("Physiology")?
+            .tree()?
+            .create_node("Heart", None, None, NodeType::Regular)
+            .map(|_| ())
+    });
+
+    assert!(matches!(
+        result,
+        Err(RelanotesError::ValidationRejection(
+            RelanotesValidationRejection::DuplicateRegularNode(_)
+        ))
+    ));
+    let groups = workspace.groups().unwrap();
+    let anatomy = &groups.get_subgroup_abstraction(anatomy_id).unwrap().nodes;
+    assert!(anatomy.nodes_map.contains_key(&heart));
+    let physiology = &groups
+        .get_subgroup_abstraction(physiology_id)
+        .unwrap()
+        .nodes;
+    assert!(physiology.nodes_map.is_empty());
+    let nodes = workspace.storage().load_nodes(None).unwrap();
+    assert_eq!(nodes.len(), 1);
+    assert_eq!(nodes[0].subgroup_id, anatomy_id);
+}
+
+#[test]
+fn failed_tree_transaction_restores_the_moved_and_deleted_nodes() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let lungs = add_node(&mut tree, "Lungs", None, None, NodeType::Regular);
+    let ventricle = add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+    let atrium = add_node(&mut tree, "Atrium", None, Some(heart), NodeType::Inherited);
+
+    let result = tree.transaction(|tree| {
+        tree.move_node(ventricle, Some(lungs))?;
+        tree.delete_node(atrium, DeletionStrategy::RefuseIfHasChildren)?;
+        tree.create_node("Lungs", None, None, NodeType::Regular)
+            .map(|_| ())
+    });
+
+    assert!(result.is_err());
+    assert_eq!(
+        tree.get_node_loaded_children(&heart).unwrap(),
+        [ventricle, atrium]
+    );
+    assert!(tree.get_node_loaded_children(&lungs).unwrap().is_empty());
+    assert_eq!(tree.nodes_map[&ventricle].unique_path, [heart, ventricle]);
+    // The versions are the ones of the database again, so the nodes can still be edited
+    tree.move_node(ventricle, Some(lungs)).unwrap();
+    tree.delete_node(atrium, DeletionStrategy::RefuseIfHasChildren)
+        .unwrap();
+}
+
+#[test]
+fn nested_transactions_are_undone_with_the_outer_one() {
+    let fixture = setup();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+
+    let result = tree.transaction::<(), _, _>(|tree| {
+        tree.transaction::<_, RelanotesError, _>(|tree| {
+            add_node(tree, "Ventricle", None, Some(heart), NodeType::Inherited);
+            Ok(())
+        })?;
+        // The failed inner transaction leaves the outer one as it was
+        let result = tree.transaction(|tree| {
+            add_node(tree, "Atrium", None, Some(heart), NodeType::Inherited);
+            tree.create_node("Heart", None, None, NodeType::Regular)
+                .map(|_| ())
+        });
+        assert!(result.is_err());
+        assert_eq!(tree.get_node_loaded_children_count(&heart), Some(1));
+        Err(RelanotesError::NodeNotFound(0))
+    });
+
+    assert!(matches!(result, Err(RelanotesError::NodeNotFound(0))));
+    assert_eq!(
+        tree.nodes_map.keys().copied().collect::<Vec<i32>>(),
+        vec![heart]
+    );
+    assert!(tree.get_node_loaded_children(&heart).unwrap().is_empty());
+    assert_eq!(fixture.storage.load_nodes(None).unwrap().len(), 1);
+}
+
+#[test]
+fn failed_workspace_transaction_restores_the_groups_and_subgroups() {
+    let mut workspace = Workspace::in_memory();
+    let medicine = workspace.create_group("Medicine").unwrap();
+    let medicine_id = medicine.group.id;
+    let anatomy = medicine.create_subgroup("Anatomy").unwrap();
+    let anatomy_id = anatomy.subgroup.id;
+    let heart = add_node(
+        anatomy.tree().unwrap(),
+        "Heart",
+        None,
+        None,
+        NodeType::Regular,
+    );
+
+    let result = workspace.transaction(|workspace| {
+        workspace.create_group("Biology")?;
+        let medicine = workspace.group("Medicine")?;
+        medicine.group.name = "Surgery".into();
+        medicine.save()?;
+        add_node(
+            medicine.subgroup("Anatomy")?.tree()?,
+            "Lungs",
+            None,
+            None,
+            NodeType::Regular,
+        );
+        medicine.subgroups.delete(anatomy_id)?;
+        Err::<(), _>(RelanotesError::NodeNotFound(0))
+    });
+
+    assert!(result.is_err());
+    let groups = workspace.groups().unwrap();
+    assert_eq!(
+        groups.groups_map.keys().copied().collect::<Vec<i32>>(),
+        vec![medicine_id]
+    );
+    assert_eq!(groups.groups_map[&medicine_id].group.name, "Medicine");
+    let anatomy = &groups.get_subgroup_abstraction(anatomy_id).unwrap().nodes;
+    assert_eq!(anatomy.get_roots(), [heart]);
+    assert_eq!(workspace.storage().load_groups().unwrap().len(), 1);
+    assert_eq!(workspace.storage().load_nodes(None).unwrap().len(), 1);
+}
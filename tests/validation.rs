@@ -0,0 +1,893 @@
+mod common;
+
+use common::{add_node, load_tree, setup_in_memory, Fixture};
+use relanotes_rs::errors::RelanotesError;
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::node_types::{
+    NamePolicy, NodeTypeDefinition, NodeTypesSet, UniquenessScope,
+};
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::{
+    NodeType, NodesTree, RelanotesValidationRejection,
+};
+use relanotes_rs::storage::{MemoryStorage, Storage};
+
+fn add_custom_types(storage: &dyn Storage) -> (NodeType, NodeType) {
+    let definition = NodeTypeDefinition {
+        name: "Definition".into(),
+        value: "definition".into(),
+        name_policy: NamePolicy::Required,
+        allows_description: false,
+        allows_root: false,
+        parent_types: NodeTypesSet::only(&["regular"]),
+        child_types: NodeTypesSet::only(&[]),
+        uniqueness_scope: UniquenessScope::Owner,
+    };
+    let separator = NodeTypeDefinition {
+        name: "Separator".into(),
+        value: "separator".into(),
+        name_policy: NamePolicy::Forbidden,
+        allows_description: false,
+        allows_root: true,
+        parent_types: NodeTypesSet::Any,
+        child_types: NodeTypesSet::only(&[]),
+        uniqueness_scope: UniquenessScope::None,
+    };
+    (
+        NodeType::Custom {
+            type_id: definition.save(storage).unwrap(),
+        },
+        NodeType::Custom {
+            type_id: separator.save(storage).unwrap(),
+        },
+    )
+}
+
+fn validate(
+    fixture: &Fixture<MemoryStorage>,
+    tree: &NodesTree,
+    id: Option<i32>,
+    name: &str,
+    description: Option<&str>,
+    linked_to_id: Option<i32>,
+    node_type: NodeType,
+) -> Result<(), RelanotesValidationRejection> {
+    tree.validate_node_mutation_or_creation(
+        id,
+        name,
+        description,
+        linked_to_id,
+        tree.get_subgroup_id(),
+        fixture.group_id,
+        node_type,
+    )
+}
+
+#[test]
+fn valid_nodes_are_accepted() {
+    let fixture = setup_in_memory();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let divisor = add_node(&mut tree, "Parts", None, Some(heart), NodeType::Divisor);
+    let ventricle = add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(divisor),
+        NodeType::Inherited,
+    );
+    add_node(
+        &mut tree,
+        "Mnemonic",
+        Some("text"),
+        Some(ventricle),
+        NodeType::StickyNotes,
+    );
+
+    assert!(validate(
+        &fixture,
+        &tree,
+        None,
+        "Lungs",
+        Some("text"),
+        None,
+        NodeType::Regular
+    )
+    .is_ok());
+    assert!(validate(
+        &fixture,
+        &tree,
+        Some(heart),
+        "Heart",
+        None,
+        None,
+        NodeType::Regular
+    )
+    .is_ok());
+    assert!(validate(
+        &fixture,
+        &tree,
+        Some(ventricle),
+        "Ventricle",
+        None,
+        Some(divisor),
+        NodeType::Inherited
+    )
+    .is_ok());
+}
+
+#[test]
+fn rejects_unknown_node_type() {
+    let fixture = setup_in_memory();
+    let tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let result = validate(
+        &fixture,
+        &tree,
+        None,
+        "Heart",
+        None,
+        None,
+        NodeType::Custom { type_id: 9999 },
+    );
+    assert!(matches!(
+        result,
+        Err(RelanotesValidationRejection::TechnicalError(_))
+    ));
+}
+
+#[test]
+fn rejects_mutating_other_subgroup() {
+    let fixture = setup_in_memory();
+    let tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let result = tree.validate_node_mutation_or_creation(
+        None,
+        "Heart",
+        None,
+        None,
+        fixture.other_subgroup_id,
+        fixture.group_id,
+        NodeType::Regular,
+    );
+    assert!(matches!(
+        result,
+        Err(RelanotesValidationRejection::TryingToMutateOtherSubgroup { .. })
+    ));
+}
+
+#[test]
+fn rejects_empty_name() {
+    let fixture = setup_in_memory();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    for id in &[None, Some(heart)] {
+        let result = validate(&fixture, &tree, *id, "", None, None, NodeType::Regular);
+        assert!(matches!(
+            result,
+            Err(RelanotesValidationRejection::EmptyName)
+        ));
+    }
+}
+
+#[test]
+fn rejects_node_linked_to_itself() {
+    let fixture = setup_in_memory();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let ventricle = add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+    let result = validate(
+        &fixture,
+        &tree,
+        Some(ventricle),
+        "Ventricle",
+        None,
+        Some(ventricle),
+        NodeType::Inherited,
+    );
+    assert!(
+        matches!(result, Err(RelanotesValidationRejection::LinkedToItself(id)) if id == ventricle)
+    );
+}
+
+#[test]
+fn rejects_duplicate_regular_node_in_the_group() {
+    let fixture = setup_in_memory();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let lungs = add_node(&mut tree, "Lungs", None, None, NodeType::Regular);
+    let other_tree = load_tree(&fixture.storage, fixture.other_subgroup_id);
+
+    let result = validate(
+        &fixture,
+        &other_tree,
+        None,
+        "Heart",
+        None,
+        None,
+        NodeType::Regular,
+    );
+    assert!(
+        matches!(result, Err(RelanotesValidationRejection::DuplicateRegularNode(ref name)) if name == "Heart")
+    );
+    let result = validate(
+        &fixture,
+        &tree,
+        Some(lungs),
+        "Heart",
+        None,
+        None,
+        NodeType::Regular,
+    );
+    assert!(matches!(
+        result,
+        Err(RelanotesValidationRejection::DuplicateRegularNode(_))
+    ));
+}
+
+#[test]
+fn rejects_sticky_note_without_owner() {
+    let fixture = setup_in_memory();
+    let tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let result = validate(
+        &fixture,
+        &tree,
+        None,
+        "Mnemonic",
+        None,
+        None,
+        NodeType::StickyNotes,
+    );
+    assert!(matches!(
+        result,
+        Err(RelanotesValidationRejection::StickyNoteWithoutOwner)
+    ));
+}
+
+#[test]
+fn rejects_invalid_sticky_note_owner() {
+    let fixture = setup_in_memory();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let divisor = add_node(&mut tree, "Parts", None, Some(heart), NodeType::Divisor);
+    let note = add_node(
+        &mut tree,
+        "Mnemonic",
+        None,
+        Some(heart),
+        NodeType::StickyNotes,
+    );
+
+    let result = validate(
+        &fixture,
+        &tree,
+        None,
+        "Mnemonic",
+        None,
+        Some(divisor),
+        NodeType::StickyNotes,
+    );
+    assert!(matches!(
+        result,
+        Err(RelanotesValidationRejection::InvalidStickyNoteOwner)
+    ));
+    // The owner has to be loaded in the subgroup
+    let result = validate(
+        &fixture,
+        &tree,
+        Some(note),
+        "Mnemonic",
+        None,
+        Some(9999),
+        NodeType::StickyNotes,
+    );
+    assert!(matches!(
+        result,
+        Err(RelanotesValidationRejection::InvalidStickyNoteOwner)
+    ));
+}
+
+#[test]
+fn rejects_duplicate_sticky_note() {
+    let fixture = setup_in_memory();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    add_node(
+        &mut tree,
+        "Mnemonic",
+        None,
+        Some(heart),
+        NodeType::StickyNotes,
+    );
+    let other = add_node(&mut tree, "Other", None, Some(heart), NodeType::StickyNotes);
+
+    let result = validate(
+        &fixture,
+        &tree,
+        None,
+        "Mnemonic",
+        None,
+        Some(heart),
+        NodeType::StickyNotes,
+    );
+    assert!(
+        matches!(result, Err(RelanotesValidationRejection::DuplicateStickyNote(ref name)) if name == "Mnemonic")
+    );
+    let result = validate(
+        &fixture,
+        &tree,
+        Some(other),
+        "Mnemonic",
+        None,
+        Some(heart),
+        NodeType::StickyNotes,
+    );
+    assert!(matches!(
+        result,
+        Err(RelanotesValidationRejection::DuplicateStickyNote(_))
+    ));
+}
+
+#[test]
+fn rejects_inherited_node_without_owner() {
+    let fixture = setup_in_memory();
+    let tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let result = validate(
+        &fixture,
+        &tree,
+        None,
+        "Ventricle",
+        None,
+        None,
+        NodeType::Inherited,
+    );
+    assert!(matches!(
+        result,
+        Err(RelanotesValidationRejection::InheritedNodeWithoutOwner)
+    ));
+}
+
+#[test]
+fn rejects_invalid_inherited_node_owner() {
+    let fixture = setup_in_memory();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let note = add_node(
+        &mut tree,
+        "Mnemonic",
+        None,
+        Some(heart),
+        NodeType::StickyNotes,
+    );
+    let root_divisor = add_node(&mut tree, "Organs", None, None, NodeType::Divisor);
+
+    // Sticky notes can only have sticky notes as children
+    let result = validate(
+        &fixture,
+        &tree,
+        None,
+        "Ventricle",
+        None,
+        Some(note),
+        NodeType::Inherited,
+    );
+    assert!(matches!(
+        result,
+        Err(RelanotesValidationRejection::InvalidInheritedNodeOwner)
+    ));
+    // The divisor without an owner doesn't own anything
+    let result = validate(
+        &fixture,
+        &tree,
+        None,
+        "Ventricle",
+        None,
+        Some(root_divisor),
+        NodeType::Inherited,
+    );
+    assert!(matches!(
+        result,
+        Err(RelanotesValidationRejection::InvalidInheritedNodeOwner)
+    ));
+}
+
+#[test]
+fn rejects_duplicate_inherited_node() {
+    let fixture = setup_in_memory();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let divisor = add_node(&mut tree, "Parts", None, Some(heart), NodeType::Divisor);
+    add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+    let atrium = add_node(
+        &mut tree,
+        "Atrium",
+        None,
+        Some(divisor),
+        NodeType::Inherited,
+    );
+
+    // Divisors are transparent for the namespace of the owner
+    let result = validate(
+        &fixture,
+        &tree,
+        None,
+        "Ventricle",
+        None,
+        Some(divisor),
+        NodeType::Inherited,
+    );
+    assert!(
+        matches!(result, Err(RelanotesValidationRejection::DuplicateInheritedNode(ref name)) if name == "Ventricle")
+    );
+    let result = validate(
+        &fixture,
+        &tree,
+        Some(atrium),
+        "Ventricle",
+        None,
+        Some(divisor),
+        NodeType::Inherited,
+    );
+    assert!(matches!(
+        result,
+        Err(RelanotesValidationRejection::DuplicateInheritedNode(_))
+    ));
+    // And so are the divisors among the children of the divisors
+    let sides = add_node(&mut tree, "Sides", None, Some(divisor), NodeType::Divisor);
+    let result = tree.create_node("Atrium", None, Some(sides), NodeType::Inherited);
+    assert!(matches!(
+        result,
+        Err(RelanotesError::ValidationRejection(
+            RelanotesValidationRejection::DuplicateInheritedNode(_)
+        ))
+    ));
+    add_node(&mut tree, "Valve", None, Some(sides), NodeType::Inherited);
+    let result = validate(
+        &fixture,
+        &tree,
+        Some(atrium),
+        "Valve",
+        None,
+        Some(divisor),
+        NodeType::Inherited,
+    );
+    assert!(matches!(
+        result,
+        Err(RelanotesValidationRejection::DuplicateInheritedNode(_))
+    ));
+}
+
+#[test]
+fn rejects_symlink_with_name() {
+    let fixture = setup_in_memory();
+    let tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let result = validate(
+        &fixture,
+        &tree,
+        None,
+        "Heart",
+        None,
+        None,
+        NodeType::SymLink,
+    );
+    assert!(matches!(
+        result,
+        Err(RelanotesValidationRejection::SymLinkWithName)
+    ));
+}
+
+#[test]
+fn rejects_symlink_with_description() {
+    let fixture = setup_in_memory();
+    let tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let result = validate(
+        &fixture,
+        &tree,
+        None,
+        "",
+        Some("text"),
+        None,
+        NodeType::SymLink,
+    );
+    assert!(matches!(
+        result,
+        Err(RelanotesValidationRejection::SymLinkWithDescription)
+    ));
+}
+
+#[test]
+fn rejects_symlink_without_owner() {
+    let fixture = setup_in_memory();
+    let tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let result = validate(&fixture, &tree, None, "", None, None, NodeType::SymLink);
+    assert!(matches!(
+        result,
+        Err(RelanotesValidationRejection::SymLinkWithoutOwner)
+    ));
+}
+
+#[test]
+fn rejects_invalid_symlink_owner() {
+    let fixture = setup_in_memory();
+    let mut other_tree = load_tree(&fixture.storage, fixture.other_subgroup_id);
+    let heart = add_node(&mut other_tree, "Heart", None, None, NodeType::Regular);
+    let ventricle = add_node(
+        &mut other_tree,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+    let note = add_node(
+        &mut other_tree,
+        "Mnemonic",
+        None,
+        Some(heart),
+        NodeType::StickyNotes,
+    );
+    let divisor = add_node(
+        &mut other_tree,
+        "Parts",
+        None,
+        Some(heart),
+        NodeType::Divisor,
+    );
+    let tree = load_tree(&fixture.storage, fixture.subgroup_id);
+
+    assert!(validate(
+        &fixture,
+        &tree,
+        None,
+        "",
+        None,
+        Some(heart),
+        NodeType::SymLink
+    )
+    .is_ok());
+    assert!(validate(
+        &fixture,
+        &tree,
+        None,
+        "",
+        None,
+        Some(ventricle),
+        NodeType::SymLink
+    )
+    .is_ok());
+    // Missing source and sources that are not regular or inherited
+    for source in &[9999, note, divisor] {
+        let result = validate(
+            &fixture,
+            &tree,
+            None,
+            "",
+            None,
+            Some(*source),
+            NodeType::SymLink,
+        );
+        assert!(matches!(
+            result,
+            Err(RelanotesValidationRejection::InvalidSymLinkOwner)
+        ));
+    }
+}
+
+#[test]
+fn rejects_symlink_to_same_subgroup() {
+    let fixture = setup_in_memory();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let result = validate(
+        &fixture,
+        &tree,
+        None,
+        "",
+        None,
+        Some(heart),
+        NodeType::SymLink,
+    );
+    assert!(matches!(
+        result,
+        Err(RelanotesValidationRejection::SymLinkToSameSubgroup)
+    ));
+}
+
+#[test]
+fn rejects_duplicate_symlink() {
+    let fixture = setup_in_memory();
+    let mut other_tree = load_tree(&fixture.storage, fixture.other_subgroup_id);
+    let heart = add_node(&mut other_tree, "Heart", None, None, NodeType::Regular);
+    let lungs = add_node(&mut other_tree, "Lungs", None, None, NodeType::Regular);
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let symlink = add_node(&mut tree, "", None, Some(heart), NodeType::SymLink);
+
+    let result = validate(
+        &fixture,
+        &tree,
+        None,
+        "",
+        None,
+        Some(heart),
+        NodeType::SymLink,
+    );
+    assert!(
+        matches!(result, Err(RelanotesValidationRejection::DuplicateSymLink(id)) if id == heart)
+    );
+    // The symlink doesn't collide with itself
+    assert!(validate(
+        &fixture,
+        &tree,
+        Some(symlink),
+        "",
+        None,
+        Some(heart),
+        NodeType::SymLink
+    )
+    .is_ok());
+    let other_symlink = add_node(&mut tree, "", None, Some(lungs), NodeType::SymLink);
+    let result = validate(
+        &fixture,
+        &tree,
+        Some(other_symlink),
+        "",
+        None,
+        Some(heart),
+        NodeType::SymLink,
+    );
+    assert!(matches!(
+        result,
+        Err(RelanotesValidationRejection::DuplicateSymLink(_))
+    ));
+}
+
+#[test]
+fn rejects_divisor_with_description() {
+    let fixture = setup_in_memory();
+    let tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let result = validate(
+        &fixture,
+        &tree,
+        None,
+        "Parts",
+        Some("text"),
+        None,
+        NodeType::Divisor,
+    );
+    assert!(matches!(
+        result,
+        Err(RelanotesValidationRejection::DivisorWithDescription)
+    ));
+}
+
+#[test]
+fn rejects_invalid_divisor_owner() {
+    let fixture = setup_in_memory();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let note = add_node(
+        &mut tree,
+        "Mnemonic",
+        None,
+        Some(heart),
+        NodeType::StickyNotes,
+    );
+    let divisor = add_node(&mut tree, "Parts", None, Some(heart), NodeType::Divisor);
+
+    let result = validate(
+        &fixture,
+        &tree,
+        None,
+        "Sides",
+        None,
+        Some(note),
+        NodeType::Divisor,
+    );
+    assert!(matches!(
+        result,
+        Err(RelanotesValidationRejection::InvalidDivisorOwner)
+    ));
+    // Divisors can divide the children of other divisors
+    assert!(validate(
+        &fixture,
+        &tree,
+        None,
+        "Sides",
+        None,
+        Some(divisor),
+        NodeType::Divisor,
+    )
+    .is_ok());
+}
+
+#[test]
+fn rejects_custom_node_with_name() {
+    let fixture = setup_in_memory();
+    let (_, separator) = add_custom_types(&*fixture.storage);
+    let tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    assert!(validate(&fixture, &tree, None, "", None, None, separator).is_ok());
+    let result = validate(&fixture, &tree, None, "Line", None, None, separator);
+    assert!(
+        matches!(result, Err(RelanotesValidationRejection::NameNotAllowed(ref t)) if t == "separator")
+    );
+}
+
+#[test]
+fn rejects_custom_node_with_description() {
+    let fixture = setup_in_memory();
+    let (definition, _) = add_custom_types(&*fixture.storage);
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let result = validate(
+        &fixture,
+        &tree,
+        None,
+        "Pump",
+        Some("text"),
+        Some(heart),
+        definition,
+    );
+    assert!(
+        matches!(result, Err(RelanotesValidationRejection::DescriptionNotAllowed(ref t)) if t == "definition")
+    );
+}
+
+#[test]
+fn rejects_custom_node_without_owner() {
+    let fixture = setup_in_memory();
+    let (definition, _) = add_custom_types(&*fixture.storage);
+    let tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let result = validate(&fixture, &tree, None, "Pump", None, None, definition);
+    assert!(
+        matches!(result, Err(RelanotesValidationRejection::NodeWithoutOwner(ref t)) if t == "definition")
+    );
+}
+
+#[test]
+fn rejects_invalid_custom_node_owner() {
+    let fixture = setup_in_memory();
+    let (definition, separator) = add_custom_types(&*fixture.storage);
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let ventricle = add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+    let pump = add_node(&mut tree, "Pump", None, Some(heart), definition);
+
+    // Not in the parent types of the definition
+    let result = validate(
+        &fixture,
+        &tree,
+        None,
+        "Chamber",
+        None,
+        Some(ventricle),
+        definition,
+    );
+    assert!(
+        matches!(result, Err(RelanotesValidationRejection::InvalidNodeOwner(ref t)) if t == "definition")
+    );
+    // The definition doesn't allow any children
+    let result = validate(&fixture, &tree, None, "", None, Some(pump), separator);
+    assert!(
+        matches!(result, Err(RelanotesValidationRejection::InvalidNodeOwner(ref t)) if t == "separator")
+    );
+}
+
+#[test]
+fn rejects_duplicate_custom_node() {
+    let fixture = setup_in_memory();
+    let (definition, _) = add_custom_types(&*fixture.storage);
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let lungs = add_node(&mut tree, "Lungs", None, None, NodeType::Regular);
+    add_node(&mut tree, "Pump", None, Some(heart), definition);
+    let organ = add_node(&mut tree, "Organ", None, Some(heart), definition);
+
+    // Another owner has its own namespace
+    assert!(validate(&fixture, &tree, None, "Pump", None, Some(lungs), definition).is_ok());
+    let result = validate(
+        &fixture,
+        &tree,
+        Some(organ),
+        "Pump",
+        None,
+        Some(heart),
+        definition,
+    );
+    assert!(matches!(
+        result,
+        Err(RelanotesValidationRejection::DuplicateNode { ref node_type, ref name })
+            if node_type == "definition" && name == "Pump"
+    ));
+}
+
+#[test]
+fn create_node_rejects_invalid_nodes() {
+    let fixture = setup_in_memory();
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+
+    let result = tree.create_node("Heart", None, None, NodeType::Regular);
+    assert!(matches!(
+        result,
+        Err(RelanotesError::ValidationRejection(
+            RelanotesValidationRejection::DuplicateRegularNode(_)
+        ))
+    ));
+    let result = tree.create_node("Ventricle", None, None, NodeType::Inherited);
+    assert!(matches!(
+        result,
+        Err(RelanotesError::ValidationRejection(
+            RelanotesValidationRejection::InheritedNodeWithoutOwner
+        ))
+    ));
+    let count = fixture.storage.load_nodes(None).unwrap().len();
+    assert_eq!(count, 1);
+    assert_eq!(tree.nodes_map.len(), 1);
+}
+
+#[test]
+fn renaming_rejects_invalid_names() {
+    let fixture = setup_in_memory();
+    let (_, separator) = add_custom_types(&*fixture.storage);
+    let mut tree = load_tree(&fixture.storage, fixture.subgroup_id);
+    let heart = add_node(&mut tree, "Heart", None, None, NodeType::Regular);
+    let divisor = add_node(&mut tree, "Parts", None, Some(heart), NodeType::Divisor);
+    add_node(
+        &mut tree,
+        "Ventricle",
+        None,
+        Some(heart),
+        NodeType::Inherited,
+    );
+    let atrium = add_node(
+        &mut tree,
+        "Atrium",
+        None,
+        Some(divisor),
+        NodeType::Inherited,
+    );
+    let line = add_node(&mut tree, "", None, None, separator);
+
+    let result = tree.update_name_and_description(heart, "".into(), None);
+    assert!(matches!(
+        result,
+        Err(RelanotesError::ValidationRejection(
+            RelanotesValidationRejection::EmptyName
+        ))
+    ));
+    // Divisors are transparent for the namespace of the owner
+    let result = tree.update_name_and_description(atrium, "Ventricle".into(), None);
+    assert!(matches!(
+        result,
+        Err(RelanotesError::ValidationRejection(
+            RelanotesValidationRejection::DuplicateInheritedNode(_)
+        ))
+    ));
+    let result = tree.update_name_and_description(line, "Line".into(), None);
+    assert!(matches!(
+        result,
+        Err(RelanotesError::ValidationRejection(
+            RelanotesValidationRejection::NameNotAllowed(ref t)
+        )) if t == "separator"
+    ));
+    assert_eq!(tree.nodes_map[&atrium].node.get_name(), "Atrium");
+    let atrium_row = fixture.storage.get_node(atrium).unwrap().unwrap();
+    assert_eq!(atrium_row.name, "Atrium");
+
+    tree.update_name_and_description(atrium, "Left atrium".into(), Some("Chamber".into()))
+        .unwrap();
+    assert_eq!(tree.nodes_map[&atrium].node.get_name(), "Left atrium");
+}
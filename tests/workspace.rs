@@ -0,0 +1,158 @@
+mod common;
+
+use common::TempFile;
+use relanotes_rs::errors::RelanotesError;
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::node_types::{
+    NamePolicy, NodeTypeDefinition, NodeTypesSet, UniquenessScope,
+};
+use relanotes_rs::groups_mod::subgroups_mod::nodes_mod::NodeType;
+use relanotes_rs::workspace::Workspace;
+
+#[test]
+fn opened_workspace_is_set_up_and_loaded_on_access() {
+    let file = TempFile::new("notebook.db");
+    {
+        let mut workspace = Workspace::create(&file.path).unwrap();
+        let tree = workspace
+            .create_group("Medicine")
+            .unwrap()
+            .create_subgroup("Anatomy")
+            .unwrap()
+            .tree()
+            .unwrap();
+        let heart = tree
+            .create_node("Heart", Some("Pumps the blood"), None, NodeType::Regular)
+            .unwrap()
+            .get_node_id();
+        tree.create_node("Left ventricle", None, Some(heart), NodeType::Inherited)
+            .unwrap();
+    }
+
+    let mut workspace = Workspace::open(&file.path).unwrap();
+    let anatomy = workspace
+        .group("Medicine")
+        .unwrap()
+        .subgroup("Anatomy")
+        .unwrap();
+    assert!(!anatomy.nodes.loaded);
+    let heart = anatomy.node_by_path("Heart").unwrap();
+    assert_eq!(heart.get_description(), Some("Pumps the blood"));
+    let ventricle = anatomy.node_by_path("Heart > Left ventricle").unwrap();
+    assert_eq!(ventricle.get_node_type(), NodeType::Inherited);
+}
+
+#[test]
+fn missing_names_are_reported() {
+    let mut workspace = Workspace::in_memory();
+    workspace
+        .create_group("Medicine")
+        .unwrap()
+        .create_subgroup("Anatomy")
+        .unwrap();
+
+    assert!(matches!(
+        workspace.group("Physics"),
+        Err(RelanotesError::GroupNameNotFound(ref name)) if name == "Physics"
+    ));
+    let medicine = workspace.group("Medicine").unwrap();
+    assert!(matches!(
+        medicine.subgroup("Physiology"),
+        Err(RelanotesError::SubGroupNameNotFound(ref name)) if name == "Physiology"
+    ));
+    assert!(matches!(
+        medicine.subgroup("Anatomy").unwrap().node_by_path("Heart"),
+        Err(RelanotesError::PathResolution(_))
+    ));
+}
+
+#[test]
+fn saved_node_types_are_available_in_loaded_trees() {
+    let mut workspace = Workspace::in_memory();
+    let medicine = workspace.create_group("Medicine").unwrap();
+    medicine.create_subgroup("Anatomy").unwrap().tree().unwrap();
+    medicine
+        .create_subgroup("Physiology")
+        .unwrap()
+        .tree()
+        .unwrap();
+
+    let type_id = workspace
+        .save_node_type(&NodeTypeDefinition {
+            name: "Separator".into(),
+            value: "separator".into(),
+            name_policy: NamePolicy::Forbidden,
+            allows_description: false,
+            allows_root: true,
+            parent_types: NodeTypesSet::Any,
+            child_types: NodeTypesSet::only(&[]),
+            uniqueness_scope: UniquenessScope::None,
+        })
+        .unwrap();
+
+    for subgroup in ["Anatomy", "Physiology"] {
+        let tree = workspace
+            .group("Medicine")
+            .unwrap()
+            .subgroup(subgroup)
+            .unwrap()
+            .tree()
+            .unwrap();
+        assert!(tree
+            .create_node("", None, None, NodeType::Custom { type_id })
+            .is_ok());
+    }
+}
+
+#[test]
+fn node_types_saved_in_failed_transactions_are_forgotten() {
+    let mut workspace = Workspace::in_memory();
+    let medicine = workspace.create_group("Medicine").unwrap();
+    medicine.create_subgroup("Anatomy").unwrap().tree().unwrap();
+    let separator = NodeTypeDefinition {
+        name: "Separator".into(),
+        value: "separator".into(),
+        name_policy: NamePolicy::Forbidden,
+        allows_description: false,
+        allows_root: true,
+        parent_types: NodeTypesSet::Any,
+        child_types: NodeTypesSet::only(&[]),
+        uniqueness_scope: UniquenessScope::None,
+    };
+
+    let result = workspace.transaction(|workspace| {
+        workspace.save_node_type(&separator)?;
+        Err::<(), _>(RelanotesError::NodeNotFound(0))
+    });
+    assert!(result.is_err());
+
+    // Neither the loaded trees nor the ones loaded later know the type
+    let medicine = workspace.group("Medicine").unwrap();
+    let anatomy = medicine.subgroup("Anatomy").unwrap().tree().unwrap();
+    assert_eq!(
+        anatomy.get_node_types().get_type_id_by_value("separator"),
+        None
+    );
+    let physiology = medicine
+        .create_subgroup("Physiology")
+        .unwrap()
+        .tree()
+        .unwrap();
+    assert_eq!(
+        physiology
+            .get_node_types()
+            .get_type_id_by_value("separator"),
+        None
+    );
+
+    let type_id = workspace.save_node_type(&separator).unwrap();
+    let anatomy = workspace
+        .group("Medicine")
+        .unwrap()
+        .subgroup("Anatomy")
+        .unwrap()
+        .tree()
+        .unwrap();
+    assert!(anatomy
+        .create_node("", None, None, NodeType::Custom { type_id })
+        .is_ok());
+}